@@ -8,6 +8,7 @@
 //
 
 use std::fmt::{self, Display};
+use std::sync::Arc;
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum NativeType {
@@ -15,10 +16,74 @@ pub enum NativeType {
     U16,
 }
 
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct StructField {
+    pub name: Arc<String>,
+    pub base_type: BaseType,
+    /// Number of bytes from the start of the struct to this field
+    pub offset: usize,
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct StructType {
+    pub name: Arc<String>,
+    pub fields: Vec<StructField>,
+}
+
+impl StructType {
+    /// Lays out the given fields one after another in declaration order. All of the field types must have a size.
+    pub fn new(name: Arc<String>, fields: Vec<(Arc<String>, BaseType)>) -> StructType {
+        let mut offset = 0;
+        let mut struct_fields = Vec::new();
+        for (field_name, base_type) in fields {
+            let size = base_type.size().unwrap();
+            struct_fields.push(StructField {
+                name: field_name,
+                base_type: base_type,
+                offset: offset,
+            });
+            offset += size;
+        }
+        StructType {
+            name: name,
+            fields: struct_fields,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&StructField> {
+        self.fields.iter().find(|field| *field.name == name)
+    }
+
+    pub fn size(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.base_type.size().unwrap())
+            .sum()
+    }
+}
+
+/// A group of named constants. Enum values share the representation of their base type,
+/// but they can only be mixed with plain integers through an explicit cast.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct EnumType {
+    pub name: Arc<String>,
+    pub base_type: BaseType,
+}
+
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub enum BaseType {
+    /// A fixed number of values stored one after another
+    Array(Box<BaseType>, usize),
     Bool,
+    Enum(Arc<EnumType>),
+    /// The address of a function that takes the given parameter types and returns the given type
+    Function(Vec<BaseType>, Box<BaseType>),
+    I16,
+    I8,
+    /// A type that has been referred to by name, but hasn't been looked up yet
+    Named(Arc<String>),
     Pointer(Box<BaseType>),
+    Struct(Arc<StructType>),
     U16,
     U8,
     Void,
@@ -28,8 +93,18 @@ impl Display for BaseType {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         use self::BaseType::*;
         match *self {
+            Array(ref inner, count) => write!(f, "[{}; {}]", inner, count),
             Bool => write!(f, "bool"),
+            Enum(ref enum_type) => write!(f, "{}", enum_type.name),
+            Function(ref parameters, ref return_type) => {
+                let parameters: Vec<String> = parameters.iter().map(|parameter| parameter.to_string()).collect();
+                write!(f, "fn({}): {}", parameters.join(", "), return_type)
+            }
+            I16 => write!(f, "i16"),
+            I8 => write!(f, "i8"),
+            Named(ref name) => write!(f, "{}", name),
             Pointer(ref inner) => write!(f, "&[{}]", inner),
+            Struct(ref struct_type) => write!(f, "{}", struct_type.name),
             U16 => write!(f, "u16"),
             U8 => write!(f, "u8"),
             Void => write!(f, "void"),
@@ -41,28 +116,67 @@ impl BaseType {
     pub fn as_native(&self) -> Option<NativeType> {
         use self::BaseType::*;
         match *self {
-            U8 | Bool => Some(NativeType::U8),
-            U16 | Pointer(_) => Some(NativeType::U16),
-            Void => None,
+            U8 | I8 | Bool => Some(NativeType::U8),
+            U16 | I16 | Pointer(_) | Function(_, _) => Some(NativeType::U16),
+            Enum(ref enum_type) => enum_type.base_type.as_native(),
+            Array(_, _) | Named(_) | Struct(_) | Void => None,
         }
     }
 
     pub fn is_pointer(&self) -> bool {
-        self.can_index()
+        match *self {
+            BaseType::Pointer(_) => true,
+            _ => false,
+        }
     }
 
-    pub fn can_index(&self) -> bool {
+    pub fn is_array(&self) -> bool {
         match *self {
-            BaseType::Pointer(_) => true,
+            BaseType::Array(_, _) => true,
             _ => false,
         }
     }
 
+    pub fn is_function(&self) -> bool {
+        match *self {
+            BaseType::Function(_, _) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_enum(&self) -> bool {
+        match *self {
+            BaseType::Enum(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn can_index(&self) -> bool {
+        self.is_pointer() || self.is_array()
+    }
+
     pub fn can_index_array(&self) -> bool {
         use self::BaseType::*;
         match *self {
-            Bool | Pointer(_) | Void => false,
-            U8 | U16 => true,
+            Array(_, _) | Bool | Enum(_) | Function(_, _) | Named(_) | Pointer(_) | Struct(_) | Void => false,
+            I8 | I16 | U8 | U16 => true,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        use self::BaseType::*;
+        match *self {
+            I8 | I16 | U8 | U16 => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        use self::BaseType::*;
+        match *self {
+            I8 | I16 => true,
+            Enum(ref enum_type) => enum_type.base_type.is_signed(),
+            _ => false,
         }
     }
 
@@ -72,6 +186,11 @@ impl BaseType {
             true
         } else {
             match *self {
+                // Arrays decay into a pointer to their first element
+                Array(ref inner, _) => match *into {
+                    Pointer(ref pointee) => inner == pointee,
+                    _ => false,
+                },
                 Pointer(_) => match *into {
                     U16 => true,
                     _ => false,
@@ -81,14 +200,19 @@ impl BaseType {
                     _ => false,
                 },
                 U8 => match *into {
-                    Pointer(_) | U16 => true,
+                    Pointer(_) | U16 | I16 => true,
+                    _ => false,
+                },
+                I8 => match *into {
+                    I16 => true,
                     _ => false,
                 },
+                I16 => false,
                 Bool => match *into {
                     U8 | U16 => true,
                     _ => false,
                 },
-                Void => false,
+                Enum(_) | Function(_, _) | Named(_) | Struct(_) | Void => false,
             }
         }
     }
@@ -99,23 +223,33 @@ impl BaseType {
             true
         } else {
             match *self {
+                Array(_, _) => self.can_assign_into(into),
                 Pointer(_) => match *into {
                     U16 | U8 | Bool => true,
                     _ => false,
                 },
                 U16 => match *into {
-                    Pointer(_) | U8 | Bool => true,
+                    Enum(_) | Function(_, _) | Pointer(_) | U8 | I8 | I16 | Bool => true,
                     _ => false,
                 },
                 U8 => match *into {
-                    Pointer(_) | U16 | Bool => true,
+                    Enum(_) | Pointer(_) | U16 | I8 | I16 | Bool => true,
+                    _ => false,
+                },
+                I16 | I8 => match *into {
+                    Enum(_) | U8 | U16 | I8 | I16 | Bool => true,
                     _ => false,
                 },
                 Bool => match *into {
-                    U8 | U16 => true,
+                    U8 | U16 | I8 | I16 => true,
                     _ => false,
                 },
-                Void => false,
+                Enum(_) => into.is_integer(),
+                Function(_, _) => match *into {
+                    U16 | Bool => true,
+                    _ => false,
+                },
+                Named(_) | Struct(_) | Void => false,
             }
         }
     }
@@ -134,15 +268,22 @@ impl BaseType {
                     Pointer(_) => true,
                     _ => false,
                 },
-                U8 | Bool | Void => false,
+                Array(_, _) | I8 | I16 | U8 | Bool | Enum(_) | Function(_, _) | Named(_) | Struct(_) | Void => false,
             }
         }
     }
 
+    pub fn struct_type(&self) -> Option<&Arc<StructType>> {
+        match *self {
+            BaseType::Struct(ref struct_type) => Some(struct_type),
+            _ => None,
+        }
+    }
+
     pub fn underlying_type(&self) -> Option<&BaseType> {
         use self::BaseType::*;
         match *self {
-            Pointer(ref base_type) => Some(base_type),
+            Array(ref base_type, _) | Pointer(ref base_type) => Some(base_type),
             _ => None,
         }
     }
@@ -150,14 +291,19 @@ impl BaseType {
     pub fn size(&self) -> Option<usize> {
         use self::BaseType::*;
         match *self {
-            U8 | Bool => Some(1),
-            U16 | Pointer(_) => Some(2),
-            Void => None,
+            U8 | I8 | Bool => Some(1),
+            U16 | I16 | Pointer(_) | Function(_, _) => Some(2),
+            Enum(ref enum_type) => enum_type.base_type.size(),
+            Array(ref inner, count) => inner.size().map(|size| size * count),
+            Struct(ref struct_type) => Some(struct_type.size()),
+            Named(_) | Void => None,
         }
     }
 
     pub fn choose_type(left: &BaseType, right: &BaseType) -> Option<BaseType> {
-        if left == right {
+        if left.struct_type().is_some() || right.struct_type().is_some() || left.is_array() || right.is_array() {
+            None
+        } else if left == right {
             Some(left.clone())
         } else if left.can_assign_into(right) || right.can_assign_into(left) {
             let left_size = left.size();
@@ -13,6 +13,7 @@ extern crate clap;
 
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::PathBuf;
 use std::process;
 
 use hasselc::{Compiler, CompilerOptions, CompilerOptionsBuilder};
@@ -32,7 +33,7 @@ fn handle_result<T>(result: error::Result<T>) -> T {
 
 struct Options {
     compiler_options: CompilerOptions,
-    input_name: String,
+    input_names: Vec<String>,
     output_name: Option<String>,
 }
 
@@ -85,10 +86,20 @@ fn get_options() -> Options {
                 .help("Generates a NMI vector pointing to the given label")
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("INCLUDE")
+                .short("I")
+                .value_name("INCLUDE")
+                .help("Adds a directory to search for imported files")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             clap::Arg::with_name("INPUT")
-                .help("Input source file to use")
-                .required(true),
+                .help("Input source files to use")
+                .required(true)
+                .multiple(true),
         );
     let cli_matches = cli_app.get_matches();
 
@@ -100,6 +111,7 @@ fn get_options() -> Options {
         Some("2") => {
             compiler_options.optimize_llir(true);
             compiler_options.optimize_code(true);
+            compiler_options.inline_functions(true);
         }
         _ => {}
     }
@@ -107,10 +119,16 @@ fn get_options() -> Options {
     compiler_options.vector_reset_label(cli_matches.value_of("VECTOR_RESET").map(String::from));
     compiler_options.vector_irq_label(cli_matches.value_of("VECTOR_IRQ").map(String::from));
     compiler_options.vector_nmi_label(cli_matches.value_of("VECTOR_NMI").map(String::from));
+    compiler_options.import_paths(
+        cli_matches
+            .values_of("INCLUDE")
+            .map(|values| values.map(PathBuf::from).collect())
+            .unwrap_or_else(Vec::new),
+    );
 
     Options {
         compiler_options: compiler_options.build().unwrap(),
-        input_name: cli_matches.value_of("INPUT").unwrap().into(),
+        input_names: cli_matches.values_of("INPUT").unwrap().map(String::from).collect(),
         output_name: cli_matches.value_of("OUTPUT").map(String::from),
     }
 }
@@ -118,24 +136,25 @@ fn get_options() -> Options {
 fn main() {
     let options = get_options();
 
-    let input_source = {
-        let mut file = match File::open(&options.input_name) {
-            Ok(file) => file,
-            Err(e) => {
-                println!("Failed to open input source file: {}", e);
+    let mut compiler = Compiler::new(options.compiler_options);
+    for input_name in &options.input_names {
+        let input_source = {
+            let mut file = match File::open(input_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    println!("Failed to open input source file {}: {}", input_name, e);
+                    return;
+                }
+            };
+            let mut file_contents = String::new();
+            if !file.read_to_string(&mut file_contents).is_ok() {
+                println!("Failed to read the input source file {}", input_name);
                 return;
             }
+            file_contents
         };
-        let mut file_contents = String::new();
-        if !file.read_to_string(&mut file_contents).is_ok() {
-            println!("Failed to read the input source file");
-            return;
-        }
-        file_contents
-    };
-
-    let mut compiler = Compiler::new(options.compiler_options);
-    handle_result(compiler.parse_unit(&options.input_name, &input_source));
+        handle_result(compiler.parse_unit(input_name, &input_source));
+    }
 
     let compiler_output = handle_result(compiler.compile());
 
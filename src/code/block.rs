@@ -9,7 +9,7 @@
 
 use std::fmt::Write;
 use std::sync::Arc;
-use symbol_table::{SymbolName, SymbolRef, SymbolTable};
+use symbol_table::{ConstantValue, SymbolName, SymbolRef, SymbolTable};
 use error;
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -51,7 +51,7 @@ pub enum Parameter {
     AbsoluteY(Global),
     Indirect(Global),
     IndirectX(i8),
-    IndirectY(i8),
+    IndirectY(u8),
 }
 
 impl Parameter {
@@ -65,6 +65,7 @@ impl Parameter {
             Parameter::ZeroPageY(offset) => format!("${:02X}, Y", offset),
             Parameter::Absolute(ref gbl) => gbl.to_asm(global_symbol_table),
             Parameter::AbsoluteY(ref gbl) => format!("{}, Y", gbl.to_asm(global_symbol_table)),
+            Parameter::Indirect(ref gbl) => format!("({})", gbl.to_asm(global_symbol_table)),
             Parameter::IndirectX(val) => format!("(${:02X}, X)", val),
             Parameter::IndirectY(val) => format!("(${:02X}), Y", val),
             _ => unimplemented!(),
@@ -76,24 +77,40 @@ impl Parameter {
 pub enum Code {
     Adc(Parameter),
     And(Parameter),
+    Asl(Parameter),
     Bcc(Parameter),
     Bcs(Parameter),
     Beq(Parameter),
+    Bmi(Parameter),
     Bne(Parameter),
+    Bpl(Parameter),
+    Brk(Parameter),
     Clc(Parameter),
+    Cld(Parameter),
+    Cli(Parameter),
     Cmp(Parameter),
+    Dec(Parameter),
     Eor(Parameter),
+    Inc(Parameter),
     Jmp(Parameter),
     Jsr(Parameter),
     Lda(Parameter),
     Ldx(Parameter),
     Ldy(Parameter),
+    Lsr(Parameter),
+    Nop(Parameter),
+    Ora(Parameter),
+    Pha(Parameter),
     Php(Parameter),
     Pla(Parameter),
+    Rol(Parameter),
     Ror(Parameter),
+    Rti(Parameter),
     Rts(Parameter),
     Sbc(Parameter),
     Sec(Parameter),
+    Sed(Parameter),
+    Sei(Parameter),
     Sta(Parameter),
     Stx(Parameter),
     Sty(Parameter),
@@ -104,24 +121,33 @@ pub enum Code {
 
     Comment(String),
     InlineAsm(Arc<String>),
+    /// Table of the low bytes of each address
+    LowBytes(Vec<Global>),
+    /// Table of the high bytes of each address
+    HighBytes(Vec<Global>),
 }
 
 impl Code {
     pub fn parameter(&self) -> &Parameter {
         use self::Code::*;
         match *self {
-            Adc(ref p) | And(ref p) | Bcc(ref p) | Bcs(ref p) | Beq(ref p) | Bne(ref p) | Clc(ref p) | Cmp(ref p)
-            | Eor(ref p) | Jmp(ref p) | Jsr(ref p) | Lda(ref p) | Ldx(ref p) | Ldy(ref p) | Php(ref p) | Pla(ref p)
-            | Ror(ref p) | Rts(ref p) | Sbc(ref p) | Sec(ref p) | Sta(ref p) | Stx(ref p) | Sty(ref p) | Tax(ref p)
-            | Tay(ref p) | Txa(ref p) | Tya(ref p) => p,
+            Adc(ref p) | And(ref p) | Asl(ref p) | Bcc(ref p) | Bcs(ref p) | Beq(ref p) | Bmi(ref p)
+            | Bne(ref p) | Bpl(ref p) | Brk(ref p) | Clc(ref p) | Cld(ref p) | Cli(ref p) | Cmp(ref p)
+            | Dec(ref p) | Eor(ref p) | Inc(ref p) | Jmp(ref p) | Jsr(ref p) | Lda(ref p) | Ldx(ref p)
+            | Ldy(ref p) | Lsr(ref p) | Nop(ref p) | Ora(ref p) | Pha(ref p) | Php(ref p) | Pla(ref p)
+            | Rol(ref p) | Ror(ref p) | Rti(ref p) | Rts(ref p) | Sbc(ref p) | Sec(ref p) | Sed(ref p)
+            | Sei(ref p) | Sta(ref p) | Stx(ref p) | Sty(ref p) | Tax(ref p) | Tay(ref p) | Txa(ref p)
+            | Tya(ref p) => p,
             Comment(_) => unreachable!(),
             InlineAsm(_) => unreachable!(),
+            LowBytes(_) | HighBytes(_) => unreachable!(),
         }
     }
 
     pub fn is_branch(&self) -> bool {
         match *self {
-            Code::Beq(_) | Code::Bne(_) | Code::Jsr(_) | Code::Rts(_) | Code::Jmp(_) => true,
+            Code::Bcc(_) | Code::Bcs(_) | Code::Beq(_) | Code::Bmi(_) | Code::Bne(_) | Code::Bpl(_) | Code::Jsr(_)
+            | Code::Rti(_) | Code::Rts(_) | Code::Jmp(_) => true,
             _ => false,
         }
     }
@@ -130,24 +156,40 @@ impl Code {
         match *self {
             Code::Adc(ref p) => format!("ADC\t{}", p.to_asm(global_symbol_table)),
             Code::And(ref p) => format!("AND\t{}", p.to_asm(global_symbol_table)),
+            Code::Asl(ref p) => format!("ASL\t{}", p.to_asm(global_symbol_table)),
             Code::Bcc(ref p) => format!("BCC\t{}", p.to_asm(global_symbol_table)),
             Code::Bcs(ref p) => format!("BCS\t{}", p.to_asm(global_symbol_table)),
             Code::Beq(ref p) => format!("BEQ\t{}", p.to_asm(global_symbol_table)),
+            Code::Bmi(ref p) => format!("BMI\t{}", p.to_asm(global_symbol_table)),
             Code::Bne(ref p) => format!("BNE\t{}", p.to_asm(global_symbol_table)),
+            Code::Bpl(ref p) => format!("BPL\t{}", p.to_asm(global_symbol_table)),
+            Code::Brk(ref p) => format!("BRK\t{}", p.to_asm(global_symbol_table)),
             Code::Clc(ref p) => format!("CLC\t{}", p.to_asm(global_symbol_table)),
+            Code::Cld(ref p) => format!("CLD\t{}", p.to_asm(global_symbol_table)),
+            Code::Cli(ref p) => format!("CLI\t{}", p.to_asm(global_symbol_table)),
             Code::Cmp(ref p) => format!("CMP\t{}", p.to_asm(global_symbol_table)),
+            Code::Dec(ref p) => format!("DEC\t{}", p.to_asm(global_symbol_table)),
             Code::Eor(ref p) => format!("EOR\t{}", p.to_asm(global_symbol_table)),
+            Code::Inc(ref p) => format!("INC\t{}", p.to_asm(global_symbol_table)),
             Code::Jmp(ref p) => format!("JMP\t{}", p.to_asm(global_symbol_table)),
             Code::Jsr(ref p) => format!("JSR\t{}", p.to_asm(global_symbol_table)),
             Code::Lda(ref p) => format!("LDA\t{}", p.to_asm(global_symbol_table)),
             Code::Ldx(ref p) => format!("LDX\t{}", p.to_asm(global_symbol_table)),
             Code::Ldy(ref p) => format!("LDY\t{}", p.to_asm(global_symbol_table)),
+            Code::Lsr(ref p) => format!("LSR\t{}", p.to_asm(global_symbol_table)),
+            Code::Nop(ref p) => format!("NOP\t{}", p.to_asm(global_symbol_table)),
+            Code::Ora(ref p) => format!("ORA\t{}", p.to_asm(global_symbol_table)),
+            Code::Pha(ref p) => format!("PHA\t{}", p.to_asm(global_symbol_table)),
             Code::Php(ref p) => format!("PHP\t{}", p.to_asm(global_symbol_table)),
             Code::Pla(ref p) => format!("PLA\t{}", p.to_asm(global_symbol_table)),
+            Code::Rol(ref p) => format!("ROL\t{}", p.to_asm(global_symbol_table)),
             Code::Ror(ref p) => format!("ROR\t{}", p.to_asm(global_symbol_table)),
+            Code::Rti(ref p) => format!("RTI\t{}", p.to_asm(global_symbol_table)),
             Code::Rts(ref p) => format!("RTS\t{}", p.to_asm(global_symbol_table)),
             Code::Sbc(ref p) => format!("SBC\t{}", p.to_asm(global_symbol_table)),
             Code::Sec(ref p) => format!("SEC\t{}", p.to_asm(global_symbol_table)),
+            Code::Sed(ref p) => format!("SED\t{}", p.to_asm(global_symbol_table)),
+            Code::Sei(ref p) => format!("SEI\t{}", p.to_asm(global_symbol_table)),
             Code::Sta(ref p) => format!("STA\t{}", p.to_asm(global_symbol_table)),
             Code::Stx(ref p) => format!("STX\t{}", p.to_asm(global_symbol_table)),
             Code::Sty(ref p) => format!("STY\t{}", p.to_asm(global_symbol_table)),
@@ -157,10 +199,20 @@ impl Code {
             Code::Tya(ref p) => format!("TYA\t{}", p.to_asm(global_symbol_table)),
             Code::Comment(ref msg) => format!("; {}", msg),
             Code::InlineAsm(ref asm) => format!("{}", asm),
+            Code::LowBytes(ref addresses) => byte_table_asm(global_symbol_table, "<", addresses),
+            Code::HighBytes(ref addresses) => byte_table_asm(global_symbol_table, ">", addresses),
         }
     }
 }
 
+fn byte_table_asm(global_symbol_table: &SymbolTable, operator: &str, addresses: &[Global]) -> String {
+    let bytes: Vec<String> = addresses
+        .iter()
+        .map(|address| format!("{}{}", operator, address.to_asm(global_symbol_table)))
+        .collect();
+    format!(".byte\t{}", bytes.join(","))
+}
+
 #[derive(Clone, Debug)]
 pub struct CodeBlock {
     pub location: Global,
@@ -200,13 +252,28 @@ pub fn to_asm(global_symbol_table: &SymbolTable, blocks: &[CodeBlock]) -> error:
     for block in blocks {
         asm.push_str(&block.to_asm(global_symbol_table).unwrap());
     }
-    for (symbol_ref, bytes) in global_symbol_table.data_constants() {
+    for (symbol_ref, value) in global_symbol_table.data_constants() {
         let symbol_name = global_symbol_table.get_symbol_name(symbol_ref).unwrap();
-        write!(asm, "\n{}:\t.byte\t", symbol_name)?;
-        for byte in bytes.iter() {
-            write!(asm, "${:02X},", byte)?;
+        match value {
+            ConstantValue::Bytes(ref bytes) => {
+                let byte_list: Vec<String> = bytes.iter().map(|byte| format!("${:02X}", byte)).collect();
+                write!(asm, "\n{}:\t.byte\t{}\n", symbol_name, byte_list.join(","))?;
+            }
+            ConstantValue::Functions(ref functions) => {
+                // Functions are called through a pointer at their indirect entry point
+                let byte_list: Vec<String> = functions
+                    .iter()
+                    .map(|function| {
+                        let metadata = global_symbol_table.function(*function).unwrap();
+                        let indirect_entry = metadata.read().unwrap().indirect_entry.unwrap();
+                        let entry_name = global_symbol_table.get_symbol_name(indirect_entry).unwrap();
+                        format!("<{},>{}", entry_name, entry_name)
+                    })
+                    .collect();
+                write!(asm, "\n{}:\t.byte\t{}\n", symbol_name, byte_list.join(","))?;
+            }
+            ConstantValue::Number(_) => unreachable!(),
         }
-        write!(asm, "$00\n")?;
     }
     Ok(asm)
 }
@@ -15,7 +15,7 @@ use llir;
 use symbol_table::{SymbolName, SymbolRef};
 use src_tag::{SrcTag, SrcTagged};
 use src_unit::SrcUnits;
-use base_type::BaseType;
+use base_type::{BaseType, NativeType};
 
 pub struct CodeBlockGenerator<'a> {
     llir_blocks: &'a [llir::FrameBlock],
@@ -93,18 +93,79 @@ impl<'a> CodeGenerator<'a> {
                         registers.subtract(body, param, carry)
                     })?;
                 }
+                llir::Statement::And(ref data) => {
+                    self.generate_binary_op(data, |registers, body, param, _carry| {
+                        registers.modify_accum(body, Code::And(param))
+                    })?;
+                }
+                llir::Statement::ExclusiveOr(ref data) => {
+                    self.generate_binary_op(data, |registers, body, param, _carry| {
+                        registers.modify_accum(body, Code::Eor(param))
+                    })?;
+                }
+                llir::Statement::Or(ref data) => {
+                    self.generate_binary_op(data, |registers, body, param, _carry| {
+                        registers.modify_accum(body, Code::Ora(param))
+                    })?;
+                }
+                llir::Statement::ShiftLeft(ref data) => {
+                    let op = if data.rotate {
+                        Code::Rol(Parameter::Accumulator)
+                    } else {
+                        Code::Asl(Parameter::Accumulator)
+                    };
+                    self.generate_shift(data, op)?;
+                }
+                llir::Statement::ShiftRight(ref data) => {
+                    let op = if data.rotate {
+                        Code::Ror(Parameter::Accumulator)
+                    } else {
+                        Code::Lsr(Parameter::Accumulator)
+                    };
+                    self.generate_shift(data, op)?;
+                }
                 llir::Statement::AddToDataStackPointer(ref data) => {
                     self.registers.load_dsp(&mut self.code, Register::Accum);
                     let add_param = Parameter::Immediate(match data.offset {
                         llir::SPOffset::Immediate(val) => val as u8,
                         llir::SPOffset::FrameSize(frame_ref) => self.lookup_frame_size(frame_ref)? as u8,
                         llir::SPOffset::NegativeFrameSize(frame_ref) => -self.lookup_frame_size(frame_ref)? as u8,
+                        llir::SPOffset::InterruptFrameSize(frame_ref) => self.interrupt_frame_size(frame_ref)?,
+                        llir::SPOffset::NegativeInterruptFrameSize(frame_ref) => {
+                            self.interrupt_frame_size(frame_ref)?.wrapping_neg()
+                        }
                     });
                     self.registers
                         .add(&mut self.code, add_param, llir::CarryMode::ClearCarry);
                     self.registers.save_dsp_later(Register::Accum);
                     self.registers.load_dsp(&mut self.code, Register::XIndex);
                 }
+                llir::Statement::Break(_) => {
+                    // The interrupt handler saves and restores the registers, but it can change memory
+                    self.registers.save_all_and_reset(&mut self.code);
+                    self.code.push(Code::Brk(Parameter::Implicit));
+                    // Returning from a BRK skips the byte after it
+                    self.code.push(Code::Nop(Parameter::Implicit));
+                }
+                llir::Statement::ClearFlag(ref data) => {
+                    let op = match data.flag {
+                        llir::StatusFlag::Carry => Code::Clc(Parameter::Implicit),
+                        llir::StatusFlag::Decimal => Code::Cld(Parameter::Implicit),
+                        llir::StatusFlag::InterruptDisable => Code::Cli(Parameter::Implicit),
+                    };
+                    self.generate_status_flag(data.flag, op);
+                }
+                llir::Statement::SetFlag(ref data) => {
+                    let op = match data.flag {
+                        llir::StatusFlag::Carry => Code::Sec(Parameter::Implicit),
+                        llir::StatusFlag::Decimal => Code::Sed(Parameter::Implicit),
+                        llir::StatusFlag::InterruptDisable => Code::Sei(Parameter::Implicit),
+                    };
+                    self.generate_status_flag(data.flag, op);
+                }
+                llir::Statement::NoOp(_) => {
+                    self.code.push(Code::Nop(Parameter::Implicit));
+                }
                 llir::Statement::BranchIfZero(ref data) => {
                     self.registers.save_all_now(&mut self.code);
                     self.load_into_accum(&data.value)?;
@@ -115,13 +176,28 @@ impl<'a> CodeGenerator<'a> {
                 }
                 llir::Statement::CompareBranch(ref data) => {
                     let cmp_param = self.prepare_binary_op(&data.left, &data.right)?;
-                    self.code.push(Code::Cmp(cmp_param));
+                    if data.branch_flag == llir::BranchFlag::Negative {
+                        // CMP doesn't set the overflow flag, so subtract instead. Then the N and V flags
+                        // are combined into bit 7 with an add: %00 and %11 become clear, %01 and %10 set.
+                        self.registers
+                            .subtract(&mut self.code, cmp_param, llir::CarryMode::SetCarry);
+                        self.registers.load_status_into_accum(&mut self.code);
+                        self.registers
+                            .modify_accum(&mut self.code, Code::And(Parameter::Immediate(0xC0)));
+                        self.registers
+                            .add(&mut self.code, Parameter::Immediate(0x40), llir::CarryMode::ClearCarry);
+                    } else {
+                        self.code.push(Code::Cmp(cmp_param));
+                    }
+                    // Stores don't change the flags, so write out pending values before branching away
+                    self.registers.save_all_now(&mut self.code);
 
                     if let Some(branch_set) = data.branch_set {
                         let param = Parameter::Absolute(Global::UnresolvedSymbol(branch_set));
                         self.code.push(match data.branch_flag {
                             llir::BranchFlag::Zero => Code::Beq(param),
                             llir::BranchFlag::Carry => Code::Bcs(param),
+                            llir::BranchFlag::Negative => Code::Bmi(param),
                         });
                     }
 
@@ -130,6 +206,7 @@ impl<'a> CodeGenerator<'a> {
                         self.code.push(match data.branch_flag {
                             llir::BranchFlag::Zero => Code::Bne(param),
                             llir::BranchFlag::Carry => Code::Bcc(param),
+                            llir::BranchFlag::Negative => Code::Bpl(param),
                         });
                     }
                 }
@@ -177,6 +254,44 @@ impl<'a> CodeGenerator<'a> {
                     self.load_into_accum(&data.value)?;
                     self.store_accum(&data.destination)?;
                 }
+                llir::Statement::Decrement(ref data) => {
+                    let param = self.step_parameter(&data.location)?;
+                    self.registers.modify_memory(&mut self.code, Code::Dec(param));
+                }
+                llir::Statement::Increment(ref data) => {
+                    let param = self.step_parameter(&data.location)?;
+                    self.registers.modify_memory(&mut self.code, Code::Inc(param));
+                }
+                llir::Statement::Peek(ref data) => {
+                    let byte = llir::Value::Memory(llir::MemoryData::new(BaseType::U8, data.address.clone(), None));
+                    self.load_into_accum(&byte)?;
+                    self.store_accum(&data.destination)?;
+                }
+                llir::Statement::Poke(ref data) => {
+                    self.load_into_accum(&data.value)?;
+                    self.store_accum(&data.address)?;
+                }
+                llir::Statement::RotateLeft(ref data) => {
+                    self.generate_rotate(&data.location, Code::Rol)?;
+                }
+                llir::Statement::RotateRight(ref data) => {
+                    self.generate_rotate(&data.location, Code::Ror)?;
+                }
+                llir::Statement::EnterInterrupt(ref data) => {
+                    self.registers.save_all_and_reset(&mut self.code);
+                    self.code.push(Code::Pha(Parameter::Implicit));
+                    self.code.push(Code::Txa(Parameter::Implicit));
+                    self.code.push(Code::Pha(Parameter::Implicit));
+                    self.code.push(Code::Tya(Parameter::Implicit));
+                    self.code.push(Code::Pha(Parameter::Implicit));
+                    // The interrupted code may be in decimal mode, and RTI restores its flags afterwards
+                    self.code.push(Code::Cld(Parameter::Implicit));
+                    for location in &data.saved {
+                        let param = self.location_to_parameter(location)?;
+                        self.code.push(Code::Lda(param));
+                        self.code.push(Code::Pha(Parameter::Implicit));
+                    }
+                }
                 llir::Statement::GoTo(ref data) => {
                     self.registers.save_all_and_reset(&mut self.code);
                     self.code
@@ -187,6 +302,34 @@ impl<'a> CodeGenerator<'a> {
                 llir::Statement::InlineAsm(ref data) => {
                     self.code.push(Code::InlineAsm(Arc::clone(&data.asm)));
                 }
+                llir::Statement::JumpIndirect(ref data) => {
+                    let vector = match data.vector {
+                        llir::Location::Global(addr) => addr,
+                        _ => unreachable!(),
+                    };
+                    self.load_value(Register::YIndex, &data.index)?;
+                    self.registers.load(
+                        &mut self.code,
+                        Register::Accum,
+                        Parameter::AbsoluteY(Global::UnresolvedSymbol(data.low_table)),
+                    );
+                    self.registers.save_later(Register::Accum, addr_param(vector));
+                    self.registers.load(
+                        &mut self.code,
+                        Register::Accum,
+                        Parameter::AbsoluteY(Global::UnresolvedSymbol(data.high_table)),
+                    );
+                    self.registers.save_later(Register::Accum, addr_param(vector + 1));
+                    self.registers.save_all_and_reset(&mut self.code);
+                    self.code
+                        .push(Code::Jmp(Parameter::Indirect(Global::Resolved(vector))));
+                }
+                llir::Statement::LowByteTable(ref data) => {
+                    self.code.push(Code::LowBytes(address_globals(&data.addresses)));
+                }
+                llir::Statement::HighByteTable(ref data) => {
+                    self.code.push(Code::HighBytes(address_globals(&data.addresses)));
+                }
                 llir::Statement::JumpRoutine(ref data) => {
                     self.registers.save_all_and_reset(&mut self.code);
                     self.code
@@ -196,10 +339,33 @@ impl<'a> CodeGenerator<'a> {
                             _ => unreachable!(),
                         })));
                 }
+                llir::Statement::JumpVector(ref data) => {
+                    let vector = match data.vector {
+                        llir::Location::Global(addr) => addr,
+                        _ => unreachable!(),
+                    };
+                    self.registers.save_all_and_reset(&mut self.code);
+                    self.code
+                        .push(Code::Jmp(Parameter::Indirect(Global::Resolved(vector))));
+                }
                 llir::Statement::Return(_) => {
                     self.registers.save_all_and_reset(&mut self.code);
                     self.code.push(Code::Rts(Parameter::Implicit));
                 }
+                llir::Statement::ReturnFromInterrupt(ref data) => {
+                    self.registers.save_all_and_reset(&mut self.code);
+                    for location in data.saved.iter().rev() {
+                        let param = self.location_to_parameter(location)?;
+                        self.code.push(Code::Pla(Parameter::Implicit));
+                        self.code.push(Code::Sta(param));
+                    }
+                    self.code.push(Code::Pla(Parameter::Implicit));
+                    self.code.push(Code::Tay(Parameter::Implicit));
+                    self.code.push(Code::Pla(Parameter::Implicit));
+                    self.code.push(Code::Tax(Parameter::Implicit));
+                    self.code.push(Code::Pla(Parameter::Implicit));
+                    self.code.push(Code::Rti(Parameter::Implicit));
+                }
             }
         }
         self.registers.save_all_now(&mut self.code);
@@ -210,7 +376,7 @@ impl<'a> CodeGenerator<'a> {
         // TODO: Choose left or right to go into accum based on least work
         self.load_into_accum(left)?;
         match *right {
-            llir::Value::Immediate(ref _base_type, ref val) => Ok(Parameter::Immediate(val.number() as u8)),
+            llir::Value::Immediate(ref _base_type, ref val) => self.immediate_param(val),
             llir::Value::Memory(ref data) => {
                 self.load_stack_pointer_if_necessary(&data.location)?;
                 Ok(self.location_to_parameter(&data.location)?)
@@ -233,6 +399,42 @@ impl<'a> CodeGenerator<'a> {
         Ok(())
     }
 
+    fn generate_status_flag(&mut self, flag: llir::StatusFlag, op: Code) {
+        if flag == llir::StatusFlag::InterruptDisable {
+            // Writes can't be put off past the point where interrupts are enabled or disabled
+            self.registers.save_all_now(&mut self.code);
+        }
+        self.code.push(op);
+    }
+
+    /// Rotates a byte through the carry flag. Nothing that runs before the rotate may change the carry flag.
+    fn generate_rotate(&mut self, location: &llir::Location, op: fn(Parameter) -> Code) -> error::Result<()> {
+        match *location {
+            llir::Location::Global(_)
+            | llir::Location::DataStackOffset(_)
+            | llir::Location::FrameOffset(_, _)
+            | llir::Location::UnresolvedGlobal(_) => {
+                let param = self.step_parameter(location)?;
+                self.registers.modify_memory(&mut self.code, op(param));
+            }
+            // There's no rotate that addresses memory through a pointer or with the Y register
+            _ => {
+                let value = llir::Value::Memory(llir::MemoryData::new(BaseType::U8, location.clone(), None));
+                self.load_into_accum(&value)?;
+                self.registers.modify_accum(&mut self.code, op(Parameter::Accumulator));
+                self.store_accum(location)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_shift(&mut self, shift: &llir::ShiftData, op: Code) -> error::Result<()> {
+        self.load_into_accum(&shift.value)?;
+        self.registers.modify_accum(&mut self.code, op);
+        self.store_accum(&shift.destination)?;
+        Ok(())
+    }
+
     fn load_into_accum(&mut self, value: &llir::Value) -> error::Result<()> {
         self.load_value(Register::Accum, value)
     }
@@ -246,17 +448,12 @@ impl<'a> CodeGenerator<'a> {
         }
 
         match *value {
-            llir::Value::Immediate(ref _base_type, ref val) => self.registers.load(
-                &mut self.code,
-                register,
-                Parameter::Immediate(val.number() as u8),
-            ),
+            llir::Value::Immediate(ref _base_type, ref val) => {
+                let param = self.immediate_param(val)?;
+                self.registers.load(&mut self.code, register, param);
+            }
             llir::Value::Memory(ref data) => match data.location {
-                llir::Location::Global(addr) => self.registers.load(
-                    &mut self.code,
-                    register,
-                    Parameter::Absolute(Global::Resolved(addr)),
-                ),
+                llir::Location::Global(addr) => self.registers.load(&mut self.code, register, addr_param(addr)),
                 llir::Location::DataStackOffset(offset) => {
                     self.registers.load_dsp(&mut self.code, Register::XIndex);
                     self.registers
@@ -271,9 +468,9 @@ impl<'a> CodeGenerator<'a> {
                         Parameter::ZeroPageX(offset - frame_size),
                     );
                 }
-                llir::Location::FrameOffsetIndirect(frame_ref, offset) => {
+                llir::Location::FrameOffsetIndirect(frame_ref, ref pointers) => {
                     self.registers.load_dsp(&mut self.code, Register::XIndex);
-                    let dsp_offset = offset - self.lookup_frame_size(frame_ref)?;
+                    let dsp_offset = pointers[0] - self.lookup_frame_size(frame_ref)?;
                     self.registers
                         .load(&mut self.code, register, Parameter::IndirectX(dsp_offset));
                 }
@@ -295,13 +492,25 @@ impl<'a> CodeGenerator<'a> {
                         Parameter::AbsoluteY(Global::Resolved(addr)),
                     );
                 }
+                llir::Location::ZeroPageIndirect(pointer, offset) => {
+                    self.registers
+                        .load(&mut self.code, Register::YIndex, Parameter::Immediate(offset));
+                    self.registers
+                        .load(&mut self.code, register, Parameter::IndirectY(pointer));
+                }
+                llir::Location::UnresolvedGlobalIndexed(symbol_ref, ref index) => {
+                    self.load_value(Register::YIndex, index)?;
+                    self.registers.load(
+                        &mut self.code,
+                        register,
+                        Parameter::AbsoluteY(Global::UnresolvedSymbol(symbol_ref)),
+                    );
+                }
                 llir::Location::UnresolvedGlobal(symbol_ref) => {
-                    let param = match data.base_type {
-                        BaseType::U8 => Parameter::Absolute(Global::UnresolvedSymbol(symbol_ref)),
-                        BaseType::U16 | BaseType::Pointer(_) => {
-                            Parameter::Absolute(Global::UnresolvedSymbolLowByte(symbol_ref))
-                        }
-                        _ => unimplemented!(),
+                    let param = match data.base_type.as_native() {
+                        Some(NativeType::U8) => Parameter::Absolute(Global::UnresolvedSymbol(symbol_ref)),
+                        Some(NativeType::U16) => Parameter::Absolute(Global::UnresolvedSymbolLowByte(symbol_ref)),
+                        None => unimplemented!(),
                     };
                     self.registers.load(&mut self.code, register, param);
                 }
@@ -338,6 +547,20 @@ impl<'a> CodeGenerator<'a> {
         Ok(())
     }
 
+    fn step_parameter(&mut self, location: &llir::Location) -> error::Result<Parameter> {
+        self.load_stack_pointer_if_necessary(location)?;
+        self.location_to_parameter(location)
+    }
+
+    fn immediate_param(&self, value: &llir::ImmediateValue) -> error::Result<Parameter> {
+        match *value {
+            llir::ImmediateValue::FrameOffset(frame_ref, offset) => Ok(Parameter::Immediate(
+                (offset - self.lookup_frame_size(frame_ref)?) as u8,
+            )),
+            _ => Ok(Parameter::Immediate(value.number() as u8)),
+        }
+    }
+
     fn lookup_frame_size(&self, symbol: SymbolRef) -> error::Result<i8> {
         for block in self.llir_blocks {
             if block.symbol == symbol {
@@ -347,6 +570,17 @@ impl<'a> CodeGenerator<'a> {
         unreachable!("existence of frames should have been checked in previous stages")
     }
 
+    /// Space that an interrupt handler skips on the data stack: the largest frame that the
+    /// interrupted code could be setting up for a call, plus the handler's own frame
+    fn interrupt_frame_size(&self, symbol: SymbolRef) -> error::Result<u8> {
+        let largest = self.llir_blocks
+            .iter()
+            .map(|block| block.frame_size as u8)
+            .max()
+            .unwrap_or(0);
+        Ok(largest.wrapping_add(self.lookup_frame_size(symbol)? as u8))
+    }
+
     fn load_stack_pointer_if_necessary(&mut self, location: &llir::Location) -> error::Result<()> {
         match *location {
             llir::Location::DataStackOffset(_)
@@ -370,13 +604,24 @@ impl<'a> CodeGenerator<'a> {
             llir::Location::FrameOffset(frame_ref, offset) => Ok(Parameter::ZeroPageX(
                 offset - self.lookup_frame_size(frame_ref)?,
             )),
-            llir::Location::FrameOffsetIndirect(frame_ref, offset) => Ok(Parameter::IndirectX(
-                offset - self.lookup_frame_size(frame_ref)?,
+            llir::Location::FrameOffsetIndirect(frame_ref, ref pointers) => Ok(Parameter::IndirectX(
+                pointers[0] - self.lookup_frame_size(frame_ref)?,
             )),
+            llir::Location::ZeroPageIndirect(pointer, offset) => {
+                self.registers
+                    .load(&mut self.code, Register::YIndex, Parameter::Immediate(offset));
+                Ok(Parameter::IndirectY(pointer))
+            }
             llir::Location::UnresolvedGlobal(symbol) => Ok(Parameter::Absolute(Global::UnresolvedSymbol(symbol))),
             llir::Location::UnresolvedGlobalIndexed(symbol, ref index) => {
                 self.load_value(Register::YIndex, index)?;
-                Ok(Parameter::Absolute(Global::UnresolvedSymbol(symbol)))
+                Ok(Parameter::AbsoluteY(Global::UnresolvedSymbol(symbol)))
+            }
+            llir::Location::UnresolvedGlobalLowByte(symbol) => {
+                Ok(Parameter::Absolute(Global::UnresolvedSymbolLowByte(symbol)))
+            }
+            llir::Location::UnresolvedGlobalHighByte(symbol) => {
+                Ok(Parameter::Absolute(Global::UnresolvedSymbolHighByte(symbol)))
             }
             _ => {
                 println!("WARN: Unimplemented location_to_parameter: {:?}", location);
@@ -393,3 +638,10 @@ fn addr_param(addr: u16) -> Parameter {
         Parameter::Absolute(Global::Resolved(addr))
     }
 }
+
+fn address_globals(addresses: &[SymbolRef]) -> Vec<Global> {
+    addresses
+        .iter()
+        .map(|address| Global::UnresolvedSymbol(*address))
+        .collect()
+}
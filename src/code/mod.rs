@@ -15,3 +15,4 @@ mod register;
 pub use self::block::*;
 pub use self::generator::CodeBlockGenerator;
 pub use self::optimizer::optimize_code;
+pub use self::register::{DATA_STACK_POINTER_LOCATION, RETURN_VALUE_LOCATION};
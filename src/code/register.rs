@@ -11,6 +11,7 @@ use code::{Code, Parameter};
 use llir::CarryMode;
 
 pub const DATA_STACK_POINTER_LOCATION: u16 = 0x0000;
+pub const RETURN_VALUE_LOCATION: u16 = 0x0001;
 
 pub const DSP_PARAM: Parameter = Parameter::ZeroPage(DATA_STACK_POINTER_LOCATION as u8);
 const DSP_REG_VALUE: RegisterValue = RegisterValue::Param(DSP_PARAM);
@@ -38,6 +39,26 @@ impl RegisterEquivalency {
         }
     }
 
+    pub fn forget(&mut self, value: &RegisterValue) {
+        self.equivalencies.retain(|equivalency| equivalency != value);
+    }
+
+    /// Forgets any equivalency to memory that is addressed using the given index register
+    pub fn forget_indexed_by(&mut self, register: Register) {
+        self.equivalencies.retain(|equivalency| match *equivalency {
+            RegisterValue::Param(ref param) => !indexed_by(param, register),
+            RegisterValue::Intermediate(_) => true,
+        });
+    }
+
+    /// Forgets any equivalency to memory that is read through a pointer with a byte at the given location
+    pub fn forget_pointing_through(&mut self, location: &Parameter) {
+        self.equivalencies.retain(|equivalency| match *equivalency {
+            RegisterValue::Param(ref param) => !points_through(param, location),
+            RegisterValue::Intermediate(_) => true,
+        });
+    }
+
     pub fn is_equivalent(&self, value: &RegisterValue) -> bool {
         self.equivalencies.contains(value)
     }
@@ -114,15 +135,30 @@ impl Register {
     }
 }
 
+/// True if the parameter's address depends on the given index register
+fn indexed_by(param: &Parameter, register: Register) -> bool {
+    match *param {
+        Parameter::AbsoluteX(_) | Parameter::IndirectX(_) | Parameter::ZeroPageX(_) => register == Register::XIndex,
+        Parameter::AbsoluteY(_) | Parameter::IndirectY(_) | Parameter::ZeroPageY(_) => register == Register::YIndex,
+        _ => false,
+    }
+}
+
+/// True if the parameter reads through a pointer that has a byte at the given location
+fn points_through(param: &Parameter, location: &Parameter) -> bool {
+    match (param, location) {
+        (&Parameter::IndirectY(pointer), &Parameter::ZeroPage(address)) => {
+            address == pointer || address as usize == pointer as usize + 1
+        }
+        _ => false,
+    }
+}
+
 struct SaveLocation(pub Parameter);
 
 impl SaveLocation {
     pub fn requires(&self, register: Register) -> bool {
-        match self.0 {
-            Parameter::AbsoluteX(_) | Parameter::IndirectX(_) | Parameter::ZeroPageX(_) => register == Register::XIndex,
-            Parameter::AbsoluteY(_) | Parameter::IndirectY(_) | Parameter::ZeroPageY(_) => register == Register::YIndex,
-            _ => false,
-        }
+        indexed_by(&self.0, register)
     }
 }
 
@@ -176,6 +212,19 @@ impl RegisterAllocator {
         }
     }
 
+    /// Writes out any register that has a pending save to the location about to be read,
+    /// or to the pointer that it's about to be read through
+    fn save_before_read(&mut self, code: &mut Vec<Code>, param: &Parameter) {
+        for index in 0..self.save_locations.len() {
+            if self.save_locations[index]
+                .iter()
+                .any(|location| location.0 == *param || points_through(param, &location.0))
+            {
+                self.spillover(code, Register::from_ordinal(index));
+            }
+        }
+    }
+
     pub fn add(&mut self, code: &mut Vec<Code>, param: Parameter, carry_mode: CarryMode) {
         self.save_as_necessary(code, Register::Accum);
         match carry_mode {
@@ -200,6 +249,26 @@ impl RegisterAllocator {
         self.values[Register::Accum.ordinal()].clobber(next_intermediate);
     }
 
+    /// Performs an operation that modifies the accumulator without needing any other registers
+    pub fn modify_accum(&mut self, code: &mut Vec<Code>, op: Code) {
+        self.save_as_necessary(code, Register::Accum);
+        code.push(op);
+        let next_intermediate = self.next_intermediate();
+        self.values[Register::Accum.ordinal()].clobber(next_intermediate);
+    }
+
+    /// Performs an operation that modifies memory in place, such as an increment
+    pub fn modify_memory(&mut self, code: &mut Vec<Code>, op: Code) {
+        let param = op.parameter().clone();
+        self.save_before_read(code, &param);
+        code.push(op);
+        // Registers that were loaded from or saved to the location no longer match it
+        let value = RegisterValue::Param(param);
+        for equivalency in &mut self.values {
+            equivalency.forget(&value);
+        }
+    }
+
     pub fn load_status_into_accum(&mut self, code: &mut Vec<Code>) {
         self.save_as_necessary(code, Register::Accum);
         code.push(Code::Php(Parameter::Implicit));
@@ -211,12 +280,21 @@ impl RegisterAllocator {
     pub fn load(&mut self, code: &mut Vec<Code>, register: Register, param: Parameter) {
         if !self.values[register.ordinal()].is_equivalent(&RegisterValue::Param(param.clone())) {
             self.save_as_necessary(code, register);
+            self.save_before_read(code, &param);
             code.push(register.load_op(param.clone()));
             self.values[register.ordinal()].clobber(RegisterValue::Param(param));
+            // Memory addressed with the register that was just loaded may be somewhere else now
+            for equivalency in &mut self.values {
+                equivalency.forget_indexed_by(register);
+            }
         }
     }
 
     pub fn save_later(&mut self, register: Register, location: Parameter) {
+        // Anything read through a pointer that's being changed is somewhere else now
+        for equivalency in &mut self.values {
+            equivalency.forget_pointing_through(&location);
+        }
         self.save_locations[register.ordinal()].push(SaveLocation(location.clone()));
         self.values[register.ordinal()].add_value(RegisterValue::Param(location));
     }
@@ -262,7 +340,7 @@ impl RegisterAllocator {
 mod tests {
     use std::sync::{Arc, RwLock};
     use super::*;
-    use code::CodeBlock;
+    use code::{CodeBlock, Global};
     use symbol_table::{DefaultSymbolTable, HandleGenerator, SymbolName};
 
     fn symbol_table() -> DefaultSymbolTable {
@@ -358,4 +436,134 @@ mod tests {
             code_block.to_asm(&symbol_table()).unwrap()
         );
     }
+
+    #[test]
+    fn save_before_read_from_other_register() {
+        let mut code_block = CodeBlock::new(SymbolName::new("test".into()), 1, None);
+        let mut registers = RegisterAllocator::new();
+
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::Immediate(1),
+        );
+        registers.save_later(Register::Accum, Parameter::ZeroPage(2));
+        registers.load(
+            &mut code_block.body,
+            Register::YIndex,
+            Parameter::ZeroPage(2),
+        );
+
+        assert_eq!(
+            "\ntest:\n\
+             \tLDA\t#1\n\
+             \tSTA\t$02\n\
+             \tLDY\t$02\n",
+            code_block.to_asm(&symbol_table()).unwrap()
+        );
+    }
+
+    #[test]
+    fn modify_memory_then_load() {
+        let mut code_block = CodeBlock::new(SymbolName::new("test".into()), 1, None);
+        let mut registers = RegisterAllocator::new();
+
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::Immediate(1),
+        );
+        registers.save_later(Register::Accum, Parameter::ZeroPage(2));
+        registers.modify_memory(&mut code_block.body, Code::Inc(Parameter::ZeroPage(2)));
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::ZeroPage(2),
+        );
+
+        assert_eq!(
+            "\ntest:\n\
+             \tLDA\t#1\n\
+             \tSTA\t$02\n\
+             \tINC\t$02\n\
+             \tLDA\t$02\n",
+            code_block.to_asm(&symbol_table()).unwrap()
+        );
+    }
+
+    #[test]
+    fn load_after_pointer_change() {
+        let mut code_block = CodeBlock::new(SymbolName::new("test".into()), 1, None);
+        let mut registers = RegisterAllocator::new();
+
+        registers.load(
+            &mut code_block.body,
+            Register::YIndex,
+            Parameter::Immediate(0),
+        );
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::IndirectY(0x80),
+        );
+        registers.load(
+            &mut code_block.body,
+            Register::XIndex,
+            Parameter::Immediate(5),
+        );
+        registers.save_later(Register::XIndex, Parameter::ZeroPage(0x81));
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::IndirectY(0x80),
+        );
+
+        assert_eq!(
+            "\ntest:\n\
+             \tLDY\t#0\n\
+             \tLDA\t($80), Y\n\
+             \tLDX\t#5\n\
+             \tSTX\t$81\n\
+             \tLDA\t($80), Y\n",
+            code_block.to_asm(&symbol_table()).unwrap()
+        );
+    }
+
+    #[test]
+    fn load_after_index_change() {
+        let mut code_block = CodeBlock::new(SymbolName::new("test".into()), 1, None);
+        let mut registers = RegisterAllocator::new();
+
+        registers.load(
+            &mut code_block.body,
+            Register::YIndex,
+            Parameter::Immediate(1),
+        );
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::Immediate(5),
+        );
+        registers.save_later(Register::Accum, Parameter::AbsoluteY(Global::Resolved(0x0200)));
+        registers.load(
+            &mut code_block.body,
+            Register::YIndex,
+            Parameter::Immediate(0),
+        );
+        registers.load(
+            &mut code_block.body,
+            Register::Accum,
+            Parameter::AbsoluteY(Global::Resolved(0x0200)),
+        );
+
+        assert_eq!(
+            "\ntest:\n\
+             \tLDY\t#1\n\
+             \tLDA\t#5\n\
+             \tSTA\t$0200, Y\n\
+             \tLDY\t#0\n\
+             \tLDA\t$0200, Y\n",
+            code_block.to_asm(&symbol_table()).unwrap()
+        );
+    }
 }
@@ -7,6 +7,11 @@
 // copied, modified, or distributed except according to those terms.
 //
 
+use std::cmp::{max, Reverse};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use hassel_asm::Assembler;
 
@@ -15,8 +20,10 @@ use llir;
 use code;
 use error::{self, to_compiler_error, ErrorKind};
 use parse::ast;
-use symbol_table::{DefaultSymbolTable, HandleGenerator, SymbolTable};
+use base_type::BaseType;
+use symbol_table::{ConstantValue, DefaultSymbolTable, HandleGenerator, Location, SymbolRef, SymbolTable, Variable};
 use src_unit::SrcUnits;
+use zero_page::{skip_declared, ZeroPageAllocator, ZERO_PAGE_POINTER_NAME};
 
 #[derive(Debug)]
 pub struct CompilerOutput {
@@ -37,11 +44,42 @@ pub struct CompilerOptions {
 
     #[builder(default)] pub optimize_code: bool,
 
+    /// Replaces calls to small functions that don't call anything else with their bodies
+    #[builder(default)]
+    pub inline_functions: bool,
+
     #[builder(default)] pub vector_reset_label: Option<String>,
 
     #[builder(default)] pub vector_irq_label: Option<String>,
 
     #[builder(default)] pub vector_nmi_label: Option<String>,
+
+    /// Where top-level arrays and static variables start being placed in RAM
+    #[builder(default = "0x0300")]
+    pub static_ram_start: u16,
+
+    /// The last address in RAM that can hold top-level arrays and static variables
+    #[builder(default = "0x07FF")]
+    pub static_ram_end: u16,
+
+    /// First address in zero page that the compiler can place static variables and pointers in.
+    /// The data stack grows up from the bottom of zero page, so this has to be after `data_stack_end`.
+    #[builder(default = "0x80")]
+    pub zero_page_start: u8,
+
+    /// Last address in zero page that the compiler can use. Setting this
+    /// below `zero_page_start` keeps the compiler out of zero page entirely.
+    #[builder(default = "0xFF")]
+    pub zero_page_end: u8,
+
+    /// Last address in zero page that the data stack can grow into. The data stack starts right
+    /// after the return value, and nothing else is placed between there and this address.
+    #[builder(default = "0x7F")]
+    pub data_stack_end: u8,
+
+    /// Directories to search for imported files that aren't next to the file importing them
+    #[builder(default)]
+    pub import_paths: Vec<PathBuf>,
 }
 
 pub struct Compiler {
@@ -49,6 +87,8 @@ pub struct Compiler {
     src_units: SrcUnits,
     units: Vec<ast::Expression>,
     options: CompilerOptions,
+    /// Canonical paths of every file parsed so far, so that each is only loaded once
+    loaded_files: HashSet<PathBuf>,
 }
 
 impl Compiler {
@@ -59,16 +99,86 @@ impl Compiler {
             src_units: SrcUnits::new(),
             units: Vec::new(),
             options: options,
+            loaded_files: HashSet::new(),
         }
     }
 
+    /// Parses a unit of source code, along with everything it imports. Imports are found relative
+    /// to the unit's name when it is a file path, and then in each of the configured import paths.
     pub fn parse_unit(&mut self, unit_name: &str, unit: &str) -> error::Result<()> {
-        let unit_id = self.src_units.push_unit(unit_name.into(), unit.into());
-        let expressions = ast::Expression::parse(self.src_units.unit(unit_id))?;
+        if let Ok(path) = fs::canonicalize(unit_name) {
+            if !self.loaded_files.insert(path) {
+                return Ok(());
+            }
+        }
+        let expressions = self.load_unit(unit_name, unit)?;
         self.units.extend(expressions.into_iter());
         Ok(())
     }
 
+    /// Parses a unit and replaces its imports with the expressions of the imported units
+    fn load_unit(&mut self, unit_name: &str, unit: &str) -> error::Result<Vec<ast::Expression>> {
+        let unit_id = self.src_units.push_unit(unit_name.into(), unit.into());
+        let parsed = match ast::Expression::parse(self.src_units.unit(unit_id)) {
+            Ok(parsed) => parsed,
+            Err(err @ error::Error(ErrorKind::ParseError(_), _)) => return Err(err),
+            Err(err) => return Err(self.compiler_error(err)),
+        };
+        let mut expressions = Vec::new();
+        for expression in parsed {
+            match expression {
+                ast::Expression::Import(ref data) => expressions.extend(self.import(unit_name, data)?),
+                _ => expressions.push(expression),
+            }
+        }
+        Ok(expressions)
+    }
+
+    fn import(&mut self, importer_name: &str, data: &ast::ImportData) -> error::Result<Vec<ast::Expression>> {
+        let path = match self.find_import(importer_name, &data.path) {
+            Some(path) => path,
+            None => return Err(self.import_error(data, "file not found".into())),
+        };
+        match fs::canonicalize(&path) {
+            Ok(canonical_path) => if !self.loaded_files.insert(canonical_path) {
+                return Ok(Vec::new());
+            },
+            Err(err) => return Err(self.import_error(data, err.to_string())),
+        }
+
+        let mut source = String::new();
+        if let Err(err) = File::open(&path).and_then(|mut file| file.read_to_string(&mut source)) {
+            return Err(self.import_error(data, err.to_string()));
+        }
+        self.load_unit(&path.to_string_lossy(), &source)
+    }
+
+    fn find_import(&self, importer_name: &str, import_path: &str) -> Option<PathBuf> {
+        let importer_dir = Path::new(importer_name).parent().unwrap_or_else(|| Path::new(""));
+        let mut candidates = vec![importer_dir.join(import_path)];
+        candidates.extend(self.options.import_paths.iter().map(|dir| dir.join(import_path)));
+        candidates.into_iter().find(|candidate| candidate.is_file())
+    }
+
+    fn import_error(&self, data: &ast::ImportData, reason: String) -> error::Error {
+        self.compiler_error(ErrorKind::ImportFailed(data.tag, Arc::clone(&data.path), reason).into())
+    }
+
+    /// Gives an error that was found before compiling started the location it happened at
+    fn compiler_error(&self, err: error::Error) -> error::Error {
+        let compiler_output = CompilerOutput {
+            global_symbol_table: Arc::clone(&self.global_symbol_table),
+            ast: None,
+            ir: None,
+            llir: None,
+            code: None,
+            asm: None,
+            asm_map: None,
+            bytes: None,
+        };
+        to_compiler_error(&self.src_units, err, compiler_output)
+    }
+
     pub fn compile(self) -> error::Result<CompilerOutput> {
         let mut compiler_output = CompilerOutput {
             global_symbol_table: Arc::clone(&self.global_symbol_table),
@@ -84,10 +194,12 @@ impl Compiler {
         match ir::generate(
             &self.global_symbol_table,
             compiler_output.ast.as_ref().unwrap(),
+            self.options.inline_functions,
         ) {
             Ok(ir) => compiler_output.ir = Some(ir),
             Err(err) => return Err(to_compiler_error(&self.src_units, err, compiler_output)),
         }
+        place_statics(&self.options, &self.global_symbol_table, compiler_output.ir.as_ref().unwrap())?;
 
         compiler_output.llir = Some(llir::generate_llir(compiler_output.ir.as_ref().unwrap())?);
         if self.options.optimize_llir {
@@ -155,3 +267,111 @@ impl Compiler {
         Ok(compiler_output)
     }
 }
+
+/// Gives every static variable an address. Statics marked with `zp` are placed in zero page first,
+/// followed by the pointer used for `(zp),Y` addressing, and then the most used byte and word
+/// statics while there's room. Everything else goes in static RAM in the order it was declared.
+/// Zero page and static RAM that the program declares registers or memory in are never handed out.
+fn place_statics(
+    options: &CompilerOptions,
+    global_symbol_table: &RwLock<SymbolTable>,
+    ir: &[ir::Block],
+) -> error::Result<()> {
+    // An empty range starts right after its end
+    if options.static_ram_start as usize > options.static_ram_end as usize + 1 {
+        return Err(ErrorKind::InvalidStaticRamRange(options.static_ram_start, options.static_ram_end).into());
+    }
+    let mut zero_page = ZeroPageAllocator::new(options.zero_page_start, options.zero_page_end, options.data_stack_end)?;
+    let mut symbol_table = global_symbol_table.write().unwrap();
+    let mut declared: Vec<(u16, usize)> = symbol_table
+        .variables()
+        .filter_map(|variable| match (variable.location, variable.base_type.size()) {
+            (Location::Global(address), Some(size)) if size > 0 => Some((address, size)),
+            _ => None,
+        })
+        .collect();
+    // Pointers declared with `memory` are constants, and at least their first element is in use
+    for constant in symbol_table.constants() {
+        if let (&BaseType::Pointer(ref element_type), &ConstantValue::Number(address)) =
+            (&constant.base_type, &constant.value)
+        {
+            if let (true, Some(size)) = (0 <= address && address <= 0xFFFF, element_type.size()) {
+                declared.push((address as u16, max(size, 1)));
+            }
+        }
+    }
+    for &(address, size) in &declared {
+        if address < 0x100 {
+            zero_page.declare(address, size);
+        }
+    }
+    let statics: Vec<(SymbolRef, Variable)> = symbol_table
+        .undetermined_globals()
+        .into_iter()
+        .map(|symbol_ref| (symbol_ref, symbol_table.variable(symbol_ref).unwrap()))
+        .collect();
+
+    for &(symbol_ref, ref variable) in &statics {
+        if let Location::UndeterminedZeroPage = variable.location {
+            match zero_page.allocate(variable.base_type.size().unwrap()) {
+                Some(address) => symbol_table.set_variable_location(symbol_ref, Location::Global(address)),
+                None => {
+                    let name = symbol_table.get_symbol_name(symbol_ref).unwrap();
+                    return Err(ErrorKind::OutOfZeroPage(name).into());
+                }
+            }
+        }
+    }
+
+    if let Some(address) = zero_page.allocate(BaseType::U16.size().unwrap()) {
+        symbol_table.insert_variable(
+            Arc::new(ZERO_PAGE_POINTER_NAME.into()),
+            Variable::new(BaseType::U16, Location::Global(address)),
+        );
+    }
+
+    let uses = ir::count_symbol_uses(ir);
+    let mut hot_statics: Vec<(usize, SymbolRef, usize)> = statics
+        .iter()
+        .filter_map(|&(symbol_ref, ref variable)| match (variable.location, &variable.base_type) {
+            (Location::UndeterminedGlobal, &BaseType::Bool)
+            | (Location::UndeterminedGlobal, &BaseType::U8)
+            | (Location::UndeterminedGlobal, &BaseType::I8)
+            | (Location::UndeterminedGlobal, &BaseType::U16)
+            | (Location::UndeterminedGlobal, &BaseType::I16) => uses
+                .get(&symbol_ref)
+                .map(|&count| (count, symbol_ref, variable.base_type.size().unwrap())),
+            _ => None,
+        })
+        .collect();
+    hot_statics.sort_by_key(|&(count, symbol_ref, _)| (Reverse(count), symbol_ref));
+    for (_, symbol_ref, size) in hot_statics {
+        if let Some(address) = zero_page.allocate(size) {
+            symbol_table.set_variable_location(symbol_ref, Location::Global(address));
+        }
+    }
+
+    let static_ram_start = options.static_ram_start as usize;
+    let static_ram_end = options.static_ram_end as usize;
+    let declared_static_ram: Vec<(usize, usize)> = declared
+        .iter()
+        .map(|&(address, size)| (address as usize, address as usize + size - 1))
+        .filter(|&(start, end)| start <= static_ram_end && static_ram_start <= end)
+        .collect();
+    let mut next_address = static_ram_start;
+    let mut static_ram_used = 0;
+    for symbol_ref in symbol_table.undetermined_globals() {
+        let size = symbol_table.variable(symbol_ref).unwrap().base_type.size().unwrap();
+        let address = skip_declared(&declared_static_ram, next_address, size);
+        symbol_table.set_variable_location(symbol_ref, Location::Global(address as u16));
+        next_address = address + size;
+        static_ram_used += size;
+    }
+    if next_address > static_ram_end + 1 {
+        let static_ram_size = (static_ram_start..static_ram_end + 1)
+            .filter(|&address| !declared_static_ram.iter().any(|&(start, end)| start <= address && address <= end))
+            .count();
+        return Err(ErrorKind::OutOfStaticRam(static_ram_used, static_ram_size).into());
+    }
+    Ok(())
+}
@@ -11,6 +11,7 @@ use std::sync::Arc;
 use compiler::CompilerOutput;
 use src_tag::SrcTag;
 use src_unit::SrcUnits;
+use symbol_table::MAX_FRAME_SIZE;
 use base_type::BaseType;
 
 error_chain! {
@@ -35,6 +36,27 @@ error_chain! {
             description("Failed to compile code")
             display("{}:{}:{}: {}", unit_name, row_col.0, row_col.1, reason)
         }
+        InvalidStaticRamRange(start: u16, end: u16) {
+            description("Invalid static RAM range")
+            display("Static RAM range ${:04X}-${:04X} starts after it ends", start, end)
+        }
+        OutOfFrame(function: Arc<String>, required: usize) {
+            description("Out of frame space")
+            display("Function \"{}\" needs a {} byte frame, but frames can hold at most {} bytes",
+                function, required, MAX_FRAME_SIZE)
+        }
+        OutOfStaticRam(required: usize, available: usize) {
+            description("Out of static RAM")
+            display("Static variables need {} bytes of RAM, but only {} bytes are available", required, available)
+        }
+        OutOfZeroPage(name: Arc<String>) {
+            description("Out of zero page")
+            display("Not enough zero page left to place static variable \"{}\"", name)
+        }
+        ZeroPageConflict(start: u8, end: u8, reserved: &'static str, address: u16) {
+            description("Zero page range conflicts with a reserved location")
+            display("Zero page range ${:02X}-${:02X} overlaps the {} at ${:02X}", start, end, reserved, address)
+        }
 
         //
         // SrcTagged Compiler Errors
@@ -47,6 +69,10 @@ error_chain! {
             description("Constant evaluation failed")
             display("Constant evaluation failed")
         }
+        DuplicateCase(src_tag: SrcTag, value: i32) {
+            description("Duplicate case")
+            display("Duplicate case for value {}", value)
+        }
         DuplicateSymbol(src_tag: SrcTag, name: Arc<String>) {
             description("Duplicate symbol")
             display("Duplicate symbol \"{}\"", name)
@@ -55,10 +81,22 @@ error_chain! {
             description("Expected N args, got M")
             display("In function call to \"{}\", expected {} arguments, got {}", function, expected, actual)
         }
+        FrameTooLarge(src_tag: SrcTag, required: usize) {
+            description("Frame too large")
+            display("Locals need a {} byte frame, but frames can hold at most {} bytes", required, MAX_FRAME_SIZE)
+        }
+        ImportFailed(src_tag: SrcTag, path: Arc<String>, reason: String) {
+            description("Failed to import file")
+            display("Couldn't import \"{}\": {}", path, reason)
+        }
         InvalidLeftValue(src_tag: SrcTag) {
             description("Invalid left value")
             display("Cannot assign into expression")
         }
+        NotInLoop(src_tag: SrcTag, keyword: &'static str) {
+            description("Keyword must be used inside of a loop")
+            display("\"{}\" can only be used inside of a loop", keyword)
+        }
         MustReturnAValue(src_tag: SrcTag) {
             description("Must return a value")
             display("Must return a value")
@@ -95,10 +133,14 @@ pub fn to_compiler_error(src_units: &SrcUnits, err: Error, compiler_output: Comp
     let (name, row_col) = match err.0 {
         ConstCantBeVoid(ref src_tag, ..)
         | ConstEvaluationFailed(ref src_tag, ..)
+        | DuplicateCase(ref src_tag, ..)
         | DuplicateSymbol(ref src_tag, ..)
         | ExpectedNArgumentsGotM(ref src_tag, ..)
+        | FrameTooLarge(ref src_tag, ..)
+        | ImportFailed(ref src_tag, ..)
         | InvalidLeftValue(ref src_tag, ..)
         | MustReturnAValue(ref src_tag, ..)
+        | NotInLoop(ref src_tag, ..)
         | OrgOutOfRange(ref src_tag, ..)
         | OutOfBounds(ref src_tag, ..)
         | SymbolNotFound(ref src_tag, ..)
@@ -9,13 +9,20 @@
 
 use std::sync::{Arc, RwLock};
 use error::{self, ErrorKind};
-use parse::ast::BinaryOperator;
+use parse::ast::{BinaryOperator, UnaryOperator};
 use src_tag::{SrcTag, SrcTagged};
 use symbol_table::{DefaultSymbolTable, FunctionMetadata, FunctionMetadataPtr, Location, ParentedSymbolTableWrapper,
-                   SymbolName, SymbolRef, SymbolTable, Variable};
+                   SymbolName, SymbolRef, SymbolTable, Variable, MAX_FRAME_SIZE};
 use base_type::BaseType;
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
+pub struct AddressOfData {
+    pub tag: SrcTag,
+    pub value: Box<Expr>,
+    pub value_type: Option<BaseType>,
+}
+
+#[derive(Debug, Clone, new)]
 pub struct ArrayIndexData {
     pub tag: SrcTag,
     pub array: SymbolRef,
@@ -23,7 +30,7 @@ pub struct ArrayIndexData {
     pub array_type: Option<BaseType>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
 pub struct BinaryOpData {
     pub tag: SrcTag,
     pub op: BinaryOperator,
@@ -32,35 +39,93 @@ pub struct BinaryOpData {
     pub right: Box<Expr>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
 pub struct CallData {
     pub tag: SrcTag,
     pub function: SymbolName,
+    /// Function pointer to call through when the called name isn't a function
+    pub pointer: Option<Box<Expr>>,
     pub arguments: Vec<Expr>,
     pub return_type: Option<BaseType>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
+pub struct CastData {
+    pub tag: SrcTag,
+    pub value: Box<Expr>,
+    pub cast_type: BaseType,
+}
+
+#[derive(Debug, Clone, new)]
+pub struct DereferenceData {
+    pub tag: SrcTag,
+    pub value: Box<Expr>,
+    pub value_type: Option<BaseType>,
+}
+
+#[derive(Debug, Clone, new)]
+pub struct FieldAccessData {
+    pub tag: SrcTag,
+    pub value: Box<Expr>,
+    pub field: SymbolName,
+    /// Byte offset of the field from the start of its struct
+    pub offset: Option<usize>,
+    /// True when the field is accessed through a pointer to the struct
+    pub indirect: bool,
+    pub value_type: Option<BaseType>,
+}
+
+#[derive(Debug, Clone, new)]
 pub struct NumberData {
     pub tag: SrcTag,
     pub value: i32,
     pub value_type: Option<BaseType>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
 pub struct SymbolData {
     pub tag: SrcTag,
     pub symbol: SymbolRef,
     pub value_type: Option<BaseType>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, new)]
+pub struct UnaryOpData {
+    pub tag: SrcTag,
+    pub op: UnaryOperator,
+    pub result_type: Option<BaseType>,
+    pub value: Box<Expr>,
+}
+
+/// Builds a u16 out of two bytes
+#[derive(Debug, Clone, new)]
+pub struct WordData {
+    pub tag: SrcTag,
+    pub high: Box<Expr>,
+    pub low: Box<Expr>,
+}
+
+/// Reads the byte at an address, for `peek(address)`
+#[derive(Debug, Clone, new)]
+pub struct PeekData {
+    pub tag: SrcTag,
+    pub address: Box<Expr>,
+}
+
+#[derive(Debug, Clone)]
 pub enum Expr {
     Number(NumberData),
     Symbol(SymbolData),
     BinaryOp(BinaryOpData),
+    UnaryOp(UnaryOpData),
     Call(CallData),
     ArrayIndex(ArrayIndexData),
+    FieldAccess(FieldAccessData),
+    Cast(CastData),
+    AddressOf(AddressOfData),
+    Dereference(DereferenceData),
+    Peek(PeekData),
+    Word(WordData),
 }
 
 impl SrcTagged for Expr {
@@ -70,13 +135,20 @@ impl SrcTagged for Expr {
             Number(ref d) => d.tag,
             Symbol(ref d) => d.tag,
             BinaryOp(ref d) => d.tag,
+            UnaryOp(ref d) => d.tag,
             Call(ref d) => d.tag,
             ArrayIndex(ref d) => d.tag,
+            FieldAccess(ref d) => d.tag,
+            Cast(ref d) => d.tag,
+            AddressOf(ref d) => d.tag,
+            Dereference(ref d) => d.tag,
+            Peek(ref d) => d.tag,
+            Word(ref d) => d.tag,
         }
     }
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
 pub struct AssignData {
     pub tag: SrcTag,
     pub value_type: Option<BaseType>,
@@ -84,7 +156,23 @@ pub struct AssignData {
     pub right_value: Expr,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
+pub struct BreakData {
+    pub tag: SrcTag,
+}
+
+/// An assignment like `x += 1` that combines the current value of the left value with the right value.
+/// The left value is only evaluated once, and is both the left operand and the destination.
+#[derive(Debug, Clone, new)]
+pub struct CompoundAssignData {
+    pub tag: SrcTag,
+    pub op: BinaryOperator,
+    pub value_type: Option<BaseType>,
+    pub left_value: Expr,
+    pub right_value: Expr,
+}
+
+#[derive(Debug, Clone, new)]
 pub struct ConditionalData {
     pub tag: SrcTag,
     pub condition: Expr,
@@ -92,40 +180,116 @@ pub struct ConditionalData {
     pub when_false: Vec<Statement>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
+pub struct ContinueData {
+    pub tag: SrcTag,
+}
+
+#[derive(Debug, Clone, new)]
 pub struct GoToData {
     pub tag: SrcTag,
     pub destination: Arc<String>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
 pub struct InlineAsmData {
     pub tag: SrcTag,
     pub asm: Arc<String>,
 }
 
-#[derive(Debug, new)]
+/// A single 6502 instruction that's written like a function call
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Intrinsic {
+    /// `brk()`
+    Break,
+    /// `clc()`
+    ClearCarry,
+    /// `cld()`
+    ClearDecimal,
+    /// `cli()`
+    ClearInterruptDisable,
+    /// `nop()`
+    NoOp,
+    /// `rol(value)` rotates a value left through the carry flag in place. The carry flag going in is
+    /// whatever `sec()`, `clc()` or the last shift or rotate left in it.
+    RotateLeft,
+    /// `ror(value)` rotates a value right through the carry flag in place
+    RotateRight,
+    /// `sec()`
+    SetCarry,
+    /// `sed()`
+    SetDecimal,
+    /// `sei()`
+    SetInterruptDisable,
+}
+
+#[derive(Debug, Clone, new)]
+pub struct IntrinsicData {
+    pub tag: SrcTag,
+    pub intrinsic: Intrinsic,
+    /// The value that's rotated, for the intrinsics that take one
+    pub value: Option<Expr>,
+}
+
+/// Writes a byte to an address, for `poke(address, value)`
+#[derive(Debug, Clone, new)]
+pub struct PokeData {
+    pub tag: SrcTag,
+    pub address: Expr,
+    pub value: Expr,
+}
+
+/// A constant that a match case compares against
+#[derive(Debug, Clone, new)]
+pub struct MatchValue {
+    pub tag: SrcTag,
+    pub value: u8,
+    /// Type of the case's constant, which must match the type of the matched value
+    pub value_type: BaseType,
+}
+
+#[derive(Debug, Clone, new)]
+pub struct MatchCase {
+    pub tag: SrcTag,
+    pub values: Vec<MatchValue>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, new)]
+pub struct MatchData {
+    pub tag: SrcTag,
+    pub value: Expr,
+    pub cases: Vec<MatchCase>,
+    pub default: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, new)]
 pub struct ReturnData {
     pub tag: SrcTag,
     pub value_type: Option<BaseType>,
     pub value: Option<Expr>,
 }
 
-#[derive(Debug, new)]
+#[derive(Debug, Clone, new)]
 pub struct WhileLoopData {
     pub tag: SrcTag,
     pub condition: Expr,
     pub body: Vec<Statement>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     Assign(AssignData),
-    Break,
+    Break(BreakData),
     Call(CallData),
+    CompoundAssign(CompoundAssignData),
     Conditional(ConditionalData),
+    Continue(ContinueData),
     GoTo(GoToData),
     InlineAsm(InlineAsmData),
+    Intrinsic(IntrinsicData),
+    Match(MatchData),
+    Poke(PokeData),
     Return(ReturnData),
     WhileLoop(WhileLoopData),
 }
@@ -157,6 +321,9 @@ impl Block {
                 parameters: Vec::new(),
                 return_type: BaseType::Void,
                 frame_size: 0,
+                indirect_entry: None,
+                interrupt: false,
+                inline: false,
             })),
             anonymous: true,
         }
@@ -177,10 +344,13 @@ impl Block {
             .iter()
             .map(|p| p.base_type.size().unwrap())
             .fold(0, |acc, size| acc + size);
+        if frame_size > MAX_FRAME_SIZE {
+            return Err(ErrorKind::FrameTooLarge(src_tag, frame_size).into());
+        }
         let handle_gen = parent_symbol_table.read().unwrap().handle_gen();
         let mut symbol_table = ParentedSymbolTableWrapper::new(
             parent_symbol_table,
-            Box::new(DefaultSymbolTable::new(handle_gen, frame_size as i8)),
+            Box::new(DefaultSymbolTable::new(handle_gen, frame_size)),
         );
 
         let mut frame_offset = 0i8;
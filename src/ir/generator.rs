@@ -12,14 +12,17 @@ use error::{self, ErrorKind};
 use ir;
 use parse::ast;
 use src_tag::{SrcTag, SrcTagged};
-use symbol_table::{ConstantValue, FunctionMetadata, FunctionMetadataPtr, Location, SymbolName, SymbolTable, Variable};
-use base_type::BaseType;
+use symbol_table::{ConstantValue, FunctionMetadata, FunctionMetadataPtr, Location, SymbolName, SymbolRef, SymbolTable,
+                   Variable, MAX_FRAME_SIZE};
+use base_type::{BaseType, EnumType, StructType};
 
 pub fn generate(
     global_symbol_table: &Arc<RwLock<SymbolTable>>,
     input: &[ast::Expression],
+    inline_small_functions: bool,
 ) -> error::Result<Vec<ir::Block>> {
     let mut blocks = vec![ir::Block::new_anonymous(Arc::clone(global_symbol_table))];
+    let mut static_initializers = Vec::new();
 
     for ast_expr in input {
         match *ast_expr {
@@ -32,12 +35,46 @@ pub fn generate(
                 };
 
                 let symbol_name = SymbolName::clone(&data.name);
+                let mut parameters = Vec::new();
+                for parameter in &data.parameters {
+                    let base_type =
+                        resolve_named_type(&*global_symbol_table.read().unwrap(), data.tag, &parameter.base_type)?;
+                    if base_type.is_array() {
+                        return Err(ErrorKind::TypeExprError(
+                            data.tag,
+                            format!("Can't pass {} by value; use a pointer instead", base_type),
+                        ).into());
+                    }
+                    parameters.push(ast::NameType::new(SymbolName::clone(&parameter.name), base_type));
+                }
+                let return_type =
+                    resolve_named_type(&*global_symbol_table.read().unwrap(), data.tag, &data.return_type)?;
+                if return_type.struct_type().is_some() {
+                    return Err(ErrorKind::TypeExprError(data.tag, "Functions can't return a struct".into()).into());
+                }
+                if return_type.is_array() {
+                    return Err(ErrorKind::TypeExprError(data.tag, "Functions can't return an array".into()).into());
+                }
+                if data.interrupt && !parameters.is_empty() {
+                    return Err(
+                        ErrorKind::TypeExprError(data.tag, "Interrupt handlers can't take parameters".into()).into(),
+                    );
+                }
+                if data.interrupt && return_type != BaseType::Void {
+                    return Err(
+                        ErrorKind::TypeExprError(data.tag, "Interrupt handlers can't return a value".into()).into(),
+                    );
+                }
+
                 let metadata = Arc::new(RwLock::new(FunctionMetadata {
                     name: Arc::clone(&data.name),
                     location: location,
-                    parameters: data.parameters.clone(),
-                    return_type: data.return_type.clone(),
+                    parameters: parameters,
+                    return_type: return_type,
                     frame_size: 127, // 127 is an intentional non-sensical value
+                    indirect_entry: None,
+                    interrupt: data.interrupt,
+                    inline: data.inline,
                 }));
 
                 let optional_function_ref = global_symbol_table.write().unwrap().insert_function(
@@ -53,13 +90,62 @@ pub fn generate(
                         FunctionMetadataPtr::clone(&metadata),
                     )?;
 
-                    let body_ir = generate_statement_irs(&mut *function.symbol_table.write().unwrap(), &data.body)?;
+                    let body_ir = generate_statement_irs(&mut *function.symbol_table.write().unwrap(), &data.body, false)?;
                     function.body.extend(body_ir);
                     blocks.push(function);
                 } else {
                     return Err(ErrorKind::DuplicateSymbol(data.tag, Arc::clone(&symbol_name)).into());
                 }
             }
+            ast::Expression::DeclareVariable(ref data) if data.name_type.base_type.is_array() => {
+                // Arrays declared at the top level live in static RAM rather than in a frame
+                let mut symbol_table = global_symbol_table.write().unwrap();
+                let base_type = resolve_named_type(&*symbol_table, data.tag, &data.name_type.base_type)?;
+                check_array_declaration(data, &base_type)?;
+                let variable = Variable::new(base_type, Location::UndeterminedGlobal);
+                if symbol_table
+                    .insert_variable(SymbolName::clone(&data.name_type.name), variable)
+                    .is_none()
+                {
+                    return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name_type.name)).into());
+                }
+            }
+            ast::Expression::DeclareStatic(ref static_data) => {
+                let data = &static_data.variable;
+                let mut symbol_table = global_symbol_table.write().unwrap();
+                let base_type = resolve_named_type(&*symbol_table, data.tag, &data.name_type.base_type)?;
+                if base_type.is_array() {
+                    check_array_declaration(data, &base_type)?;
+                } else if base_type.size().is_none() {
+                    return Err(ErrorKind::TypeMustHaveSize(data.tag, SymbolName::clone(&data.name_type.name)).into());
+                }
+                let location = if static_data.zero_page {
+                    Location::UndeterminedZeroPage
+                } else {
+                    Location::UndeterminedGlobal
+                };
+                let variable = Variable::new(base_type.clone(), location);
+                match symbol_table.insert_variable(SymbolName::clone(&data.name_type.name), variable) {
+                    Some(symbol_ref) => if let Some(ref value) = data.value {
+                        // Initializers run before the data stack is set up, so they can only store constants
+                        let number = match constant_eval(&mut *symbol_table, &base_type, value)? {
+                            ConstantValue::Number(number) => number,
+                            ConstantValue::Bytes(_) | ConstantValue::Functions(_) => {
+                                return Err(ErrorKind::ConstEvaluationFailed(value.src_tag()).into())
+                            }
+                        };
+                        static_initializers.push(ir::Statement::Assign(ir::AssignData::new(
+                            data.tag,
+                            None,
+                            ir::Expr::Symbol(ir::SymbolData::new(data.tag, symbol_ref, None)),
+                            ir::Expr::Number(ir::NumberData::new(value.src_tag(), number, Some(base_type))),
+                        )));
+                    },
+                    None => {
+                        return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name_type.name)).into())
+                    }
+                }
+            }
             ast::Expression::Org(ref data) => {
                 if data.address < 0x200 || data.address > 0xFFFF {
                     return Err(ErrorKind::OrgOutOfRange(data.tag).into());
@@ -69,15 +155,22 @@ pub fn generate(
             ast::Expression::Comment => {}
             ast::Expression::Error => unreachable!("error"),
             ast::Expression::BinaryOp { .. } => unreachable!("binary_op"),
+            ast::Expression::Bool(_) => unreachable!("bool"),
             ast::Expression::Number(_) => unreachable!("number"),
             ast::Expression::Name(_) => unreachable!("name"),
             _ => {
-                let stmt = generate_statement_ir(&mut *global_symbol_table.write().unwrap(), ast_expr)?;
+                let stmt = generate_statement_ir(&mut *global_symbol_table.write().unwrap(), ast_expr, false)?;
                 blocks.last_mut().unwrap().body.extend(stmt);
             }
         }
     }
 
+    // Statics are initialized before anything else runs in the first block, which is where the program starts
+    let entry_block = &mut blocks[0];
+    static_initializers.extend(entry_block.body.drain(..));
+    entry_block.body = static_initializers;
+
+    ir::inline::inline_functions(global_symbol_table, &mut blocks, inline_small_functions)?;
     ir::type_checker::resolve_types(&mut blocks)?;
     Ok(blocks)
 }
@@ -85,17 +178,24 @@ pub fn generate(
 fn generate_statement_irs(
     symbol_table: &mut SymbolTable,
     input: &[ast::Expression],
+    in_loop: bool,
 ) -> error::Result<Vec<ir::Statement>> {
     let mut statements: Vec<ir::Statement> = vec![];
 
     for ast_expr in input {
-        statements.extend(generate_statement_ir(symbol_table, ast_expr)?);
+        statements.extend(generate_statement_ir(symbol_table, ast_expr, in_loop)?);
     }
 
     Ok(statements)
 }
 
-fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression) -> error::Result<Vec<ir::Statement>> {
+/// Generates the IR for a single statement. `in_loop` is true when the statement is
+/// somewhere inside of the body of a loop, which allows `break` and `continue`.
+fn generate_statement_ir(
+    symbol_table: &mut SymbolTable,
+    input: &ast::Expression,
+    in_loop: bool,
+) -> error::Result<Vec<ir::Statement>> {
     let mut statements: Vec<ir::Statement> = vec![];
     match *input {
         ast::Expression::Assignment(ref data) => {
@@ -108,13 +208,31 @@ fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression
                 right_value,
             )));
         }
-        ast::Expression::Break => {
-            unimplemented!("ir_gen: break");
+        ast::Expression::CompoundAssignment(ref data) => {
+            let left_value = generate_expression(symbol_table, &data.left_value)?;
+            let right_value = generate_expression(symbol_table, &data.right_value)?;
+            statements.push(ir::Statement::CompoundAssign(ir::CompoundAssignData::new(
+                data.tag,
+                data.op,
+                None,
+                left_value,
+                right_value,
+            )));
+        }
+        ast::Expression::Break(ref data) => {
+            if !in_loop {
+                return Err(ErrorKind::NotInLoop(data.tag, "break").into());
+            }
+            statements.push(ir::Statement::Break(ir::BreakData::new(data.tag)));
+        }
+        ast::Expression::CallFunction(ref data) if is_builtin_call(symbol_table, &INTRINSIC_CALLS, data) => {
+            statements.push(generate_intrinsic_call(symbol_table, data)?);
         }
         ast::Expression::CallFunction(ref data) => {
             let stmt = ir::Statement::Call(ir::CallData::new(
                 data.tag,
                 SymbolName::clone(&data.name),
+                generate_function_pointer(symbol_table, data),
                 generate_expressions(symbol_table, &data.arguments)?,
                 None,
             ));
@@ -122,8 +240,8 @@ fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression
         }
         ast::Expression::Conditional(ref data) => {
             let condition = generate_expression(symbol_table, &data.condition)?;
-            let when_true = generate_statement_irs(symbol_table, &data.when_true)?;
-            let when_false = generate_statement_irs(symbol_table, &data.when_false)?;
+            let when_true = generate_statement_irs(symbol_table, &data.when_true, in_loop)?;
+            let when_false = generate_statement_irs(symbol_table, &data.when_false, in_loop)?;
             statements.push(ir::Statement::Conditional(ir::ConditionalData::new(
                 data.tag,
                 condition,
@@ -131,15 +249,49 @@ fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression
                 when_false,
             )));
         }
+        ast::Expression::Match(ref data) => {
+            let value = generate_expression(symbol_table, &data.value)?;
+            let mut matched_values = Vec::new();
+            let mut cases = Vec::new();
+            for case in &data.cases {
+                let mut values = Vec::new();
+                for case_value in &case.values {
+                    let number = match constant_eval(symbol_table, &BaseType::U8, case_value)? {
+                        ConstantValue::Number(number) => number,
+                        ConstantValue::Bytes(_) | ConstantValue::Functions(_) => {
+                            return Err(ErrorKind::ConstEvaluationFailed(case_value.src_tag()).into())
+                        }
+                    };
+                    if matched_values.contains(&number) {
+                        return Err(ErrorKind::DuplicateCase(case_value.src_tag(), number).into());
+                    }
+                    matched_values.push(number);
+                    let value_type = constant_type(symbol_table, case_value);
+                    values.push(ir::MatchValue::new(case_value.src_tag(), number as u8, value_type));
+                }
+                let body = generate_statement_irs(symbol_table, &case.body, in_loop)?;
+                cases.push(ir::MatchCase::new(case.tag, values, body));
+            }
+            let default = generate_statement_irs(symbol_table, &data.default, in_loop)?;
+            statements.push(ir::Statement::Match(ir::MatchData::new(
+                data.tag,
+                value,
+                cases,
+                default,
+            )));
+        }
+        ast::Expression::Continue(ref data) => {
+            if !in_loop {
+                return Err(ErrorKind::NotInLoop(data.tag, "continue").into());
+            }
+            statements.push(ir::Statement::Continue(ir::ContinueData::new(data.tag)));
+        }
         ast::Expression::DeclareConst(ref data) => {
             let symbol_name = SymbolName::clone(&data.name_type.name);
-            let value = constant_eval(symbol_table, &data.name_type.base_type, &*data.value)?;
+            let base_type = resolve_named_type(symbol_table, data.tag, &data.name_type.base_type)?;
+            let value = constant_eval(symbol_table, &base_type, &*data.value)?;
             if symbol_table
-                .insert_constant(
-                    SymbolName::clone(&symbol_name),
-                    &data.name_type.base_type,
-                    value,
-                )
+                .insert_constant(SymbolName::clone(&symbol_name), &base_type, value)
                 .is_none()
             {
                 return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name_type.name)).into());
@@ -150,36 +302,76 @@ fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression
                 return Err(ErrorKind::OutOfBounds(data.tag, data.location as isize, 0, 0xFFFF).into());
             }
             let symbol_name = SymbolName::clone(&data.name_type.name);
-            let variable = Variable::new(
-                data.name_type.base_type.clone(),
-                Location::Global(data.location as u16),
-            );
+            let base_type = resolve_named_type(symbol_table, data.tag, &data.name_type.base_type)?;
+            // A pointer in memory names the array at that address, so the pointer itself is a constant
+            let inserted = if base_type.is_pointer() {
+                symbol_table.insert_constant(symbol_name, &base_type, ConstantValue::Number(data.location))
+            } else {
+                let location = Location::Global(data.location as u16);
+                symbol_table.insert_variable(symbol_name, Variable::new(base_type, location))
+            };
+            if inserted.is_none() {
+                return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name_type.name)).into());
+            }
+        }
+        ast::Expression::DeclareStruct(ref data) => {
+            let mut fields: Vec<(SymbolName, BaseType)> = Vec::new();
+            for field in &data.fields {
+                if fields.iter().any(|&(ref name, _)| *name == field.name) {
+                    return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&field.name)).into());
+                }
+                let base_type = resolve_named_type(symbol_table, data.tag, &field.base_type)?;
+                if base_type.size().is_none() {
+                    return Err(ErrorKind::TypeMustHaveSize(data.tag, SymbolName::clone(&field.name)).into());
+                }
+                fields.push((SymbolName::clone(&field.name), base_type));
+            }
+
+            let struct_type = StructType::new(SymbolName::clone(&data.name), fields);
             if symbol_table
-                .insert_variable(symbol_name, variable)
+                .insert_type(SymbolName::clone(&data.name), BaseType::Struct(Arc::new(struct_type)))
                 .is_none()
             {
-                return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name_type.name)).into());
+                return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name)).into());
             }
         }
+        ast::Expression::DeclareType(ref data) => {
+            let base_type = resolve_named_type(symbol_table, data.tag, &data.base_type)?;
+            if symbol_table
+                .insert_type(SymbolName::clone(&data.name), base_type)
+                .is_none()
+            {
+                return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name)).into());
+            }
+        }
+        ast::Expression::DeclareEnum(ref data) => generate_enum(symbol_table, data)?,
         ast::Expression::DeclareVariable(ref data) => {
             let symbol_name = SymbolName::clone(&data.name_type.name);
-            if data.name_type.base_type.size().is_none() {
+            let base_type = resolve_named_type(symbol_table, data.tag, &data.name_type.base_type)?;
+            if base_type.size().is_none() {
                 return Err(ErrorKind::TypeMustHaveSize(data.tag, SymbolName::clone(&data.name_type.name)).into());
             }
+            if base_type.is_array() {
+                check_array_declaration(data, &base_type)?;
+            }
+            let frame_size = symbol_table.frame_size() + base_type.size().unwrap();
+            if frame_size > MAX_FRAME_SIZE {
+                return Err(ErrorKind::FrameTooLarge(data.tag, frame_size).into());
+            }
 
-            let next_location = symbol_table.next_frame_offset(data.name_type.base_type.size().unwrap());
-            let variable = Variable::new(
-                data.name_type.base_type.clone(),
-                Location::FrameOffset(next_location as i8),
-            );
+            let next_location = symbol_table.next_frame_offset(base_type.size().unwrap());
+            let variable = Variable::new(base_type, Location::FrameOffset(next_location as i8));
             if let Some(symbol_ref) = symbol_table.insert_variable(SymbolName::clone(&symbol_name), variable) {
-                let assignment = ir::Statement::Assign(ir::AssignData::new(
-                    data.tag,
-                    None,
-                    ir::Expr::Symbol(ir::SymbolData::new(data.tag, symbol_ref, None)),
-                    generate_expression(symbol_table, &data.value)?,
-                ));
-                statements.push(assignment);
+                // Variables declared without a value are left uninitialized
+                if let Some(ref value) = data.value {
+                    let assignment = ir::Statement::Assign(ir::AssignData::new(
+                        data.tag,
+                        None,
+                        ir::Expr::Symbol(ir::SymbolData::new(data.tag, symbol_ref, None)),
+                        generate_expression(symbol_table, value)?,
+                    ));
+                    statements.push(assignment);
+                }
             } else {
                 return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name_type.name)).into());
             }
@@ -209,7 +401,7 @@ fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression
         }
         ast::Expression::WhileLoop(ref data) => {
             let condition = generate_expression(symbol_table, &data.condition)?;
-            let body = generate_statement_irs(symbol_table, &data.body)?;
+            let body = generate_statement_irs(symbol_table, &data.body, true)?;
             statements.push(ir::Statement::WhileLoop(ir::WhileLoopData::new(
                 data.tag,
                 condition,
@@ -217,14 +409,24 @@ fn generate_statement_ir(symbol_table: &mut SymbolTable, input: &ast::Expression
             )));
         }
         ast::Expression::Comment => {}
+        ast::Expression::AddressOf(_) => unreachable!("address_of"),
         ast::Expression::ArrayIndex(_) => unreachable!("array_index"),
+        ast::Expression::ArrayLiteral(_) => unreachable!("array_literal"),
         ast::Expression::BinaryOp { .. } => unreachable!("binary_op"),
+        ast::Expression::Bool(_) => unreachable!("bool"),
+        ast::Expression::Cast(_) => unreachable!("cast"),
         ast::Expression::DeclareFunction { .. } => unreachable!("declare_function"),
+        ast::Expression::DeclareStatic(_) => unreachable!("declare_static"),
+        ast::Expression::Dereference(_) => unreachable!("dereference"),
         ast::Expression::Error => unreachable!("error"),
+        ast::Expression::FieldAccess(_) => unreachable!("field_access"),
+        ast::Expression::Import(_) => unreachable!("import"),
         ast::Expression::Name(_) => unreachable!("name"),
         ast::Expression::Number(_) => unreachable!("number"),
         ast::Expression::Org { .. } => unreachable!("org"),
+        ast::Expression::SizeOf(_) => unreachable!("sizeof"),
         ast::Expression::Text(_) => unreachable!("text"),
+        ast::Expression::UnaryOp(_) => unreachable!("unary_op"),
     }
 
     Ok(statements)
@@ -240,10 +442,32 @@ fn constant_eval(
             let left = constant_eval(symbol_table, base_type, &*data.left)?;
             let right = constant_eval(symbol_table, base_type, &*data.right)?;
             match *base_type {
-                BaseType::Bool | BaseType::U8 | BaseType::U16 => {
+                BaseType::Bool | BaseType::I8 | BaseType::I16 | BaseType::U8 | BaseType::U16 => {
                     Ok(constant_eval_binop(data.tag, data.op, &left, &right)?)
                 }
-                BaseType::Pointer(_) => unimplemented!(),
+                BaseType::Pointer(ref element_type) => {
+                    constant_eval_pointer_arithmetic(data, element_type, &left, &right)
+                }
+                BaseType::Array(_, _)
+                | BaseType::Enum(_)
+                | BaseType::Function(_, _)
+                | BaseType::Named(_)
+                | BaseType::Struct(_) => Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
+                BaseType::Void => Err(ErrorKind::ConstCantBeVoid(data.tag).into()),
+            }
+        }
+        ast::Expression::UnaryOp(ref data) => {
+            let value = constant_eval(symbol_table, base_type, &*data.value)?;
+            match *base_type {
+                BaseType::Bool | BaseType::I8 | BaseType::I16 | BaseType::U8 | BaseType::U16 => {
+                    Ok(constant_eval_unaryop(base_type, data.op, &value))
+                }
+                BaseType::Array(_, _)
+                | BaseType::Enum(_)
+                | BaseType::Function(_, _)
+                | BaseType::Named(_)
+                | BaseType::Pointer(_)
+                | BaseType::Struct(_) => Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
                 BaseType::Void => Err(ErrorKind::ConstCantBeVoid(data.tag).into()),
             }
         }
@@ -252,10 +476,25 @@ fn constant_eval(
             None => Err(ErrorKind::SymbolNotFound(data.tag, SymbolName::clone(&data.name)).into()),
         },
         ast::Expression::Number(ref data) => constant_eval_number(base_type, data),
+        ast::Expression::SizeOf(ref data) => {
+            constant_eval_number(base_type, &ast::NumberData::new(data.tag, size_of(symbol_table, data)?))
+        }
+        ast::Expression::CallFunction(ref data) if is_builtin_call(symbol_table, &OPERATOR_CALLS, data) => {
+            constant_eval_operator_call(symbol_table, base_type, data)
+        }
+        ast::Expression::Bool(ref data) => {
+            if BaseType::Bool.can_assign_into(base_type) {
+                Ok(ConstantValue::Number(if data.value { 1 } else { 0 }))
+            } else {
+                Err(ErrorKind::TypeError(data.tag, base_type.clone(), BaseType::Bool).into())
+            }
+        }
         ast::Expression::Text(ref data) => {
             if let BaseType::Pointer(_) = *base_type {
                 let mut bytes = Vec::new();
                 bytes.extend(data.value.as_bytes().iter());
+                // Strings are null terminated
+                bytes.push(0);
                 Ok(ConstantValue::Bytes(Arc::new(bytes)))
             } else {
                 Err(ErrorKind::TypeError(
@@ -265,13 +504,121 @@ fn constant_eval(
                 ).into())
             }
         }
+        ast::Expression::FieldAccess(ref data) => match enum_variant(symbol_table, data) {
+            Some(variant) => Ok(symbol_table.constant(variant?).unwrap().value),
+            None => Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
+        },
+        ast::Expression::ArrayLiteral(ref data) => match *base_type {
+            BaseType::Pointer(ref element_type) => constant_eval_table(symbol_table, element_type, data),
+            _ => Err(ErrorKind::TypeExprError(
+                data.tag,
+                format!("Can't initialize {} with an array literal", base_type),
+            ).into()),
+        },
         _ => Err(ErrorKind::ConstEvaluationFailed(input.src_tag()).into()),
     }
 }
 
+/// Evaluates each value in an array literal and lays them out as little-endian bytes
+fn constant_eval_table(
+    symbol_table: &mut SymbolTable,
+    element_type: &BaseType,
+    input: &ast::ArrayLiteralData,
+) -> error::Result<ConstantValue> {
+    if element_type.is_function() {
+        return constant_eval_function_table(symbol_table, element_type, input);
+    }
+    let element_size = match *element_type {
+        BaseType::I8 | BaseType::U8 | BaseType::I16 | BaseType::U16 => element_type.size().unwrap(),
+        _ => {
+            return Err(ErrorKind::TypeExprError(
+                input.tag,
+                format!("Array literals can't hold values of type {}", element_type),
+            ).into())
+        }
+    };
+    let mut bytes = Vec::new();
+    for value in &input.values {
+        let number = match constant_eval(symbol_table, element_type, value)? {
+            ConstantValue::Number(number) => number,
+            ConstantValue::Bytes(_) | ConstantValue::Functions(_) => {
+                return Err(ErrorKind::ConstEvaluationFailed(value.src_tag()).into())
+            }
+        };
+        for index in 0..element_size {
+            bytes.push((number >> (8 * index)) as u8);
+        }
+    }
+    Ok(ConstantValue::Bytes(Arc::new(bytes)))
+}
+
+/// Looks up each function named in an array literal. Their addresses are filled in when the table is emitted.
+fn constant_eval_function_table(
+    symbol_table: &mut SymbolTable,
+    element_type: &BaseType,
+    input: &ast::ArrayLiteralData,
+) -> error::Result<ConstantValue> {
+    let mut functions = Vec::new();
+    for value in &input.values {
+        let function_ref = match *value {
+            ast::Expression::Name(ref data) => match symbol_table.function_by_name(&data.name) {
+                Some(_) => symbol_table.find_symbol(&data.name).unwrap(),
+                None => return Err(ErrorKind::SymbolNotFound(data.tag, SymbolName::clone(&data.name)).into()),
+            },
+            _ => return Err(ErrorKind::ConstEvaluationFailed(value.src_tag()).into()),
+        };
+        let function_type = take_function_address(symbol_table, value.src_tag(), function_ref)?;
+        if function_type != *element_type {
+            return Err(ErrorKind::TypeExprError(
+                value.src_tag(),
+                format!("Can't put {} in a table of {}", function_type, element_type),
+            ).into());
+        }
+        functions.push(function_ref);
+    }
+    Ok(ConstantValue::Functions(Arc::new(functions)))
+}
+
+/// Gives a function an entry point for calling it through a pointer, and returns the type of the pointer
+fn take_function_address(
+    symbol_table: &mut SymbolTable,
+    tag: SrcTag,
+    function_ref: SymbolRef,
+) -> error::Result<BaseType> {
+    let function = symbol_table.function(function_ref).unwrap();
+    let mut metadata = function.write().unwrap();
+    if metadata.interrupt {
+        return Err(ErrorKind::TypeExprError(
+            tag,
+            format!("Can't take the address of interrupt handler {}", metadata.name),
+        ).into());
+    }
+    if metadata.indirect_entry.is_none() {
+        metadata.indirect_entry = Some(symbol_table.new_block_name().1);
+    }
+    Ok(metadata.function_type())
+}
+
+/// Returns the pointer that a call goes through when the called name refers to a variable or constant
+/// rather than to a function
+fn generate_function_pointer(symbol_table: &SymbolTable, data: &ast::CallFunctionData) -> Option<Box<ir::Expr>> {
+    if symbol_table.variable_by_name(&data.name).is_some() || symbol_table.constant_by_name(&data.name).is_some() {
+        let symbol_ref = symbol_table.find_symbol(&data.name).unwrap();
+        Some(Box::new(ir::Expr::Symbol(ir::SymbolData::new(data.tag, symbol_ref, None))))
+    } else {
+        None
+    }
+}
+
 fn constant_eval_number(type_name: &BaseType, input: &ast::NumberData) -> error::Result<ConstantValue> {
     match *type_name {
-        BaseType::Bool => unimplemented!(),
+        BaseType::Bool => {
+            if input.value < 0 || input.value > 1 {
+                Err(ErrorKind::OutOfBounds(input.tag, input.value as isize, 0, 1).into())
+            } else {
+                Ok(ConstantValue::Number(input.value))
+            }
+        }
         BaseType::U8 => {
             let unsigned_val = input.value as usize;
             if unsigned_val > 0xFF {
@@ -288,10 +635,329 @@ fn constant_eval_number(type_name: &BaseType, input: &ast::NumberData) -> error:
                 Ok(ConstantValue::Number(input.value))
             }
         }
+        BaseType::I8 => {
+            if input.value < -0x80 || input.value > 0x7F {
+                Err(ErrorKind::OutOfBounds(input.tag, input.value as isize, -0x80, 0x7F).into())
+            } else {
+                Ok(ConstantValue::Number(input.value))
+            }
+        }
+        BaseType::I16 => {
+            if input.value < -0x8000 || input.value > 0x7FFF {
+                Err(ErrorKind::OutOfBounds(input.tag, input.value as isize, -0x8000, 0x7FFF).into())
+            } else {
+                Ok(ConstantValue::Number(input.value))
+            }
+        }
+        // Numbers only become enum values through a cast
+        BaseType::Enum(_) => Err(ErrorKind::TypeExprError(
+            input.tag,
+            format!("Expected {}, but got a number", type_name),
+        ).into()),
+        BaseType::Array(_, _) | BaseType::Function(_, _) | BaseType::Named(_) | BaseType::Struct(_) => {
+            Err(ErrorKind::ConstEvaluationFailed(input.tag).into())
+        }
         BaseType::Void => Err(ErrorKind::ConstCantBeVoid(input.tag).into()),
     }
 }
 
+/// Returns the size of the type named in a `sizeof`. When the name isn't a type, it's the size of the
+/// variable or constant with that name.
+fn size_of(symbol_table: &SymbolTable, data: &ast::SizeOfData) -> error::Result<i32> {
+    let base_type = match data.base_type {
+        BaseType::Named(ref name) if symbol_table.type_by_name(name).is_none() => {
+            if let Some(variable) = symbol_table.variable_by_name(name) {
+                variable.base_type
+            } else if let Some(constant) = symbol_table.constant_by_name(name) {
+                constant.base_type
+            } else {
+                return Err(ErrorKind::SymbolNotFound(data.tag, SymbolName::clone(name)).into());
+            }
+        }
+        ref base_type => resolve_named_type(symbol_table, data.tag, base_type)?,
+    };
+    match base_type.size() {
+        Some(size) => Ok(size as i32),
+        None => Err(ErrorKind::TypeExprError(data.tag, format!("Can't take the size of {}", base_type)).into()),
+    }
+}
+
+/// The operators that are written like function calls, along with how many arguments they take
+const OPERATOR_CALLS: [(&str, usize); 4] = [("lo", 1), ("hi", 1), ("word", 2), ("peek", 1)];
+
+/// The statements that are written like function calls, along with how many arguments they take
+const INTRINSIC_CALLS: [(&str, usize); 11] = [
+    ("brk", 0),
+    ("clc", 0),
+    ("cld", 0),
+    ("cli", 0),
+    ("nop", 0),
+    ("poke", 2),
+    ("rol", 1),
+    ("ror", 1),
+    ("sec", 0),
+    ("sed", 0),
+    ("sei", 0),
+];
+
+/// Returns true when a call is one of the given built-ins.
+/// Anything declared with the same name takes precedence over the built-in.
+fn is_builtin_call(symbol_table: &SymbolTable, builtins: &[(&str, usize)], data: &ast::CallFunctionData) -> bool {
+    builtins.iter().any(|&(name, _)| *data.name == name) && !symbol_table.has_symbol(&data.name)
+}
+
+fn check_builtin_arguments(builtins: &[(&str, usize)], data: &ast::CallFunctionData) -> error::Result<()> {
+    let &(_, expected) = builtins.iter().find(|&&(name, _)| *data.name == name).unwrap();
+    if data.arguments.len() != expected {
+        return Err(ErrorKind::ExpectedNArgumentsGotM(
+            data.tag,
+            SymbolName::clone(&data.name),
+            expected,
+            data.arguments.len(),
+        ).into());
+    }
+    Ok(())
+}
+
+fn generate_operator_call(symbol_table: &mut SymbolTable, data: &ast::CallFunctionData) -> error::Result<ir::Expr> {
+    check_builtin_arguments(&OPERATOR_CALLS, data)?;
+    let mut arguments = generate_expressions(symbol_table, &data.arguments)?;
+    match data.name.as_str() {
+        "word" => {
+            let low = arguments.pop().unwrap();
+            let high = arguments.pop().unwrap();
+            Ok(ir::Expr::Word(ir::WordData::new(data.tag, Box::new(high), Box::new(low))))
+        }
+        "peek" => Ok(ir::Expr::Peek(ir::PeekData::new(
+            data.tag,
+            Box::new(arguments.pop().unwrap()),
+        ))),
+        name => {
+            let op = if name == "lo" {
+                ast::UnaryOperator::LowByte
+            } else {
+                ast::UnaryOperator::HighByte
+            };
+            Ok(ir::Expr::UnaryOp(ir::UnaryOpData::new(
+                data.tag,
+                op,
+                None,
+                Box::new(arguments.pop().unwrap()),
+            )))
+        }
+    }
+}
+
+fn generate_intrinsic_call(
+    symbol_table: &mut SymbolTable,
+    data: &ast::CallFunctionData,
+) -> error::Result<ir::Statement> {
+    check_builtin_arguments(&INTRINSIC_CALLS, data)?;
+    let mut arguments = generate_expressions(symbol_table, &data.arguments)?;
+    let intrinsic = match data.name.as_str() {
+        "poke" => {
+            let value = arguments.pop().unwrap();
+            let address = arguments.pop().unwrap();
+            return Ok(ir::Statement::Poke(ir::PokeData::new(data.tag, address, value)));
+        }
+        "brk" => ir::Intrinsic::Break,
+        "clc" => ir::Intrinsic::ClearCarry,
+        "cld" => ir::Intrinsic::ClearDecimal,
+        "cli" => ir::Intrinsic::ClearInterruptDisable,
+        "nop" => ir::Intrinsic::NoOp,
+        "rol" => ir::Intrinsic::RotateLeft,
+        "ror" => ir::Intrinsic::RotateRight,
+        "sec" => ir::Intrinsic::SetCarry,
+        "sed" => ir::Intrinsic::SetDecimal,
+        "sei" => ir::Intrinsic::SetInterruptDisable,
+        _ => unreachable!(),
+    };
+    Ok(ir::Statement::Intrinsic(ir::IntrinsicData::new(data.tag, intrinsic, arguments.pop())))
+}
+
+fn constant_eval_operator_call(
+    symbol_table: &mut SymbolTable,
+    base_type: &BaseType,
+    data: &ast::CallFunctionData,
+) -> error::Result<ConstantValue> {
+    check_builtin_arguments(&OPERATOR_CALLS, data)?;
+    let result = match data.name.as_str() {
+        "word" => {
+            let high = constant_eval_operand(symbol_table, &BaseType::U8, &data.arguments[0])?;
+            let low = constant_eval_operand(symbol_table, &BaseType::U8, &data.arguments[1])?;
+            (high << 8) | low
+        }
+        // Memory isn't known until the program runs
+        "peek" => return Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
+        name => {
+            let value = ConstantValue::Number(constant_eval_operand(symbol_table, &BaseType::U16, &data.arguments[0])?);
+            let op = if name == "lo" {
+                ast::UnaryOperator::LowByte
+            } else {
+                ast::UnaryOperator::HighByte
+            };
+            constant_eval_unaryop(&BaseType::U8, op, &value).number()
+        }
+    };
+    constant_eval_number(base_type, &ast::NumberData::new(data.tag, result))
+}
+
+/// Evaluates a constant that has to be a plain number
+fn constant_eval_operand(
+    symbol_table: &mut SymbolTable,
+    base_type: &BaseType,
+    input: &ast::Expression,
+) -> error::Result<i32> {
+    match constant_eval(symbol_table, base_type, input)? {
+        ConstantValue::Number(number) => Ok(number),
+        ConstantValue::Bytes(_) | ConstantValue::Functions(_) => {
+            Err(ErrorKind::ConstEvaluationFailed(input.src_tag()).into())
+        }
+    }
+}
+
+/// Declares an enum's type along with a constant for each of its variants
+fn generate_enum(symbol_table: &mut SymbolTable, data: &ast::DeclareEnumData) -> error::Result<()> {
+    let base_type = resolve_named_type(symbol_table, data.tag, &data.base_type)?;
+    if !base_type.is_integer() {
+        return Err(ErrorKind::TypeExprError(
+            data.tag,
+            format!("Enums must have an integer type, but got {}", base_type),
+        ).into());
+    }
+    let enum_type = BaseType::Enum(Arc::new(EnumType {
+        name: SymbolName::clone(&data.name),
+        base_type: base_type.clone(),
+    }));
+    if symbol_table
+        .insert_type(SymbolName::clone(&data.name), enum_type.clone())
+        .is_none()
+    {
+        return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&data.name)).into());
+    }
+
+    let mut next_value = 0;
+    for variant in &data.variants {
+        let value = variant.value.unwrap_or(next_value);
+        let value = constant_eval_number(&base_type, &ast::NumberData::new(data.tag, value))?;
+        next_value = value.number() + 1;
+        if symbol_table
+            .insert_constant(enum_variant_name(&data.name, &variant.name), &enum_type, value)
+            .is_none()
+        {
+            return Err(ErrorKind::DuplicateSymbol(data.tag, SymbolName::clone(&variant.name)).into());
+        }
+    }
+    Ok(())
+}
+
+/// Variants are stored as constants whose names can't be written in source code, so they can only be
+/// referred to through their enum
+fn enum_variant_name(enum_name: &str, variant_name: &str) -> SymbolName {
+    Arc::new(format!("{}.{}", enum_name, variant_name))
+}
+
+/// Returns the type of a constant expression: the declared type for named constants and enum variants,
+/// and u8 for everything else
+fn constant_type(symbol_table: &SymbolTable, input: &ast::Expression) -> BaseType {
+    let constant = match *input {
+        ast::Expression::Name(ref data) => symbol_table.constant_by_name(&data.name),
+        ast::Expression::FieldAccess(ref data) => match enum_variant(symbol_table, data) {
+            Some(Ok(variant)) => symbol_table.constant(variant),
+            _ => None,
+        },
+        _ => None,
+    };
+    match constant {
+        Some(constant) => constant.base_type,
+        None => BaseType::U8,
+    }
+}
+
+/// Returns the constant for a field access like `Dir.Up` when the accessed name is an enum
+fn enum_variant(symbol_table: &SymbolTable, data: &ast::FieldAccessData) -> Option<error::Result<SymbolRef>> {
+    let enum_name = match *data.value {
+        ast::Expression::Name(ref name_data) => &name_data.name,
+        _ => return None,
+    };
+    match symbol_table.type_by_name(enum_name) {
+        Some(BaseType::Enum(_)) => {
+            let variant_name = enum_variant_name(enum_name, &data.field);
+            Some(match symbol_table.find_symbol(&variant_name) {
+                Some(variant) => Ok(variant),
+                None => Err(ErrorKind::SymbolNotFound(data.tag, variant_name).into()),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn check_array_declaration(data: &ast::DeclareVariableData, base_type: &BaseType) -> error::Result<()> {
+    if base_type.size().is_none() {
+        Err(ErrorKind::TypeMustHaveSize(data.tag, SymbolName::clone(&data.name_type.name)).into())
+    } else if data.value.is_some() {
+        Err(ErrorKind::TypeExprError(data.tag, format!("Can't initialize {} with a value", base_type)).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Looks up the struct types that were referred to by name in a declaration
+fn resolve_named_type(symbol_table: &SymbolTable, tag: SrcTag, base_type: &BaseType) -> error::Result<BaseType> {
+    match *base_type {
+        BaseType::Named(ref name) => match symbol_table.type_by_name(name) {
+            Some(named_type) => Ok(named_type),
+            None => Err(ErrorKind::SymbolNotFound(tag, SymbolName::clone(name)).into()),
+        },
+        BaseType::Pointer(ref inner) => Ok(BaseType::Pointer(Box::new(
+            resolve_named_type(symbol_table, tag, inner)?,
+        ))),
+        BaseType::Array(ref inner, count) => Ok(BaseType::Array(
+            Box::new(resolve_named_type(symbol_table, tag, inner)?),
+            count,
+        )),
+        BaseType::Function(ref parameters, ref return_type) => {
+            let mut resolved_parameters = Vec::new();
+            for parameter in parameters {
+                resolved_parameters.push(resolve_named_type(symbol_table, tag, parameter)?);
+            }
+            Ok(BaseType::Function(
+                resolved_parameters,
+                Box::new(resolve_named_type(symbol_table, tag, return_type)?),
+            ))
+        }
+        _ => Ok(base_type.clone()),
+    }
+}
+
+/// Folds a constant offset into a constant address. Like pointer arithmetic at runtime,
+/// the offset counts elements rather than bytes.
+fn constant_eval_pointer_arithmetic(
+    data: &ast::BinaryOpData,
+    element_type: &BaseType,
+    left: &ConstantValue,
+    right: &ConstantValue,
+) -> error::Result<ConstantValue> {
+    let (address, offset) = match (left, right) {
+        (&ConstantValue::Number(address), &ConstantValue::Number(offset)) => (address, offset),
+        // Tables don't have an address until they're placed in the program
+        _ => return Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
+    };
+    let element_size = match element_type.size() {
+        Some(size) => size as i32,
+        None => return Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
+    };
+    let result = match data.op {
+        ast::BinaryOperator::Add => address + offset * element_size,
+        ast::BinaryOperator::Sub => address - offset * element_size,
+        _ => return Err(ErrorKind::ConstEvaluationFailed(data.tag).into()),
+    };
+    if result < 0 || result > 0xFFFF {
+        return Err(ErrorKind::OutOfBounds(data.tag, result as isize, 0, 0xFFFF).into());
+    }
+    Ok(ConstantValue::Number(result))
+}
+
 fn constant_eval_binop(
     tag: SrcTag,
     op: ast::BinaryOperator,
@@ -318,6 +984,21 @@ fn constant_eval_binop(
         }),
         Equal => Some(if left == right { 1 } else { 0 }),
         NotEqual => Some(if left != right { 1 } else { 0 }),
+        LogicalAnd => Some(if left.number() != 0 && right.number() != 0 {
+            1
+        } else {
+            0
+        }),
+        LogicalOr => Some(if left.number() != 0 || right.number() != 0 {
+            1
+        } else {
+            0
+        }),
+        BitAnd => Some(left.number() & right.number()),
+        BitOr => Some(left.number() | right.number()),
+        BitXor => Some(left.number() ^ right.number()),
+        ShiftLeft => left.number().checked_shl(right.number() as u32),
+        ShiftRight => left.number().checked_shr(right.number() as u32),
     };
     match result {
         Some(val) => Ok(ConstantValue::Number(val as i32)),
@@ -325,7 +1006,24 @@ fn constant_eval_binop(
     }
 }
 
-fn generate_expressions(symbol_table: &SymbolTable, input: &[ast::Expression]) -> error::Result<Vec<ir::Expr>> {
+fn constant_eval_unaryop(base_type: &BaseType, op: ast::UnaryOperator, value: &ConstantValue) -> ConstantValue {
+    use parse::ast::UnaryOperator::*;
+    let result = match op {
+        BitNot => !value.number(),
+        HighByte => (value.number() >> 8) & 0xFF,
+        LowByte => value.number() & 0xFF,
+        LogicalNot => if value.number() == 0 { 1 } else { 0 },
+        Negate => -value.number(),
+    };
+    // Signed constants keep their sign, but unsigned constants wrap around
+    if base_type.is_signed() {
+        ConstantValue::Number(result)
+    } else {
+        ConstantValue::Number(result & ((1 << (base_type.size().unwrap() * 8)) - 1))
+    }
+}
+
+fn generate_expressions(symbol_table: &mut SymbolTable, input: &[ast::Expression]) -> error::Result<Vec<ir::Expr>> {
     let mut result = Vec::new();
     for expression in input {
         result.push(generate_expression(symbol_table, expression)?);
@@ -333,7 +1031,7 @@ fn generate_expressions(symbol_table: &SymbolTable, input: &[ast::Expression]) -
     Ok(result)
 }
 
-fn generate_expression(symbol_table: &SymbolTable, input: &ast::Expression) -> error::Result<ir::Expr> {
+fn generate_expression(symbol_table: &mut SymbolTable, input: &ast::Expression) -> error::Result<ir::Expr> {
     match *input {
         ast::Expression::ArrayIndex(ref data) => {
             if let Some(symbol_ref) = symbol_table.find_symbol(&data.array) {
@@ -354,8 +1052,17 @@ fn generate_expression(symbol_table: &SymbolTable, input: &ast::Expression) -> e
             Box::new(generate_expression(symbol_table, &data.left)?),
             Box::new(generate_expression(symbol_table, &data.right)?),
         ))),
+        ast::Expression::UnaryOp(ref data) => Ok(ir::Expr::UnaryOp(ir::UnaryOpData::new(
+            data.tag,
+            data.op,
+            None,
+            Box::new(generate_expression(symbol_table, &data.value)?),
+        ))),
         ast::Expression::Name(ref data) => {
             if let Some(symbol_ref) = symbol_table.find_symbol(&data.name) {
+                if symbol_table.function(symbol_ref).is_some() {
+                    take_function_address(symbol_table, data.tag, symbol_ref)?;
+                }
                 Ok(ir::Expr::Symbol(ir::SymbolData::new(
                     data.tag,
                     symbol_ref,
@@ -365,21 +1072,66 @@ fn generate_expression(symbol_table: &SymbolTable, input: &ast::Expression) -> e
                 Err(ErrorKind::SymbolNotFound(data.tag, SymbolName::clone(&data.name)).into())
             }
         }
+        ast::Expression::FieldAccess(ref data) => match enum_variant(symbol_table, data) {
+            Some(variant) => Ok(ir::Expr::Symbol(ir::SymbolData::new(data.tag, variant?, None))),
+            None => Ok(ir::Expr::FieldAccess(ir::FieldAccessData::new(
+                data.tag,
+                Box::new(generate_expression(symbol_table, &data.value)?),
+                SymbolName::clone(&data.field),
+                None,
+                false,
+                None,
+            ))),
+        },
         ast::Expression::Number(ref data) => Ok(ir::Expr::Number(ir::NumberData::new(
             data.tag,
             data.value,
             None,
         ))),
+        ast::Expression::Bool(ref data) => Ok(ir::Expr::Number(ir::NumberData::new(
+            data.tag,
+            if data.value { 1 } else { 0 },
+            Some(BaseType::Bool),
+        ))),
+        ast::Expression::CallFunction(ref data) if is_builtin_call(symbol_table, &OPERATOR_CALLS, data) => {
+            generate_operator_call(symbol_table, data)
+        }
         ast::Expression::CallFunction(ref data) => {
             let function = SymbolName::clone(&data.name);
+            let pointer = generate_function_pointer(symbol_table, data);
             let args = generate_expressions(symbol_table, &data.arguments)?;
             Ok(ir::Expr::Call(ir::CallData::new(
                 data.tag,
                 function,
+                pointer,
                 args,
                 None,
             )))
         }
+        ast::Expression::SizeOf(ref data) => Ok(ir::Expr::Number(ir::NumberData::new(
+            data.tag,
+            size_of(symbol_table, data)?,
+            None,
+        ))),
+        ast::Expression::Cast(ref data) => Ok(ir::Expr::Cast(ir::CastData::new(
+            data.tag,
+            Box::new(generate_expression(symbol_table, &data.value)?),
+            resolve_named_type(symbol_table, data.tag, &data.base_type)?,
+        ))),
+        ast::Expression::AddressOf(ref data) => Ok(ir::Expr::AddressOf(ir::AddressOfData::new(
+            data.tag,
+            Box::new(generate_expression(symbol_table, &data.value)?),
+            None,
+        ))),
+        ast::Expression::Dereference(ref data) => Ok(ir::Expr::Dereference(ir::DereferenceData::new(
+            data.tag,
+            Box::new(generate_expression(symbol_table, &data.value)?),
+            None,
+        ))),
+        ast::Expression::ArrayLiteral(ref data) => Err(ErrorKind::TypeExprError(
+            data.tag,
+            "Array literals can only be used to initialize constants".into(),
+        ).into()),
         _ => panic!("not an expression: {:?}", input),
     }
 }
@@ -0,0 +1,526 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, RwLock};
+use error::{self, ErrorKind};
+use ir::block::{AssignData, Block, CallData, Expr, Statement, SymbolData};
+use src_tag::SrcTag;
+use symbol_table::{FunctionMetadataPtr, Location, SymbolName, SymbolRef, SymbolTable, Variable};
+use base_type::BaseType;
+
+/// Functions with at most this many statements that don't call anything are small enough
+/// that inlining them is cheaper than setting up a call
+const SMALL_FUNCTION_STATEMENTS: usize = 4;
+
+/// A function whose body can take the place of calls to it
+struct InlineFunction {
+    symbol_table: Arc<RwLock<SymbolTable>>,
+    metadata: FunctionMetadataPtr,
+    /// The function's body, rearranged so that every return is the last statement to run
+    body: Vec<Statement>,
+}
+
+/// Replaces calls to `inline def` functions with the bodies of those functions, with their locals
+/// moved into the caller's frame. When `small_functions` is set, small leaf functions are inlined too.
+///
+/// Only calls made as a statement, as the right side of an assignment, or as a return value are inlined.
+/// Calls anywhere else still jump to the function, so it is always generated as well.
+pub fn inline_functions(
+    global_symbol_table: &Arc<RwLock<SymbolTable>>,
+    blocks: &mut [Block],
+    small_functions: bool,
+) -> error::Result<()> {
+    let mut functions = HashMap::new();
+    for block in blocks.iter().filter(|block| !block.anonymous) {
+        let metadata = block.metadata.read().unwrap();
+        let small = small_functions && !metadata.interrupt && is_small_leaf(&block.body);
+        if !metadata.inline && !small {
+            continue;
+        }
+        match move_returns_to_tail(block.body.clone()) {
+            Ok(body) => {
+                functions.insert(
+                    SymbolName::clone(&metadata.name),
+                    InlineFunction {
+                        symbol_table: Arc::clone(&block.symbol_table),
+                        metadata: FunctionMetadataPtr::clone(&block.metadata),
+                        body: body,
+                    },
+                );
+            }
+            Err(tag) => if metadata.inline {
+                return Err(ErrorKind::TypeExprError(
+                    tag,
+                    format!("Inline function {} can't return from inside of a loop", metadata.name),
+                ).into());
+            },
+        }
+    }
+    if functions.is_empty() {
+        return Ok(());
+    }
+
+    for block in blocks.iter_mut().filter(|block| !block.anonymous) {
+        let body = mem::replace(&mut block.body, Vec::new());
+        let mut inliner = Inliner {
+            global_symbol_table: &*global_symbol_table.read().unwrap(),
+            symbol_table: &mut *block.symbol_table.write().unwrap(),
+            functions: &functions,
+            inlining: vec![SymbolName::clone(&block.name)],
+        };
+        block.body = inliner.inline_statements(body);
+    }
+    Ok(())
+}
+
+struct Inliner<'a> {
+    global_symbol_table: &'a SymbolTable,
+    /// Symbol table of the function being inlined into
+    symbol_table: &'a mut SymbolTable,
+    functions: &'a HashMap<SymbolName, InlineFunction>,
+    /// Functions whose bodies are currently being expanded, which can't be expanded again inside themselves
+    inlining: Vec<SymbolName>,
+}
+
+impl<'a> Inliner<'a> {
+    fn inline_statements(&mut self, statements: Vec<Statement>) -> Vec<Statement> {
+        let mut result = Vec::new();
+        for statement in statements {
+            match statement {
+                Statement::Call(data) => match self.expand_call(&data) {
+                    Some((expansion, _)) => result.extend(expansion),
+                    None => result.push(Statement::Call(data)),
+                },
+                Statement::Assign(mut data) => {
+                    if let Some((expansion, Some(value))) = self.expand_expr(&data.right_value) {
+                        result.extend(expansion);
+                        data.right_value = value;
+                    }
+                    result.push(Statement::Assign(data));
+                }
+                Statement::Return(mut data) => {
+                    let expanded = data.value.as_ref().and_then(|value| self.expand_expr(value));
+                    if let Some((expansion, value)) = expanded {
+                        result.extend(expansion);
+                        data.value = value;
+                    }
+                    result.push(Statement::Return(data));
+                }
+                Statement::Conditional(mut data) => {
+                    data.when_true = self.inline_statements(data.when_true);
+                    data.when_false = self.inline_statements(data.when_false);
+                    result.push(Statement::Conditional(data));
+                }
+                Statement::Match(mut data) => {
+                    for case in &mut data.cases {
+                        case.body = self.inline_statements(mem::replace(&mut case.body, Vec::new()));
+                    }
+                    data.default = self.inline_statements(data.default);
+                    result.push(Statement::Match(data));
+                }
+                Statement::WhileLoop(mut data) => {
+                    data.body = self.inline_statements(data.body);
+                    result.push(Statement::WhileLoop(data));
+                }
+                other => result.push(other),
+            }
+        }
+        result
+    }
+
+    /// Expands the expression if it's a call to an inline function. The returned expression
+    /// reads the function's return value once the expanded statements have run.
+    fn expand_expr(&mut self, expr: &Expr) -> Option<(Vec<Statement>, Option<Expr>)> {
+        match *expr {
+            Expr::Call(ref data) => self.expand_call(data).map(|(expansion, result)| {
+                (
+                    expansion,
+                    result.map(|symbol| Expr::Symbol(SymbolData::new(data.tag, symbol, None))),
+                )
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the statements that take the place of the call, and the local holding the return value
+    fn expand_call(&mut self, call: &CallData) -> Option<(Vec<Statement>, Option<SymbolRef>)> {
+        if call.pointer.is_some() || self.inlining.contains(&call.function) {
+            return None;
+        }
+        let functions = self.functions;
+        let function = functions.get(&call.function)?;
+        let metadata = function.metadata.read().unwrap();
+        match self.symbol_table.function_by_name(&call.function) {
+            Some(ref called) if Arc::ptr_eq(called, &function.metadata) => {}
+            _ => return None,
+        }
+        if call.arguments.len() != metadata.parameters.len() {
+            // Leave it to the type checker to report
+            return None;
+        }
+
+        let callee_symbol_table = function.symbol_table.read().unwrap();
+        let mut locals = HashMap::new();
+        let mut statements = Vec::new();
+        for (parameter, argument) in metadata.parameters.iter().zip(call.arguments.iter()) {
+            let parameter_ref = callee_symbol_table.find_symbol(&parameter.name).unwrap();
+            let local = self.localize(&*callee_symbol_table, &mut locals, parameter_ref);
+            statements.push(Statement::Assign(AssignData::new(
+                call.tag,
+                None,
+                Expr::Symbol(SymbolData::new(call.tag, local, None)),
+                argument.clone(),
+            )));
+        }
+        let result = if metadata.return_type == BaseType::Void {
+            None
+        } else {
+            Some(self.symbol_table.create_temporary(&metadata.return_type))
+        };
+
+        let mut body = function.body.clone();
+        self.localize_statements(&*callee_symbol_table, &mut locals, &mut body);
+        statements.extend(replace_returns(body, result));
+
+        self.inlining.push(SymbolName::clone(&call.function));
+        let statements = self.inline_statements(statements);
+        self.inlining.pop();
+        Some((statements, result))
+    }
+
+    fn localize_statements(
+        &mut self,
+        callee: &SymbolTable,
+        locals: &mut HashMap<SymbolRef, SymbolRef>,
+        statements: &mut [Statement],
+    ) {
+        for statement in statements {
+            match *statement {
+                Statement::Assign(ref mut data) => {
+                    self.localize_expr(callee, locals, &mut data.left_value);
+                    self.localize_expr(callee, locals, &mut data.right_value);
+                }
+                Statement::CompoundAssign(ref mut data) => {
+                    self.localize_expr(callee, locals, &mut data.left_value);
+                    self.localize_expr(callee, locals, &mut data.right_value);
+                }
+                Statement::Call(ref mut data) => self.localize_call(callee, locals, data),
+                Statement::Intrinsic(ref mut data) => if let Some(ref mut value) = data.value {
+                    self.localize_expr(callee, locals, value);
+                },
+                Statement::Conditional(ref mut data) => {
+                    self.localize_expr(callee, locals, &mut data.condition);
+                    self.localize_statements(callee, locals, &mut data.when_true);
+                    self.localize_statements(callee, locals, &mut data.when_false);
+                }
+                Statement::Poke(ref mut data) => {
+                    self.localize_expr(callee, locals, &mut data.address);
+                    self.localize_expr(callee, locals, &mut data.value);
+                }
+                Statement::Match(ref mut data) => {
+                    self.localize_expr(callee, locals, &mut data.value);
+                    for case in &mut data.cases {
+                        self.localize_statements(callee, locals, &mut case.body);
+                    }
+                    self.localize_statements(callee, locals, &mut data.default);
+                }
+                Statement::Return(ref mut data) => if let Some(ref mut value) = data.value {
+                    self.localize_expr(callee, locals, value);
+                },
+                Statement::WhileLoop(ref mut data) => {
+                    self.localize_expr(callee, locals, &mut data.condition);
+                    self.localize_statements(callee, locals, &mut data.body);
+                }
+                Statement::Break(_) | Statement::Continue(_) | Statement::GoTo(_) | Statement::InlineAsm(_) => {}
+            }
+        }
+    }
+
+    fn localize_call(
+        &mut self,
+        callee: &SymbolTable,
+        locals: &mut HashMap<SymbolRef, SymbolRef>,
+        data: &mut CallData,
+    ) {
+        if let Some(ref mut pointer) = data.pointer {
+            self.localize_expr(callee, locals, pointer);
+        }
+        for argument in &mut data.arguments {
+            self.localize_expr(callee, locals, argument);
+        }
+    }
+
+    fn localize_expr(
+        &mut self,
+        callee: &SymbolTable,
+        locals: &mut HashMap<SymbolRef, SymbolRef>,
+        expr: &mut Expr,
+    ) {
+        match *expr {
+            Expr::Symbol(ref mut data) => data.symbol = self.localize(callee, locals, data.symbol),
+            Expr::ArrayIndex(ref mut data) => {
+                data.array = self.localize(callee, locals, data.array);
+                self.localize_expr(callee, locals, &mut data.index);
+            }
+            Expr::BinaryOp(ref mut data) => {
+                self.localize_expr(callee, locals, &mut data.left);
+                self.localize_expr(callee, locals, &mut data.right);
+            }
+            Expr::UnaryOp(ref mut data) => self.localize_expr(callee, locals, &mut data.value),
+            Expr::Call(ref mut data) => self.localize_call(callee, locals, data),
+            Expr::FieldAccess(ref mut data) => self.localize_expr(callee, locals, &mut data.value),
+            Expr::Cast(ref mut data) => self.localize_expr(callee, locals, &mut data.value),
+            Expr::AddressOf(ref mut data) => self.localize_expr(callee, locals, &mut data.value),
+            Expr::Dereference(ref mut data) => self.localize_expr(callee, locals, &mut data.value),
+            Expr::Peek(ref mut data) => self.localize_expr(callee, locals, &mut data.address),
+            Expr::Word(ref mut data) => {
+                self.localize_expr(callee, locals, &mut data.high);
+                self.localize_expr(callee, locals, &mut data.low);
+            }
+            Expr::Number(_) => {}
+        }
+    }
+
+    /// Returns the caller's copy of a symbol that's local to the inlined function, creating it if necessary.
+    /// Variables in the inlined function's frame get new places in the caller's frame.
+    fn localize(
+        &mut self,
+        callee: &SymbolTable,
+        locals: &mut HashMap<SymbolRef, SymbolRef>,
+        symbol: SymbolRef,
+    ) -> SymbolRef {
+        if let Some(local) = locals.get(&symbol) {
+            return *local;
+        }
+        let local = if self.global_symbol_table.variable(symbol).is_some()
+            || self.global_symbol_table.constant(symbol).is_some()
+        {
+            symbol
+        } else if let Some(variable) = callee.variable(symbol) {
+            match variable.location {
+                Location::FrameOffset(_) => self.symbol_table.create_temporary(&variable.base_type),
+                _ => {
+                    let name = self.unique_name(callee, symbol);
+                    self.symbol_table
+                        .insert_variable(name, Variable::new(variable.base_type, variable.location))
+                        .unwrap()
+                }
+            }
+        } else if let Some(constant) = callee.constant(symbol) {
+            self.symbol_table
+                .insert_unnamed_constant(&constant.base_type, constant.value)
+                .unwrap()
+        } else {
+            symbol
+        };
+        locals.insert(symbol, local);
+        local
+    }
+
+    fn unique_name(&self, callee: &SymbolTable, symbol: SymbolRef) -> SymbolName {
+        let handle = self.symbol_table.handle_gen().write().unwrap().new_handle();
+        Arc::new(format!("{}#{}", callee.get_symbol_name(symbol).unwrap(), handle))
+    }
+}
+
+/// Turns each return into an assignment to the inlined function's return value. The returns
+/// must already be the last statements to run, so that the expansion can simply fall through.
+fn replace_returns(statements: Vec<Statement>, result: Option<SymbolRef>) -> Vec<Statement> {
+    let mut replaced = Vec::new();
+    for statement in statements {
+        match statement {
+            Statement::Return(data) => if let (Some(value), Some(result)) = (data.value, result) {
+                replaced.push(Statement::Assign(AssignData::new(
+                    data.tag,
+                    None,
+                    Expr::Symbol(SymbolData::new(data.tag, result, None)),
+                    value,
+                )));
+            },
+            Statement::Conditional(mut data) => {
+                data.when_true = replace_returns(data.when_true, result);
+                data.when_false = replace_returns(data.when_false, result);
+                replaced.push(Statement::Conditional(data));
+            }
+            Statement::Match(mut data) => {
+                for case in &mut data.cases {
+                    case.body = replace_returns(mem::replace(&mut case.body, Vec::new()), result);
+                }
+                data.default = replace_returns(data.default, result);
+                replaced.push(Statement::Match(data));
+            }
+            other => replaced.push(other),
+        }
+    }
+    replaced
+}
+
+/// Rearranges the statements so that every return is the last statement to run. Statements that follow
+/// an `if` or `match` containing a return are moved into each of its branches that can fall through.
+/// Returns the tag of the offending return if one is inside of a loop, since it can't be moved.
+fn move_returns_to_tail(statements: Vec<Statement>) -> Result<Vec<Statement>, SrcTag> {
+    let mut result = Vec::new();
+    let mut remaining = statements.into_iter();
+    while let Some(statement) = remaining.next() {
+        match statement {
+            Statement::Return(data) => {
+                // Anything after the return is unreachable
+                result.push(Statement::Return(data));
+                break;
+            }
+            Statement::Conditional(mut data) => {
+                if contains_return(&data.when_true) || contains_return(&data.when_false) {
+                    let rest: Vec<Statement> = remaining.collect();
+                    if !ends_with_return(&data.when_true) {
+                        data.when_true.extend(rest.iter().cloned());
+                    }
+                    if !ends_with_return(&data.when_false) {
+                        data.when_false.extend(rest);
+                    }
+                    data.when_true = move_returns_to_tail(data.when_true)?;
+                    data.when_false = move_returns_to_tail(data.when_false)?;
+                    result.push(Statement::Conditional(data));
+                    break;
+                }
+                result.push(Statement::Conditional(data));
+            }
+            Statement::Match(mut data) => {
+                if data.cases.iter().any(|case| contains_return(&case.body)) || contains_return(&data.default) {
+                    let rest: Vec<Statement> = remaining.collect();
+                    for case in &mut data.cases {
+                        if !ends_with_return(&case.body) {
+                            case.body.extend(rest.iter().cloned());
+                        }
+                        case.body = move_returns_to_tail(mem::replace(&mut case.body, Vec::new()))?;
+                    }
+                    if !ends_with_return(&data.default) {
+                        data.default.extend(rest);
+                    }
+                    data.default = move_returns_to_tail(data.default)?;
+                    result.push(Statement::Match(data));
+                    break;
+                }
+                result.push(Statement::Match(data));
+            }
+            Statement::WhileLoop(data) => {
+                if let Some(tag) = find_return(&data.body) {
+                    return Err(tag);
+                }
+                result.push(Statement::WhileLoop(data));
+            }
+            other => result.push(other),
+        }
+    }
+    Ok(result)
+}
+
+fn contains_return(statements: &[Statement]) -> bool {
+    find_return(statements).is_some()
+}
+
+fn find_return(statements: &[Statement]) -> Option<SrcTag> {
+    for statement in statements {
+        let found = match *statement {
+            Statement::Return(ref data) => Some(data.tag),
+            Statement::Conditional(ref data) => {
+                find_return(&data.when_true).or_else(|| find_return(&data.when_false))
+            }
+            Statement::Match(ref data) => data.cases
+                .iter()
+                .filter_map(|case| find_return(&case.body))
+                .next()
+                .or_else(|| find_return(&data.default)),
+            Statement::WhileLoop(ref data) => find_return(&data.body),
+            _ => None,
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// True if every path through the statements ends in a return
+fn ends_with_return(statements: &[Statement]) -> bool {
+    match statements.last() {
+        Some(&Statement::Return(_)) => true,
+        Some(&Statement::Conditional(ref data)) => {
+            ends_with_return(&data.when_true) && ends_with_return(&data.when_false)
+        }
+        Some(&Statement::Match(ref data)) => {
+            data.cases.iter().all(|case| ends_with_return(&case.body)) && ends_with_return(&data.default)
+        }
+        _ => false,
+    }
+}
+
+fn is_small_leaf(statements: &[Statement]) -> bool {
+    count_statements(statements) <= SMALL_FUNCTION_STATEMENTS && !statements.iter().any(has_call_or_asm)
+}
+
+fn count_statements(statements: &[Statement]) -> usize {
+    statements
+        .iter()
+        .map(|statement| {
+            1 + match *statement {
+                Statement::Conditional(ref data) => {
+                    count_statements(&data.when_true) + count_statements(&data.when_false)
+                }
+                Statement::Match(ref data) => {
+                    data.cases
+                        .iter()
+                        .map(|case| count_statements(&case.body))
+                        .sum::<usize>() + count_statements(&data.default)
+                }
+                Statement::WhileLoop(ref data) => count_statements(&data.body),
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+/// Inline assembly is left alone since any labels in it would be duplicated
+fn has_call_or_asm(statement: &Statement) -> bool {
+    match *statement {
+        Statement::Assign(ref data) => has_call(&data.left_value) || has_call(&data.right_value),
+        Statement::CompoundAssign(ref data) => has_call(&data.left_value) || has_call(&data.right_value),
+        Statement::Call(_) | Statement::InlineAsm(_) => true,
+        Statement::Conditional(ref data) => {
+            has_call(&data.condition) || data.when_true.iter().any(has_call_or_asm)
+                || data.when_false.iter().any(has_call_or_asm)
+        }
+        Statement::Match(ref data) => {
+            has_call(&data.value) || data.cases.iter().any(|case| case.body.iter().any(has_call_or_asm))
+                || data.default.iter().any(has_call_or_asm)
+        }
+        Statement::Intrinsic(ref data) => data.value.as_ref().map_or(false, has_call),
+        Statement::Poke(ref data) => has_call(&data.address) || has_call(&data.value),
+        Statement::Return(ref data) => data.value.as_ref().map_or(false, has_call),
+        Statement::WhileLoop(ref data) => has_call(&data.condition) || data.body.iter().any(has_call_or_asm),
+        Statement::Break(_) | Statement::Continue(_) | Statement::GoTo(_) => false,
+    }
+}
+
+fn has_call(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Call(_) => true,
+        Expr::ArrayIndex(ref data) => has_call(&data.index),
+        Expr::BinaryOp(ref data) => has_call(&data.left) || has_call(&data.right),
+        Expr::UnaryOp(ref data) => has_call(&data.value),
+        Expr::FieldAccess(ref data) => has_call(&data.value),
+        Expr::Cast(ref data) => has_call(&data.value),
+        Expr::AddressOf(ref data) => has_call(&data.value),
+        Expr::Dereference(ref data) => has_call(&data.value),
+        Expr::Peek(ref data) => has_call(&data.address),
+        Expr::Word(ref data) => has_call(&data.high) || has_call(&data.low),
+        Expr::Number(_) | Expr::Symbol(_) => false,
+    }
+}
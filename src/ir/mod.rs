@@ -9,7 +9,11 @@
 
 mod block;
 mod generator;
+mod inline;
 mod type_checker;
+mod usage;
 
 pub use self::block::*;
 pub use self::generator::generate;
+pub use self::type_checker::TypeChecking;
+pub use self::usage::count_symbol_uses;
@@ -7,11 +7,14 @@
 // copied, modified, or distributed except according to those terms.
 //
 
+use std::mem;
+use std::sync::Arc;
 use error::{self, ErrorKind};
-use ir::block::{Block, CallData, Expr, Statement};
+use ir::block::{BinaryOpData, Block, CallData, Expr, Statement};
+use parse::ast::{BinaryOperator, UnaryOperator};
 use src_tag::SrcTagged;
 use symbol_table::{SymbolName, SymbolTable};
-use base_type::BaseType;
+use base_type::{BaseType, StructType};
 
 pub trait TypeChecking {
     fn base_type(&self) -> Option<&BaseType>;
@@ -28,8 +31,15 @@ impl TypeChecking for Expr {
             Number(ref data) => data.value_type.as_ref(),
             Symbol(ref data) => data.value_type.as_ref(),
             BinaryOp(ref data) => data.result_type.as_ref(),
+            UnaryOp(ref data) => data.result_type.as_ref(),
             Call(ref data) => data.base_type(),
             ArrayIndex(ref data) => data.array_type.as_ref().and_then(|at| at.underlying_type()),
+            FieldAccess(ref data) => data.value_type.as_ref(),
+            Cast(ref data) => Some(&data.cast_type),
+            AddressOf(ref data) => data.value_type.as_ref(),
+            Dereference(ref data) => data.value_type.as_ref(),
+            Peek(_) => Some(&BaseType::U8),
+            Word(_) => Some(&BaseType::U16),
         }
     }
 
@@ -42,6 +52,8 @@ impl TypeChecking for Expr {
                     data.value_type = Some(constant.base_type);
                 } else if let Some(variable) = symbol_table.variable(data.symbol) {
                     data.value_type = Some(variable.base_type);
+                } else if let Some(function) = symbol_table.function(data.symbol) {
+                    data.value_type = Some(function.read().unwrap().function_type());
                 } else {
                     unreachable!()
                 }
@@ -50,22 +62,64 @@ impl TypeChecking for Expr {
                 data.left.infer_types(symbol_table)?;
                 data.right.infer_types(symbol_table)?;
 
-                if !data.op.is_arithmetic() && data.left.base_type().is_none() && data.right.base_type().is_none() {
+                // Each side of a logical operator is its own condition, so neither side implies the other's type
+                if data.op.is_logical() {
                     data.left.imply_defaults();
                     data.right.imply_defaults();
+                    data.result_type = Some(BaseType::Bool);
+                    return Ok(());
+                }
+
+                // The result of a shift has the type of the value being shifted
+                if data.op.is_shift() {
+                    if data.right.base_type().is_none() {
+                        data.right.imply_type(&BaseType::U8);
+                    }
+                    data.result_type = data.left.base_type().cloned();
+                    return Ok(());
+                }
+
+                if !data.op.is_arithmetic() && data.left.base_type().is_none() && data.right.base_type().is_none() {
+                    // Both sides need the same type, so a negative number on either side makes them both signed
+                    let default_type = if is_negative_number(&*data.left) || is_negative_number(&*data.right) {
+                        BaseType::I8
+                    } else {
+                        BaseType::U8
+                    };
+                    data.left.imply_type(&default_type);
+                    data.right.imply_type(&default_type);
                 }
 
                 let left_type = data.left.base_type().cloned();
                 let right_type = data.right.base_type().cloned();
 
                 if left_type.is_none() && right_type.is_some() {
-                    data.left.imply_type(right_type.as_ref().unwrap());
+                    data.left.imply_type(&operand_type(data.op, right_type.as_ref().unwrap()));
                     data.result_type = right_type;
                 } else if left_type.is_some() && right_type.is_none() {
-                    data.right.imply_type(left_type.as_ref().unwrap());
+                    data.right.imply_type(&operand_type(data.op, left_type.as_ref().unwrap()));
                     data.result_type = left_type;
                 }
             }
+            UnaryOp(ref mut data) => {
+                data.value.infer_types(symbol_table)?;
+                if data.op == UnaryOperator::LogicalNot {
+                    data.value.imply_defaults();
+                    data.result_type = Some(BaseType::Bool);
+                } else if data.op.is_byte_select() {
+                    if data.value.base_type().is_none() {
+                        // Give literals both bytes so that there's a high byte to select
+                        let implied_type = match *data.value {
+                            Number(ref number) if number.value < 0 => BaseType::I16,
+                            _ => BaseType::U16,
+                        };
+                        data.value.imply_type(&implied_type);
+                    }
+                    data.result_type = Some(BaseType::U8);
+                } else {
+                    data.result_type = data.value.base_type().cloned();
+                }
+            }
             Call(ref mut data) => data.infer_types(symbol_table)?,
             ArrayIndex(ref mut data) => {
                 data.index.infer_types(symbol_table)?;
@@ -80,6 +134,50 @@ impl TypeChecking for Expr {
                     unreachable!()
                 }
             }
+            FieldAccess(ref mut data) => {
+                data.value.infer_types(symbol_table)?;
+                let field = data.value
+                    .base_type()
+                    .and_then(accessed_struct)
+                    .and_then(|struct_type| struct_type.field(&data.field).cloned());
+                if let Some(field) = field {
+                    data.offset = Some(field.offset);
+                    data.indirect = data.value.base_type().unwrap().is_pointer();
+                    data.value_type = Some(field.base_type);
+                }
+            }
+            Cast(ref mut data) => {
+                data.value.infer_types(symbol_table)?;
+                if data.value.base_type().is_none() {
+                    // Give literals both bytes so that casting to a smaller type truncates them
+                    let implied_type = match *data.value {
+                        Number(ref number) if number.value < 0 => BaseType::I16,
+                        _ => BaseType::U16,
+                    };
+                    data.value.imply_type(&implied_type);
+                }
+            }
+            AddressOf(ref mut data) => {
+                data.value.infer_types(symbol_table)?;
+                data.value_type = data.value.base_type().map(address_type);
+            }
+            Dereference(ref mut data) => {
+                data.value.infer_types(symbol_table)?;
+                data.value_type = match data.value.base_type() {
+                    Some(&BaseType::Pointer(ref inner)) => Some(BaseType::clone(inner)),
+                    _ => None,
+                };
+            }
+            Peek(ref mut data) => {
+                data.address.infer_types(symbol_table)?;
+                data.address.imply_type(&BaseType::U16);
+            }
+            Word(ref mut data) => {
+                data.high.infer_types(symbol_table)?;
+                data.low.infer_types(symbol_table)?;
+                data.high.imply_type(&BaseType::U8);
+                data.low.imply_type(&BaseType::U8);
+            }
         }
         Ok(())
     }
@@ -89,26 +187,45 @@ impl TypeChecking for Expr {
         match *self {
             Number(ref mut data) => {
                 if data.value_type.is_none() {
-                    data.value_type = Some(base_type.clone());
+                    // Numbers only become enum values through a cast, so they keep their default type instead
+                    data.value_type = Some(if !base_type.is_enum() {
+                        base_type.clone()
+                    } else if data.value < 0 {
+                        BaseType::I8
+                    } else {
+                        BaseType::U8
+                    });
                 }
             }
             BinaryOp(ref mut data) => {
                 if data.result_type.is_none() {
                     data.result_type = Some(base_type.clone());
-                    if data.op.is_arithmetic() {
+                    if data.op.is_shift() {
+                        data.left.imply_type(base_type);
+                    } else if base_type.is_pointer() && is_pointer_arithmetic(data.op) {
+                        data.left.imply_type(base_type);
+                        data.right.imply_type(&BaseType::U16);
+                    } else if data.op.is_arithmetic() {
                         data.left.imply_type(base_type);
                         data.right.imply_type(base_type);
                     }
                 }
             }
-            Symbol(_) | Call(_) | ArrayIndex(_) => {}
+            UnaryOp(ref mut data) => {
+                if data.result_type.is_none() {
+                    data.result_type = Some(base_type.clone());
+                    data.value.imply_type(base_type);
+                }
+            }
+            Symbol(_) | Call(_) | ArrayIndex(_) | FieldAccess(_) | Cast(_) | AddressOf(_) | Dereference(_) => {}
+            Peek(_) | Word(_) => {}
         }
     }
 
     fn imply_defaults(&mut self) {
         if let Expr::Number(ref mut data) = *self {
             if data.value_type.is_none() {
-                data.value_type = Some(BaseType::U8);
+                data.value_type = Some(if data.value < 0 { BaseType::I8 } else { BaseType::U8 });
             }
         }
     }
@@ -123,7 +240,33 @@ impl TypeChecking for Expr {
             BinaryOp(ref mut data) => {
                 let left_type = data.left.resolve_type(symbol_table)?;
                 let right_type = data.right.resolve_type(symbol_table)?;
+                if data.op.is_logical() {
+                    check_boolean(&*data.left, &left_type)?;
+                    check_boolean(&*data.right, &right_type)?;
+                    return Ok(BaseType::Bool);
+                }
+                if data.op.is_shift() {
+                    check_integer(&*data.left, &left_type)?;
+                    check_integer(&*data.right, &right_type)?;
+                    data.result_type = Some(left_type.clone());
+                    return Ok(left_type);
+                }
+                let not_arithmetic = |base_type: &BaseType| base_type.is_function() || base_type.is_enum();
+                if data.op.is_arithmetic() && (not_arithmetic(&left_type) || not_arithmetic(&right_type)) {
+                    return Err(ErrorKind::TypeExprError(
+                        data.tag,
+                        format!("Can't perform arithmetic between {} and {}", left_type, right_type),
+                    ).into());
+                }
+                if data.op.is_arithmetic() && (left_type.is_pointer() || right_type.is_pointer()) {
+                    return resolve_pointer_arithmetic(data, left_type, right_type);
+                }
                 match BaseType::choose_type(&left_type, &right_type) {
+                    // Comparisons keep the type they compare at so that the LLIR knows how wide the operands are
+                    Some(base_type) if !data.op.is_arithmetic() => {
+                        data.result_type = Some(base_type);
+                        Ok(BaseType::Bool)
+                    }
                     Some(base_type) => {
                         data.result_type = Some(base_type.clone());
                         Ok(base_type)
@@ -137,6 +280,27 @@ impl TypeChecking for Expr {
                     ).into()),
                 }
             }
+            UnaryOp(ref mut data) => {
+                let value_type = data.value.resolve_type(symbol_table)?;
+                if data.op == UnaryOperator::LogicalNot {
+                    check_boolean(&*data.value, &value_type)?;
+                    return Ok(BaseType::Bool);
+                }
+                if data.op.is_byte_select() {
+                    return match value_type {
+                        BaseType::Array(_, _) | BaseType::Function(_, _) | BaseType::Pointer(_) => Ok(BaseType::U8),
+                        _ if value_type.is_integer() => Ok(BaseType::U8),
+                        _ => Err(ErrorKind::TypeExprError(
+                            data.tag,
+                            format!("Can't select a byte of {}", value_type),
+                        ).into()),
+                    };
+                }
+                // Negating an unsigned value gives its two's complement
+                check_integer(&*data.value, &value_type)?;
+                data.result_type = Some(value_type.clone());
+                Ok(value_type)
+            }
             Call(ref mut data) => data.resolve_type(symbol_table),
             ArrayIndex(ref mut data) => {
                 if !data.array_type.as_ref().unwrap().can_index() {
@@ -158,7 +322,78 @@ impl TypeChecking for Expr {
                         .clone())
                 }
             }
+            FieldAccess(ref mut data) => {
+                let value_type = data.value.resolve_type(symbol_table)?;
+                match accessed_struct(&value_type) {
+                    Some(struct_type) => match struct_type.field(&data.field) {
+                        Some(field) => Ok(field.base_type.clone()),
+                        None => Err(ErrorKind::TypeExprError(
+                            data.tag,
+                            format!("{} has no field named {}", value_type, data.field),
+                        ).into()),
+                    },
+                    None => Err(ErrorKind::TypeExprError(
+                        data.tag,
+                        format!("Can't access field {} of {}", data.field, value_type),
+                    ).into()),
+                }
+            }
+            Cast(ref mut data) => {
+                let value_type = data.value.resolve_type(symbol_table)?;
+                if value_type.can_cast_into(&data.cast_type) {
+                    Ok(data.cast_type.clone())
+                } else {
+                    Err(ErrorKind::TypeExprError(
+                        data.tag,
+                        format!("Can't cast {} into {}", value_type, data.cast_type),
+                    ).into())
+                }
+            }
             Symbol(ref data) => Ok(data.value_type.as_ref().unwrap().clone()),
+            AddressOf(ref mut data) => {
+                let value_type = data.value.resolve_type(symbol_table)?;
+                let addressable = match *data.value {
+                    Symbol(ref symbol) => symbol_table.variable(symbol.symbol).is_some(),
+                    ArrayIndex(_) | FieldAccess(_) | Dereference(_) => true,
+                    _ => false,
+                };
+                if !addressable {
+                    return Err(ErrorKind::TypeExprError(
+                        data.tag,
+                        "Can only take the address of variables, array elements, fields and dereferenced pointers"
+                            .into(),
+                    ).into());
+                }
+                let pointer_type = address_type(&value_type);
+                data.value_type = Some(pointer_type.clone());
+                Ok(pointer_type)
+            }
+            Dereference(ref mut data) => match data.value.resolve_type(symbol_table)? {
+                BaseType::Pointer(inner) => {
+                    data.value_type = Some(BaseType::clone(&inner));
+                    Ok(*inner)
+                }
+                value_type => Err(ErrorKind::TypeExprError(
+                    data.tag,
+                    format!("Can only dereference pointers, but got {}", value_type),
+                ).into()),
+            },
+            Peek(ref mut data) => {
+                check_address(&mut data.address, symbol_table)?;
+                Ok(BaseType::U8)
+            }
+            Word(ref mut data) => {
+                for byte in &mut [&mut data.high, &mut data.low] {
+                    let byte_type = byte.resolve_type(symbol_table)?;
+                    if !byte_type.can_assign_into(&BaseType::U8) {
+                        return Err(ErrorKind::TypeExprError(
+                            byte.src_tag(),
+                            format!("Expected a u8 byte, but got {}", byte_type),
+                        ).into());
+                    }
+                }
+                Ok(BaseType::U16)
+            }
         }
     }
 }
@@ -169,25 +404,24 @@ impl TypeChecking for CallData {
     }
 
     fn infer_types(&mut self, symbol_table: &SymbolTable) -> error::Result<()> {
-        if let Some(function) = symbol_table.function_by_name(&self.function) {
-            self.return_type = Some(function.read().unwrap().return_type.clone());
-            let expected_arg_count = function.read().unwrap().parameters.len();
-            if self.arguments.len() != expected_arg_count {
-                return Err(ErrorKind::ExpectedNArgumentsGotM(
-                    self.tag,
-                    SymbolName::clone(&self.function),
-                    expected_arg_count,
-                    self.arguments.len(),
-                ).into());
-            }
-            for (index, argument) in self.arguments.iter_mut().enumerate() {
-                argument.infer_types(symbol_table)?;
-                if argument.base_type().is_none() {
-                    argument.imply_type(&function.read().unwrap().parameters[index].base_type);
-                }
+        if let Some(ref mut pointer) = self.pointer {
+            pointer.infer_types(symbol_table)?;
+        }
+        let (parameters, return_type) = self.signature(symbol_table)?;
+        self.return_type = Some(return_type);
+        if self.arguments.len() != parameters.len() {
+            return Err(ErrorKind::ExpectedNArgumentsGotM(
+                self.tag,
+                SymbolName::clone(&self.function),
+                parameters.len(),
+                self.arguments.len(),
+            ).into());
+        }
+        for (argument, parameter) in self.arguments.iter_mut().zip(parameters.iter()) {
+            argument.infer_types(symbol_table)?;
+            if argument.base_type().is_none() {
+                argument.imply_type(parameter);
             }
-        } else {
-            return Err(ErrorKind::SymbolNotFound(self.tag, SymbolName::clone(&self.function)).into());
         }
         Ok(())
     }
@@ -197,24 +431,54 @@ impl TypeChecking for CallData {
     fn imply_defaults(&mut self) {}
 
     fn resolve_type(&mut self, symbol_table: &SymbolTable) -> error::Result<BaseType> {
-        if let Some(function) = symbol_table.function_by_name(&self.function) {
-            for (index, argument) in self.arguments.iter_mut().enumerate() {
-                let argument_type = argument.resolve_type(symbol_table)?;
-                if !argument_type.can_assign_into(&function.read().unwrap().parameters[index].base_type) {
-                    return Err(ErrorKind::TypeExprError(
-                        argument.src_tag(),
-                        format!(
-                            "Argument {} expected {} but got a {}",
-                            index + 1,
-                            function.read().unwrap().parameters[index].base_type,
-                            argument_type
-                        ),
-                    ).into());
-                }
+        let (parameters, _) = self.signature(symbol_table)?;
+        for (index, argument) in self.arguments.iter_mut().enumerate() {
+            let argument_type = argument.resolve_type(symbol_table)?;
+            if !argument_type.can_assign_into(&parameters[index]) {
+                return Err(ErrorKind::TypeExprError(
+                    argument.src_tag(),
+                    format!(
+                        "Argument {} expected {} but got a {}",
+                        index + 1,
+                        parameters[index],
+                        argument_type
+                    ),
+                ).into());
             }
-            Ok(self.return_type.as_ref().unwrap().clone())
-        } else {
-            unreachable!()
+        }
+        Ok(self.return_type.as_ref().unwrap().clone())
+    }
+}
+
+impl CallData {
+    /// Returns the parameter types and return type of the called function. Calls through
+    /// a pointer take them from the pointer's type, so the pointer's type must be inferred first.
+    fn signature(&self, symbol_table: &SymbolTable) -> error::Result<(Vec<BaseType>, BaseType)> {
+        match self.pointer {
+            Some(ref pointer) => match pointer.base_type() {
+                Some(&BaseType::Function(ref parameters, ref return_type)) => {
+                    Ok((parameters.clone(), BaseType::clone(return_type)))
+                }
+                Some(other_type) => Err(ErrorKind::TypeExprError(
+                    self.tag,
+                    format!("Can only call functions, but got {}", other_type),
+                ).into()),
+                None => unreachable!(),
+            },
+            None => match symbol_table.function_by_name(&self.function) {
+                Some(function) => {
+                    let metadata = function.read().unwrap();
+                    if metadata.interrupt {
+                        return Err(ErrorKind::TypeExprError(
+                            self.tag,
+                            format!("Can't call interrupt handler {}", metadata.name),
+                        ).into());
+                    }
+                    let parameters = metadata.parameters.iter().map(|p| p.base_type.clone()).collect();
+                    Ok((parameters, metadata.return_type.clone()))
+                }
+                None => Err(ErrorKind::SymbolNotFound(self.tag, SymbolName::clone(&self.function)).into()),
+            },
         }
     }
 }
@@ -231,9 +495,25 @@ impl TypeChecking for Statement {
                 data.left_value.infer_types(symbol_table)?;
                 data.right_value.infer_types(symbol_table)?
             }
+            CompoundAssign(ref mut data) => {
+                data.left_value.infer_types(symbol_table)?;
+                data.right_value.infer_types(symbol_table)?
+            }
             Call(ref mut data) => data.infer_types(symbol_table)?,
+            Intrinsic(ref mut data) => {
+                if let Some(ref mut value) = data.value {
+                    value.infer_types(symbol_table)?;
+                }
+            }
+            Poke(ref mut data) => {
+                data.address.infer_types(symbol_table)?;
+                data.address.imply_type(&BaseType::U16);
+                data.value.infer_types(symbol_table)?;
+                data.value.imply_type(&BaseType::U8);
+            }
             Conditional(ref mut data) => {
                 data.condition.infer_types(symbol_table)?;
+                data.condition.imply_defaults();
                 for statement in &mut data.when_true {
                     statement.infer_types(symbol_table)?;
                 }
@@ -241,6 +521,18 @@ impl TypeChecking for Statement {
                     statement.infer_types(symbol_table)?;
                 }
             }
+            Match(ref mut data) => {
+                data.value.infer_types(symbol_table)?;
+                data.value.imply_type(&BaseType::U8);
+                for case in &mut data.cases {
+                    for statement in &mut case.body {
+                        statement.infer_types(symbol_table)?;
+                    }
+                }
+                for statement in &mut data.default {
+                    statement.infer_types(symbol_table)?;
+                }
+            }
             Return(ref mut data) => {
                 if let Some(ref mut value) = data.value {
                     value.infer_types(symbol_table)?;
@@ -248,11 +540,12 @@ impl TypeChecking for Statement {
             }
             WhileLoop(ref mut data) => {
                 data.condition.infer_types(symbol_table)?;
+                data.condition.imply_defaults();
                 for statement in &mut data.body {
                     statement.infer_types(symbol_table)?;
                 }
             }
-            Break | GoTo(_) | InlineAsm(_) => {}
+            Break(_) | Continue(_) | GoTo(_) | InlineAsm(_) => {}
         }
         Ok(())
     }
@@ -263,6 +556,8 @@ impl TypeChecking for Statement {
             Return(ref mut data) => {
                 if let Some(ref mut value) = *(&mut data.value) {
                     value.imply_type(base_type);
+                    // Returned values are copied out as the function's return type
+                    data.value_type = Some(base_type.clone());
                 }
             }
             Conditional(ref mut data) => {
@@ -273,6 +568,16 @@ impl TypeChecking for Statement {
                     statement.imply_type(base_type);
                 }
             }
+            Match(ref mut data) => {
+                for case in &mut data.cases {
+                    for statement in &mut case.body {
+                        statement.imply_type(base_type);
+                    }
+                }
+                for statement in &mut data.default {
+                    statement.imply_type(base_type);
+                }
+            }
             WhileLoop(ref mut data) => for statement in &mut data.body {
                 statement.imply_type(base_type);
             },
@@ -290,7 +595,7 @@ impl TypeChecking for Statement {
                 data.right_value.imply_type(&left_type);
 
                 let right_type = data.right_value.resolve_type(symbol_table)?;
-                if !right_type.can_assign_into(&left_type) {
+                if left_type.is_array() || !right_type.can_assign_into(&left_type) {
                     return Err(ErrorKind::TypeExprError(
                         data.tag,
                         format!("Can't assign {} into {}", right_type, left_type),
@@ -301,13 +606,48 @@ impl TypeChecking for Statement {
             Call(ref mut data) => {
                 data.resolve_type(symbol_table)?;
             }
+            CompoundAssign(ref mut data) => {
+                let left_type = data.left_value.resolve_type(symbol_table)?;
+
+                // Check the operation as if it were written out as `x = x + y`, and then keep its typed right operand
+                let mut operation = Expr::BinaryOp(BinaryOpData::new(
+                    data.tag,
+                    data.op,
+                    None,
+                    Box::new(data.left_value.clone()),
+                    Box::new(data.right_value.clone()),
+                ));
+                operation.infer_types(symbol_table)?;
+                operation.imply_type(&left_type);
+                let result_type = operation.resolve_type(symbol_table)?;
+                // Adding a pointer to an integer moves the pointer to the left, which the left value can't follow
+                let moved_pointer = result_type.is_pointer() && !left_type.is_pointer();
+                if left_type.is_array() || moved_pointer || !result_type.can_assign_into(&left_type) {
+                    return Err(ErrorKind::TypeExprError(
+                        data.tag,
+                        format!("Can't assign {} into {}", result_type, left_type),
+                    ).into());
+                }
+                data.right_value = match operation {
+                    Expr::BinaryOp(operation) => *operation.right,
+                    _ => unreachable!(),
+                };
+                data.value_type = Some(left_type);
+            }
+            Intrinsic(ref mut data) => {
+                if let Some(ref mut value) = data.value {
+                    let value_type = value.resolve_type(symbol_table)?;
+                    if !value_type.is_integer() {
+                        return Err(ErrorKind::TypeExprError(
+                            value.src_tag(),
+                            format!("Can only rotate integers, but got {}", value_type),
+                        ).into());
+                    }
+                }
+            }
             Conditional(ref mut data) => {
                 let condition_type = data.condition.resolve_type(symbol_table)?;
-                if !condition_type.can_cast_into(&BaseType::Bool) {
-                    return Err(
-                        ErrorKind::TypeExprError(data.tag, "Condition can't evaluate to a boolean".into()).into(),
-                    );
-                }
+                check_condition(&data.condition, &condition_type)?;
                 for statement in &mut data.when_true {
                     statement.resolve_type(symbol_table)?;
                 }
@@ -315,28 +655,171 @@ impl TypeChecking for Statement {
                     statement.resolve_type(symbol_table)?;
                 }
             }
+            Match(ref mut data) => {
+                let value_type = data.value.resolve_type(symbol_table)?;
+                let matchable = match value_type {
+                    BaseType::U8 => true,
+                    BaseType::Enum(ref enum_type) => enum_type.base_type == BaseType::U8,
+                    _ => false,
+                };
+                if !matchable {
+                    return Err(ErrorKind::TypeExprError(
+                        data.value.src_tag(),
+                        format!("Can only match on u8 values, but got {}", value_type),
+                    ).into());
+                }
+                for case in &mut data.cases {
+                    for case_value in &case.values {
+                        if !case_value.value_type.can_assign_into(&value_type) {
+                            return Err(ErrorKind::TypeExprError(
+                                case_value.tag,
+                                format!("Can't match {} against {}", case_value.value_type, value_type),
+                            ).into());
+                        }
+                    }
+                    for statement in &mut case.body {
+                        statement.resolve_type(symbol_table)?;
+                    }
+                }
+                for statement in &mut data.default {
+                    statement.resolve_type(symbol_table)?;
+                }
+            }
+            Poke(ref mut data) => {
+                check_address(&mut data.address, symbol_table)?;
+                let value_type = data.value.resolve_type(symbol_table)?;
+                if !value_type.can_assign_into(&BaseType::U8) {
+                    return Err(ErrorKind::TypeExprError(
+                        data.value.src_tag(),
+                        format!("Can only poke u8 bytes, but got {}", value_type),
+                    ).into());
+                }
+            }
             Return(ref mut data) => {
                 if let Some(ref mut value) = *(&mut data.value) {
-                    data.value_type = Some(value.resolve_type(symbol_table)?);
+                    let value_type = value.resolve_type(symbol_table)?;
+                    match data.value_type {
+                        Some(ref return_type) if !value_type.can_assign_into(return_type) => {
+                            return Err(ErrorKind::TypeExprError(
+                                data.tag,
+                                format!("Can't return {} from a function that returns {}", value_type, return_type),
+                            ).into());
+                        }
+                        Some(_) => {}
+                        None => data.value_type = Some(value_type),
+                    }
                 }
             }
             WhileLoop(ref mut data) => {
                 let condition_type = data.condition.resolve_type(symbol_table)?;
-                if !condition_type.can_cast_into(&BaseType::Bool) {
-                    return Err(
-                        ErrorKind::TypeExprError(data.tag, "Condition can't evaluate to a boolean".into()).into(),
-                    );
-                }
+                check_condition(&data.condition, &condition_type)?;
                 for statement in &mut data.body {
                     statement.resolve_type(symbol_table)?;
                 }
             }
-            Break | GoTo(_) | InlineAsm(_) => {}
+            Break(_) | Continue(_) | GoTo(_) | InlineAsm(_) => {}
         }
         Ok(BaseType::Void)
     }
 }
 
+/// Returns the struct whose fields can be accessed on a value of the given type.
+/// Fields of a struct are also accessible through a pointer to it.
+fn accessed_struct(base_type: &BaseType) -> Option<&Arc<StructType>> {
+    match *base_type {
+        BaseType::Pointer(ref inner) => inner.struct_type(),
+        _ => base_type.struct_type(),
+    }
+}
+
+/// Checks the address given to `peek` or `poke`, which is either a u16 or a pointer
+fn check_address(address: &mut Expr, symbol_table: &SymbolTable) -> error::Result<()> {
+    let address_type = address.resolve_type(symbol_table)?;
+    if address_type != BaseType::U16 && !address_type.is_pointer() {
+        return Err(ErrorKind::TypeExprError(
+            address.src_tag(),
+            format!("Can't use {} as an address", address_type),
+        ).into());
+    }
+    Ok(())
+}
+
+/// Returns the type of a pointer to a value of the given type. Arrays give a pointer to their first element.
+fn address_type(base_type: &BaseType) -> BaseType {
+    match *base_type {
+        BaseType::Array(ref inner, _) => BaseType::Pointer(inner.clone()),
+        _ => BaseType::Pointer(Box::new(base_type.clone())),
+    }
+}
+
+/// Returns the type implied for an untyped operand from the type of the other operand.
+/// Arithmetic on a pointer is done with plain integers.
+fn operand_type(op: BinaryOperator, other_type: &BaseType) -> BaseType {
+    if other_type.is_pointer() && op.is_arithmetic() {
+        BaseType::U16
+    } else {
+        other_type.clone()
+    }
+}
+
+fn is_pointer_arithmetic(op: BinaryOperator) -> bool {
+    op == BinaryOperator::Add || op == BinaryOperator::Sub
+}
+
+/// Checks arithmetic involving a pointer. Only an integer can be added to or subtracted from a pointer,
+/// and the result keeps the pointer type. The pointer always ends up on the left side.
+fn resolve_pointer_arithmetic(
+    data: &mut BinaryOpData,
+    left_type: BaseType,
+    right_type: BaseType,
+) -> error::Result<BaseType> {
+    let pointer_type = if left_type.is_pointer() && right_type.is_integer() && is_pointer_arithmetic(data.op) {
+        left_type
+    } else if left_type.is_integer() && right_type.is_pointer() && data.op == BinaryOperator::Add {
+        mem::swap(&mut data.left, &mut data.right);
+        right_type
+    } else {
+        return Err(ErrorKind::TypeExprError(
+            data.tag,
+            format!("Can't perform arithmetic between {} and {}", left_type, right_type),
+        ).into());
+    };
+    data.result_type = Some(pointer_type.clone());
+    Ok(pointer_type)
+}
+
+fn is_negative_number(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Number(ref data) => data.value < 0,
+        _ => false,
+    }
+}
+
+/// Conditions of `if` and `while` must be bools. Other values need an explicit comparison or cast.
+fn check_condition(expr: &Expr, base_type: &BaseType) -> error::Result<()> {
+    if *base_type == BaseType::Bool {
+        Ok(())
+    } else {
+        Err(ErrorKind::TypeExprError(expr.src_tag(), format!("Condition must be a bool, but got {}", base_type)).into())
+    }
+}
+
+fn check_boolean(expr: &Expr, base_type: &BaseType) -> error::Result<()> {
+    if base_type.can_cast_into(&BaseType::Bool) {
+        Ok(())
+    } else {
+        Err(ErrorKind::TypeExprError(expr.src_tag(), format!("Can't use {} as a boolean", base_type)).into())
+    }
+}
+
+fn check_integer(expr: &Expr, base_type: &BaseType) -> error::Result<()> {
+    if base_type.is_integer() {
+        Ok(())
+    } else {
+        Err(ErrorKind::TypeExprError(expr.src_tag(), format!("Expected an integer, but got {}", base_type)).into())
+    }
+}
+
 pub fn resolve_types(blocks: &mut Vec<Block>) -> error::Result<()> {
     for block in blocks {
         let symbol_table = &*block.symbol_table.read().unwrap();
@@ -346,7 +829,6 @@ pub fn resolve_types(blocks: &mut Vec<Block>) -> error::Result<()> {
             statement.imply_type(&return_type);
             statement.resolve_type(symbol_table)?;
         }
-        // TODO: Iterate over all return statements (recursively) and verify return type
     }
     Ok(())
 }
@@ -0,0 +1,103 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use std::collections::HashMap;
+use ir::{Block, CallData, Expr, Statement};
+use symbol_table::SymbolRef;
+
+/// Counts how many times each symbol is referred to in the program
+pub fn count_symbol_uses(blocks: &[Block]) -> HashMap<SymbolRef, usize> {
+    let mut uses = HashMap::new();
+    for block in blocks {
+        count_statements(&mut uses, &block.body);
+    }
+    uses
+}
+
+fn count_statements(uses: &mut HashMap<SymbolRef, usize>, statements: &[Statement]) {
+    for statement in statements {
+        match *statement {
+            Statement::Assign(ref data) => {
+                count_expr(uses, &data.left_value);
+                count_expr(uses, &data.right_value);
+            }
+            Statement::CompoundAssign(ref data) => {
+                count_expr(uses, &data.left_value);
+                count_expr(uses, &data.right_value);
+            }
+            Statement::Call(ref data) => count_call(uses, data),
+            Statement::Intrinsic(ref data) => if let Some(ref value) = data.value {
+                count_expr(uses, value);
+            },
+            Statement::Conditional(ref data) => {
+                count_expr(uses, &data.condition);
+                count_statements(uses, &data.when_true);
+                count_statements(uses, &data.when_false);
+            }
+            Statement::Poke(ref data) => {
+                count_expr(uses, &data.address);
+                count_expr(uses, &data.value);
+            }
+            Statement::Match(ref data) => {
+                count_expr(uses, &data.value);
+                for case in &data.cases {
+                    count_statements(uses, &case.body);
+                }
+                count_statements(uses, &data.default);
+            }
+            Statement::Return(ref data) => if let Some(ref value) = data.value {
+                count_expr(uses, value);
+            },
+            Statement::WhileLoop(ref data) => {
+                count_expr(uses, &data.condition);
+                count_statements(uses, &data.body);
+            }
+            Statement::Break(_) | Statement::Continue(_) | Statement::GoTo(_) | Statement::InlineAsm(_) => {}
+        }
+    }
+}
+
+fn count_exprs(uses: &mut HashMap<SymbolRef, usize>, exprs: &[Expr]) {
+    for expr in exprs {
+        count_expr(uses, expr);
+    }
+}
+
+fn count_call(uses: &mut HashMap<SymbolRef, usize>, data: &CallData) {
+    if let Some(ref pointer) = data.pointer {
+        count_expr(uses, pointer);
+    }
+    count_exprs(uses, &data.arguments);
+}
+
+fn count_expr(uses: &mut HashMap<SymbolRef, usize>, expr: &Expr) {
+    match *expr {
+        Expr::Symbol(ref data) => *uses.entry(data.symbol).or_insert(0) += 1,
+        Expr::ArrayIndex(ref data) => {
+            *uses.entry(data.array).or_insert(0) += 1;
+            count_expr(uses, &data.index);
+        }
+        Expr::BinaryOp(ref data) => {
+            count_expr(uses, &data.left);
+            count_expr(uses, &data.right);
+        }
+        Expr::UnaryOp(ref data) => count_expr(uses, &data.value),
+        Expr::Call(ref data) => count_call(uses, data),
+        Expr::FieldAccess(ref data) => count_expr(uses, &data.value),
+        Expr::Cast(ref data) => count_expr(uses, &data.value),
+        Expr::AddressOf(ref data) => count_expr(uses, &data.value),
+        Expr::Dereference(ref data) => count_expr(uses, &data.value),
+        Expr::Peek(ref data) => count_expr(uses, &data.address),
+        Expr::Word(ref data) => {
+            count_expr(uses, &data.high);
+            count_expr(uses, &data.low);
+        }
+        Expr::Number(_) => {}
+    }
+}
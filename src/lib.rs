@@ -32,5 +32,6 @@ pub mod llir;
 pub mod parse;
 pub mod src_tag;
 pub mod src_unit;
+pub mod zero_page;
 
 pub use compiler::{Compiler, CompilerOptions, CompilerOptionsBuilder, CompilerOutput};
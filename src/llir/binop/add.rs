@@ -9,8 +9,10 @@
 
 use base_type::BaseType;
 use error;
-use llir::{BinaryOpData, CarryMode, Statement, Value};
-use super::{BinopGenerator, TypeCoercer};
+use llir::builder::RunBuilder;
+use llir::{BinaryOpData, CarryMode, Location, Statement, Value};
+use src_tag::SrcTag;
+use super::{immediate_u8, location_bytes, value_bytes, BinopGenerator, TypeCoercer};
 
 #[derive(new)]
 pub struct AddGenerator<'a> {
@@ -28,15 +30,16 @@ impl<'a> AddGenerator<'a> {
             type_coercer.coerce_values_to_same_types(self.binop.left_value, self.binop.right_value)?
         };
 
-        if actual_left.value_type() == BaseType::U16 {
-            if *self.binop.dest_type != BaseType::U16 {
-                panic!("type checking didn't catch coersion of U16 to U8");
+        let value_type = actual_left.value_type();
+        if value_type == BaseType::U16 || value_type == BaseType::I16 {
+            if *self.binop.dest_type != value_type {
+                panic!("type checking didn't catch coersion of {} to {}", value_type, self.binop.dest_type);
             }
 
             self.generate_16x16_into_16(actual_left.as_ref(), actual_right.as_ref(), subtract)
-        } else if actual_left.value_type() == BaseType::U8 {
-            if *self.binop.dest_type != BaseType::U8 {
-                panic!("type checking didn't catch coersion of U8 to U16");
+        } else if value_type == BaseType::U8 || value_type == BaseType::I8 {
+            if *self.binop.dest_type != value_type {
+                panic!("type checking didn't catch coersion of {} to {}", value_type, self.binop.dest_type);
             }
 
             self.generate_8x8_into_8(actual_left.as_ref(), actual_right.as_ref(), subtract)
@@ -103,3 +106,25 @@ impl<'a> AddGenerator<'a> {
         Ok(())
     }
 }
+
+/// Negates a value by subtracting it from zero
+pub fn generate_negate(run_builder: &mut RunBuilder, src_tag: SrcTag, dest: &Location, value: &Value) {
+    let byte_count = value.value_type().size().unwrap();
+    let destination = location_bytes(dest, byte_count);
+    let values = value_bytes(value, byte_count);
+    for index in 0..byte_count {
+        run_builder
+            .current_block()
+            .add_statement(Statement::Subtract(BinaryOpData::new(
+                src_tag,
+                destination[index].clone(),
+                immediate_u8(0),
+                values[index].clone(),
+                if index == 0 {
+                    CarryMode::SetCarry
+                } else {
+                    CarryMode::DontCare
+                },
+            )));
+    }
+}
@@ -0,0 +1,101 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use error;
+use llir::builder::RunBuilder;
+use llir::{BinaryOpData, CarryMode, ImmediateValue, Location, Statement, Value};
+use parse::ast::BinaryOperator;
+use src_tag::SrcTag;
+use super::{immediate_number, immediate_u8, location_bytes, value_bytes, BinopGenerator, TypeCoercer};
+
+#[derive(new)]
+pub struct BitwiseGenerator<'a> {
+    binop: BinopGenerator<'a>,
+}
+
+impl<'a> BitwiseGenerator<'a> {
+    pub fn generate(mut self, op: BinaryOperator) -> error::Result<()> {
+        let (actual_left, actual_right) = {
+            let mut type_coercer = TypeCoercer::new(
+                self.binop.run_builder,
+                self.binop.frame_ref,
+                self.binop.src_tag,
+            );
+            type_coercer.coerce_values_to_same_types(self.binop.left_value, self.binop.right_value)?
+        };
+
+        let value_type = actual_left.value_type();
+        if !value_type.is_integer() {
+            panic!("something went wrong with type checking");
+        }
+        if *self.binop.dest_type != value_type {
+            panic!("type checking didn't catch coersion of {} to {}", value_type, self.binop.dest_type);
+        }
+        let byte_count = value_type.size().unwrap();
+        let destination = location_bytes(self.binop.dest, byte_count);
+        let block_ref = self.binop.run_builder.current_block().block_ref;
+
+        if let (Some(left), Some(right)) = (immediate_number(&actual_left), immediate_number(&actual_right)) {
+            let result = match op {
+                BinaryOperator::BitAnd => left & right,
+                BinaryOperator::BitOr => left | right,
+                BinaryOperator::BitXor => left ^ right,
+                _ => unreachable!(),
+            };
+            let values = value_bytes(
+                &Value::Immediate(value_type, ImmediateValue::Number(result)),
+                byte_count,
+            );
+            self.binop
+                .generate_byte_copies(block_ref, &destination, &values);
+            return Ok(());
+        }
+
+        // Bitwise operations don't carry between bytes, so each byte can be done independently
+        let left_bytes = value_bytes(&actual_left, byte_count);
+        let right_bytes = value_bytes(&actual_right, byte_count);
+        for index in 0..byte_count {
+            let data = BinaryOpData::new(
+                self.binop.src_tag,
+                destination[index].clone(),
+                left_bytes[index].clone(),
+                right_bytes[index].clone(),
+                CarryMode::DontCare,
+            );
+            self.binop
+                .run_builder
+                .block(block_ref)
+                .add_statement(match op {
+                    BinaryOperator::BitAnd => Statement::And(data),
+                    BinaryOperator::BitOr => Statement::Or(data),
+                    BinaryOperator::BitXor => Statement::ExclusiveOr(data),
+                    _ => unreachable!(),
+                });
+        }
+        Ok(())
+    }
+}
+
+/// Flips every bit of `value` into `dest` by exclusive or-ing each byte with `$FF`
+pub fn generate_bit_not(run_builder: &mut RunBuilder, src_tag: SrcTag, dest: &Location, value: &Value) {
+    let byte_count = value.value_type().size().unwrap();
+    let destination = location_bytes(dest, byte_count);
+    let values = value_bytes(value, byte_count);
+    for index in 0..byte_count {
+        run_builder
+            .current_block()
+            .add_statement(Statement::ExclusiveOr(BinaryOpData::new(
+                src_tag,
+                destination[index].clone(),
+                values[index].clone(),
+                immediate_u8(0xFF),
+                CarryMode::DontCare,
+            )));
+    }
+}
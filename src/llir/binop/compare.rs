@@ -9,9 +9,12 @@
 
 use base_type::BaseType;
 use error;
+use llir::builder::RunBuilder;
 use llir::{BranchFlag, CompareBranchData, CopyData, GoToData, ImmediateValue, Statement, Value};
 use parse::ast::BinaryOperator;
-use super::{BinopGenerator, TypeCoercer};
+use src_tag::SrcTag;
+use symbol_table::SymbolRef;
+use super::{value_bytes, BinopGenerator, TypeCoercer};
 
 #[derive(new)]
 pub struct CompareGenerator<'a> {
@@ -19,173 +22,148 @@ pub struct CompareGenerator<'a> {
 }
 
 impl<'a> CompareGenerator<'a> {
-    pub fn generate(mut self, op: BinaryOperator) -> error::Result<()> {
-        let (actual_left, actual_right) = {
-            let mut type_coercer = TypeCoercer::new(
-                self.binop.run_builder,
-                self.binop.frame_ref,
-                self.binop.src_tag,
-            );
-            type_coercer.coerce_values_to_same_types(self.binop.left_value, self.binop.right_value)?
-        };
-
-        if actual_left.value_type() == BaseType::U16 {
-            if *self.binop.dest_type != BaseType::U16 {
-                panic!("type checking didn't catch coersion of U16 to U8");
-            }
-
-            self.generate_op(actual_left.as_ref(), actual_right.as_ref(), false, op)
-        } else if actual_left.value_type() == BaseType::U8 {
-            if *self.binop.dest_type != BaseType::U8 {
-                panic!("type checking didn't catch coersion of U8 to U16");
-            }
-
-            self.generate_op(actual_left.as_ref(), actual_right.as_ref(), true, op)
-        } else {
-            panic!("something went wrong with type checking");
-        }
-    }
-
-    fn generate_op(&mut self, left: &Value, right: &Value, single_byte: bool, op: BinaryOperator) -> error::Result<()> {
-        use parse::ast::BinaryOperator::*;
-        match op {
-            LessThan => self.generate_cmp(left, right, single_byte, BranchFlag::Carry, false),
-            GreaterThan => self.generate_cmp(right, left, single_byte, BranchFlag::Carry, false),
-            LessThanEqual => self.generate_cmp(right, left, single_byte, BranchFlag::Carry, true),
-            GreaterThanEqual => self.generate_cmp(left, right, single_byte, BranchFlag::Carry, true),
-            Equal => self.generate_cmp(left, right, single_byte, BranchFlag::Zero, true),
-            NotEqual => self.generate_cmp(left, right, single_byte, BranchFlag::Zero, false),
-            _ => unreachable!(),
-        }
-    }
-
-    fn generate_cmp(
-        &mut self,
-        left: &Value,
-        right: &Value,
-        single_byte: bool,
-        flag: BranchFlag,
-        set_value: bool,
-    ) -> error::Result<()> {
-        if single_byte {
-            self.generate_8bit_cmp(left, right, flag, set_value)
-        } else {
-            self.generate_16bit_cmp(left, right, flag, set_value)
-        }
-    }
-
-    fn generate_8bit_cmp(
-        &mut self,
-        left: &Value,
-        right: &Value,
-        flag: BranchFlag,
-        set_value: bool,
-    ) -> error::Result<()> {
-        let compare_block_ref = self.binop.run_builder.new_block().block_ref;
-        let clear_block_ref = self.binop.run_builder.new_block().block_ref;
-        let set_block_ref = self.binop.run_builder.new_block().block_ref;
-        let set_block_symbol = self.binop.run_builder.block(set_block_ref).symbol();
-        let after_block_symbol = self.binop.run_builder.new_block().symbol();
-
-        self.binop
-            .run_builder
-            .block(compare_block_ref)
-            .add_statement(Statement::CompareBranch(CompareBranchData::new(
-                self.binop.src_tag,
-                left.clone(),
-                right.clone(),
-                flag,
-                Some(set_block_symbol),
-                None,
-            )));
-
-        self.binop
-            .run_builder
-            .block(clear_block_ref)
+    pub fn generate(self, op: BinaryOperator) -> error::Result<()> {
+        let src_tag = self.binop.src_tag;
+        let run_builder = self.binop.run_builder;
+        let false_block_symbol = run_builder.reserve_block();
+        let after_block_symbol = run_builder.reserve_block();
+
+        generate_compare_branch(
+            run_builder,
+            self.binop.frame_ref,
+            src_tag,
+            op,
+            self.binop.left_value,
+            self.binop.right_value,
+            false_block_symbol,
+        )?;
+
+        run_builder
+            .current_block()
             .add_statement(Statement::Copy(CopyData::new(
-                self.binop.src_tag,
+                src_tag,
                 self.binop.dest.clone(),
-                imm_bool(!set_value),
+                imm_bool(true),
             )))
-            .add_statement(Statement::GoTo(GoToData::new(
-                self.binop.src_tag,
-                after_block_symbol,
-            )));
+            .add_statement(Statement::GoTo(GoToData::new(src_tag, after_block_symbol)));
 
-        self.binop
-            .run_builder
-            .block(set_block_ref)
+        run_builder
+            .new_reserved_block(false_block_symbol)
             .add_statement(Statement::Copy(CopyData::new(
-                self.binop.src_tag,
+                src_tag,
                 self.binop.dest.clone(),
-                imm_bool(set_value),
+                imm_bool(false),
             )));
 
+        run_builder.new_reserved_block(after_block_symbol);
         Ok(())
     }
+}
 
-    fn generate_16bit_cmp(
-        &mut self,
-        left: &Value,
-        right: &Value,
-        flag: BranchFlag,
-        set_value: bool,
-    ) -> error::Result<()> {
-        let first_compare_block_ref = self.binop.run_builder.new_block().block_ref;
-        let second_compare_block_ref = self.binop.run_builder.new_block().block_ref;
-        let set_block_ref = self.binop.run_builder.new_block().block_ref;
-        let clear_block_ref = self.binop.run_builder.new_block().block_ref;
-        let clear_block_symbol = self.binop.run_builder.block(clear_block_ref).symbol();
-        let after_block_symbol = self.binop.run_builder.new_block().symbol();
-
-        self.binop
-            .run_builder
-            .block(first_compare_block_ref)
-            .add_statement(Statement::CompareBranch(CompareBranchData::new(
-                self.binop.src_tag,
-                Value::high_byte(left),
-                Value::high_byte(right),
-                flag,
+/// Generates a chain of compare and branch statements that jumps to `false_symbol` when the
+/// comparison doesn't hold. Otherwise, execution falls through into a new current block.
+///
+/// Multi-byte values are compared starting with the most significant byte, and the next byte down
+/// is only looked at when the bytes so far are equal. For signed values, only the most significant
+/// byte is compared as signed, since the lower bytes don't hold a sign.
+pub fn generate_compare_branch(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    src_tag: SrcTag,
+    op: BinaryOperator,
+    left_value: &Value,
+    right_value: &Value,
+    false_symbol: SymbolRef,
+) -> error::Result<()> {
+    use parse::ast::BinaryOperator::*;
+
+    let (actual_left, actual_right) = {
+        let mut type_coercer = TypeCoercer::new(run_builder, frame_ref, src_tag);
+        type_coercer.coerce_values_to_same_types(left_value, right_value)?
+    };
+    let (left, right) = (actual_left.as_ref(), actual_right.as_ref());
+
+    // Each comparison holds when the given flag ends up in the given state after comparing
+    let (left, right, flag, holds_when_set) = match op {
+        LessThan => (left, right, BranchFlag::Carry, false),
+        GreaterThan => (right, left, BranchFlag::Carry, false),
+        LessThanEqual => (right, left, BranchFlag::Carry, true),
+        GreaterThanEqual => (left, right, BranchFlag::Carry, true),
+        Equal => (left, right, BranchFlag::Zero, true),
+        NotEqual => (left, right, BranchFlag::Zero, false),
+        _ => unreachable!(),
+    };
+
+    let byte_count = left.value_type().size().unwrap();
+    let signed = left.value_type().is_signed();
+    let left_bytes = value_bytes(left, byte_count);
+    let right_bytes = value_bytes(right, byte_count);
+    let true_symbol = run_builder.reserve_block();
+
+    // Returns the branch that is taken when the byte at the given index of left is less than right
+    let branch_when_less = |index: usize, target: SymbolRef| {
+        if signed && index == byte_count - 1 {
+            (index, BranchFlag::Negative, Some(target), None)
+        } else {
+            (index, BranchFlag::Carry, None, Some(target))
+        }
+    };
+    // Returns the branch that is taken when the byte at the given index of left is at least right
+    let branch_when_not_less = |index: usize, target: SymbolRef| {
+        if signed && index == byte_count - 1 {
+            (index, BranchFlag::Negative, None, Some(target))
+        } else {
+            (index, BranchFlag::Carry, Some(target), None)
+        }
+    };
+
+    let mut branches = Vec::new();
+    for index in (1..byte_count).rev() {
+        match flag {
+            // Any unequal byte decides the comparison
+            BranchFlag::Zero => branches.push((
+                index,
+                BranchFlag::Zero,
                 None,
-                Some(clear_block_symbol),
-            )));
-
-        self.binop
-            .run_builder
-            .block(second_compare_block_ref)
+                Some(if holds_when_set { false_symbol } else { true_symbol }),
+            )),
+            // A lesser byte decides the comparison, and so does a greater one
+            BranchFlag::Carry => {
+                branches.push(branch_when_less(
+                    index,
+                    if holds_when_set { false_symbol } else { true_symbol },
+                ));
+                branches.push((
+                    index,
+                    BranchFlag::Zero,
+                    None,
+                    Some(if holds_when_set { true_symbol } else { false_symbol }),
+                ));
+            }
+            BranchFlag::Negative => unreachable!(),
+        }
+    }
+    branches.push(match flag {
+        BranchFlag::Zero if holds_when_set => (0, flag, None, Some(false_symbol)),
+        BranchFlag::Zero => (0, flag, Some(false_symbol), None),
+        _ if holds_when_set => branch_when_less(0, false_symbol),
+        _ => branch_when_not_less(0, false_symbol),
+    });
+
+    for (index, flag, branch_set, branch_clear) in branches {
+        run_builder
+            .new_block()
             .add_statement(Statement::CompareBranch(CompareBranchData::new(
-                self.binop.src_tag,
-                Value::low_byte(left),
-                Value::low_byte(right),
+                src_tag,
+                left_bytes[index].clone(),
+                right_bytes[index].clone(),
                 flag,
-                None,
-                Some(clear_block_symbol),
-            )));
-
-        self.binop
-            .run_builder
-            .block(set_block_ref)
-            .add_statement(Statement::Copy(CopyData::new(
-                self.binop.src_tag,
-                self.binop.dest.clone(),
-                imm_bool(set_value),
-            )))
-            .add_statement(Statement::GoTo(GoToData::new(
-                self.binop.src_tag,
-                after_block_symbol,
+                branch_set,
+                branch_clear,
             )));
-
-        self.binop
-            .run_builder
-            .block(clear_block_ref)
-            .add_statement(Statement::Copy(CopyData::new(
-                self.binop.src_tag,
-                self.binop.dest.clone(),
-                imm_bool(!set_value),
-            )));
-
-        Ok(())
     }
+    run_builder.new_reserved_block(true_symbol);
+    Ok(())
 }
 
 fn imm_bool(value: bool) -> Value {
@@ -0,0 +1,260 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use base_type::BaseType;
+use error;
+use llir::{BinaryOpData, BranchFlag, CarryMode, CompareBranchData, GoToData, ImmediateValue, Location, MemoryData,
+           Statement, Value};
+use super::add::generate_negate;
+use super::{immediate_number, immediate_u8, location_bytes, memory_bytes, value_bytes, BinopGenerator, TypeCoercer};
+
+#[derive(new)]
+pub struct DivideGenerator<'a> {
+    binop: BinopGenerator<'a>,
+}
+
+impl<'a> DivideGenerator<'a> {
+    pub fn generate(mut self) -> error::Result<()> {
+        let (actual_left, actual_right) = {
+            let mut type_coercer = TypeCoercer::new(
+                self.binop.run_builder,
+                self.binop.frame_ref,
+                self.binop.src_tag,
+            );
+            type_coercer.coerce_values_to_same_types(self.binop.left_value, self.binop.right_value)?
+        };
+
+        let value_type = actual_left.value_type();
+        if !value_type.is_integer() {
+            panic!("something went wrong with type checking");
+        }
+        if *self.binop.dest_type != value_type {
+            panic!("type checking didn't catch coersion of {} to {}", value_type, self.binop.dest_type);
+        }
+        let byte_count = value_type.size().unwrap();
+
+        // Division by a constant zero is left to the runtime routine so that it behaves consistently
+        if let (Some(left), Some(right)) = (immediate_number(&actual_left), immediate_number(&actual_right)) {
+            if right != 0 {
+                let destination = location_bytes(self.binop.dest, byte_count);
+                let block_ref = self.binop.run_builder.current_block().block_ref;
+                let values = value_bytes(
+                    &Value::Immediate(value_type, ImmediateValue::Number(left / right)),
+                    byte_count,
+                );
+                self.binop
+                    .generate_byte_copies(block_ref, &destination, &values);
+                return Ok(());
+            }
+        }
+
+        if value_type.is_signed() {
+            self.generate_signed(actual_left.as_ref(), actual_right.as_ref(), byte_count)
+        } else {
+            self.generate_shift_and_subtract(actual_left.as_ref(), actual_right.as_ref(), byte_count)
+        }
+    }
+
+    /// Signed division divides the absolute values, and then negates the quotient
+    /// when the dividend and divisor have different signs.
+    fn generate_signed(&mut self, dividend: &Value, divisor: &Value, byte_count: usize) -> error::Result<()> {
+        let src_tag = self.binop.src_tag;
+        let value_type = dividend.value_type();
+        let sign = self.binop.create_temporary(&BaseType::U8);
+        let dividend_abs = self.binop.create_temporary(&value_type);
+        let divisor_abs = self.binop.create_temporary(&value_type);
+
+        self.binop
+            .run_builder
+            .current_block()
+            .add_statement(Statement::ExclusiveOr(BinaryOpData::new(
+                src_tag,
+                sign.clone(),
+                value_bytes(dividend, byte_count).remove(byte_count - 1),
+                value_bytes(divisor, byte_count).remove(byte_count - 1),
+                CarryMode::DontCare,
+            )));
+        self.generate_absolute(&dividend_abs, dividend, byte_count);
+        self.generate_absolute(&divisor_abs, divisor, byte_count);
+
+        self.generate_shift_and_subtract(
+            &Value::Memory(MemoryData::new(value_type.clone(), dividend_abs, None)),
+            &Value::Memory(MemoryData::new(value_type.clone(), divisor_abs, None)),
+            byte_count,
+        )?;
+
+        let dest = self.binop.dest;
+        let quotient = Value::Memory(MemoryData::new(value_type, dest.clone(), None));
+        self.generate_negate_if_negative(dest, &quotient, memory_bytes(&[sign]).remove(0));
+        Ok(())
+    }
+
+    fn generate_absolute(&mut self, destination: &Location, value: &Value, byte_count: usize) {
+        let block_ref = self.binop.run_builder.current_block().block_ref;
+        self.binop.generate_byte_copies(
+            block_ref,
+            &location_bytes(destination, byte_count),
+            &value_bytes(value, byte_count),
+        );
+        let absolute = Value::Memory(MemoryData::new(value.value_type(), destination.clone(), None));
+        let top_byte = value_bytes(value, byte_count).remove(byte_count - 1);
+        self.generate_negate_if_negative(destination, &absolute, top_byte);
+    }
+
+    /// Negates the value into the destination when the sign byte has its top bit set
+    fn generate_negate_if_negative(&mut self, destination: &Location, value: &Value, sign_byte: Value) {
+        let src_tag = self.binop.src_tag;
+        let run_builder = &mut *self.binop.run_builder;
+        let after_block_symbol = run_builder.reserve_block();
+        run_builder
+            .new_block()
+            .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                src_tag,
+                sign_byte,
+                immediate_u8(0x80),
+                BranchFlag::Carry,
+                None,
+                Some(after_block_symbol),
+            )));
+        run_builder.new_block();
+        generate_negate(run_builder, src_tag, destination, value);
+        run_builder.new_reserved_block(after_block_symbol);
+    }
+
+    /// Restoring division: the dividend is shifted bit by bit into a remainder, and whenever the
+    /// remainder reaches the divisor, the divisor is subtracted and a 1 is shifted into the quotient.
+    ///
+    /// The remainder never needs more bytes than the operands, since before the Nth shift it holds
+    /// at most N - 1 bits of the dividend. Dividing by zero produces a quotient with all bits set.
+    fn generate_shift_and_subtract(&mut self, dividend: &Value, divisor: &Value, byte_count: usize) -> error::Result<()> {
+        let src_tag = self.binop.src_tag;
+        let value_type = dividend.value_type();
+        let quotient = location_bytes(self.binop.dest, byte_count);
+        let remainder = location_bytes(&self.binop.create_temporary(&value_type), byte_count);
+        let counter = self.binop.create_temporary(&BaseType::U8);
+        let counter_value = memory_bytes(&[counter.clone()]).remove(0);
+
+        let setup_block_ref = self.binop.run_builder.current_block().block_ref;
+        let shift_block_ref = self.binop.run_builder.new_block().block_ref;
+        let mut compare_block_refs = Vec::new();
+        for _ in 0..(byte_count * 2 - 1) {
+            compare_block_refs.push(self.binop.run_builder.new_block().block_ref);
+        }
+        let subtract_block_ref = self.binop.run_builder.new_block().block_ref;
+        let count_block_ref = self.binop.run_builder.new_block().block_ref;
+        let test_counter_block_ref = self.binop.run_builder.new_block().block_ref;
+        let jump_block_ref = self.binop.run_builder.new_block().block_ref;
+        let shift_block_symbol = self.binop.run_builder.block(shift_block_ref).symbol();
+        let subtract_block_symbol = self.binop.run_builder.block(subtract_block_ref).symbol();
+        let count_block_symbol = self.binop.run_builder.block(count_block_ref).symbol();
+        let after_block_symbol = self.binop.run_builder.new_block().symbol();
+
+        self.binop.generate_byte_copies(
+            setup_block_ref,
+            &quotient,
+            &value_bytes(dividend, byte_count),
+        );
+        self.binop
+            .generate_byte_copies(setup_block_ref, &remainder, &vec![immediate_u8(0); byte_count]);
+        self.binop.generate_byte_copies(
+            setup_block_ref,
+            &[counter.clone()],
+            &[immediate_u8((byte_count * 8) as i32)],
+        );
+
+        // Shift the quotient and remainder left together as one number so that
+        // the top bit of the quotient carries into the bottom of the remainder
+        let mut shifted = quotient.clone();
+        shifted.extend(remainder.iter().cloned());
+        let shifted_values = memory_bytes(&shifted);
+        self.binop.generate_carry_chain(
+            shift_block_ref,
+            false,
+            &shifted,
+            &shifted_values,
+            &shifted_values,
+        );
+
+        // Compare the remainder against the divisor starting with the most significant byte.
+        // Only an equal byte requires looking at the next one down.
+        let remainder_values = memory_bytes(&remainder);
+        let divisor_values = value_bytes(divisor, byte_count);
+        let mut compare_blocks = compare_block_refs.into_iter();
+        for index in (0..byte_count).rev() {
+            self.binop
+                .run_builder
+                .block(compare_blocks.next().unwrap())
+                .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                    src_tag,
+                    remainder_values[index].clone(),
+                    divisor_values[index].clone(),
+                    BranchFlag::Carry,
+                    None,
+                    Some(count_block_symbol),
+                )));
+            if index > 0 {
+                self.binop
+                    .run_builder
+                    .block(compare_blocks.next().unwrap())
+                    .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                        src_tag,
+                        remainder_values[index].clone(),
+                        divisor_values[index].clone(),
+                        BranchFlag::Zero,
+                        None,
+                        Some(subtract_block_symbol),
+                    )));
+            }
+        }
+
+        self.binop.generate_carry_chain(
+            subtract_block_ref,
+            true,
+            &remainder,
+            &remainder_values,
+            &divisor_values,
+        );
+        // The shift left a zero in the lowest bit of the quotient, so this can't carry
+        self.binop.generate_carry_chain(
+            subtract_block_ref,
+            false,
+            &quotient[0..1],
+            &memory_bytes(&quotient[0..1]),
+            &[immediate_u8(1)],
+        );
+
+        self.binop.generate_carry_chain(
+            count_block_ref,
+            true,
+            &[counter],
+            &[counter_value.clone()],
+            &[immediate_u8(1)],
+        );
+
+        self.binop
+            .run_builder
+            .block(test_counter_block_ref)
+            .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                src_tag,
+                counter_value,
+                immediate_u8(0),
+                BranchFlag::Zero,
+                Some(after_block_symbol),
+                None,
+            )));
+
+        // Relative branches can't reach very far, so jump back to the start of the loop
+        self.binop
+            .run_builder
+            .block(jump_block_ref)
+            .add_statement(Statement::GoTo(GoToData::new(src_tag, shift_block_symbol)));
+
+        Ok(())
+    }
+}
@@ -12,15 +12,19 @@ use std::sync::Arc;
 
 use base_type::BaseType;
 use error;
-use llir::builder::RunBuilder;
+use llir::builder::{BlockRef, RunBuilder};
 use llir::common::convert_location;
-use llir::{CopyData, ImmediateValue, Location, MemoryData, Statement, Value};
+use llir::{BinaryOpData, CarryMode, CopyData, ImmediateValue, Location, MemoryData, ShiftData, Statement, Value};
 use parse::ast::BinaryOperator;
 use src_tag::SrcTag;
 use symbol_table::SymbolRef;
 
 pub mod add;
+pub mod bitwise;
 pub mod compare;
+pub mod divide;
+pub mod multiply;
+pub mod shift;
 
 #[derive(new)]
 pub struct BinopGenerator<'a> {
@@ -39,12 +43,151 @@ impl<'a> BinopGenerator<'a> {
         match op {
             Add => add::AddGenerator::new(self).generate(false),
             Sub => add::AddGenerator::new(self).generate(true),
+            Mul => multiply::MultiplyGenerator::new(self).generate(),
+            Div => divide::DivideGenerator::new(self).generate(),
             Equal | NotEqual | LessThan | LessThanEqual | GreaterThan | GreaterThanEqual => {
                 compare::CompareGenerator::new(self).generate(op)
             }
-            _ => unimplemented!(),
+            BitAnd | BitOr | BitXor => bitwise::BitwiseGenerator::new(self).generate(op),
+            ShiftLeft => shift::ShiftGenerator::new(self).generate(true),
+            ShiftRight => shift::ShiftGenerator::new(self).generate(false),
+            LogicalAnd | LogicalOr => unreachable!("logical operators are generated as branches"),
         }
     }
+
+    fn create_temporary(&mut self, base_type: &BaseType) -> Location {
+        convert_location(
+            self.frame_ref,
+            &self.run_builder
+                .symbol_table()
+                .write()
+                .unwrap()
+                .create_temporary_location(base_type),
+        )
+    }
+
+    /// Copies the given values into the given locations, one byte at a time
+    fn generate_byte_copies(&mut self, block_ref: BlockRef, destination: &[Location], values: &[Value]) {
+        for (location, value) in destination.iter().zip(values.iter()) {
+            self.run_builder
+                .block(block_ref)
+                .add_statement(Statement::Copy(CopyData::new(
+                    self.src_tag,
+                    location.clone(),
+                    value.clone(),
+                )));
+        }
+    }
+
+    /// Adds or subtracts multi-byte values starting with the least significant byte,
+    /// and lets the carry flow from each byte into the next
+    fn generate_carry_chain(
+        &mut self,
+        block_ref: BlockRef,
+        subtract: bool,
+        destination: &[Location],
+        left: &[Value],
+        right: &[Value],
+    ) {
+        for (index, location) in destination.iter().enumerate() {
+            let carry_mode = match (index, subtract) {
+                (0, true) => CarryMode::SetCarry,
+                (0, false) => CarryMode::ClearCarry,
+                _ => CarryMode::DontCare,
+            };
+            let data = BinaryOpData::new(
+                self.src_tag,
+                location.clone(),
+                left[index].clone(),
+                right[index].clone(),
+                carry_mode,
+            );
+            self.run_builder
+                .block(block_ref)
+                .add_statement(if subtract {
+                    Statement::Subtract(data)
+                } else {
+                    Statement::Add(data)
+                });
+        }
+    }
+}
+
+/// Fills in the high byte for a single byte value that is being widened into two bytes.
+/// Unsigned values get a high byte of zero, and signed values get their sign extended.
+pub fn generate_extension(run_builder: &mut RunBuilder, src_tag: SrcTag, destination: Location, value: &Value) {
+    let extension = match (value.value_type().is_signed(), immediate_number(value)) {
+        (false, _) => Some(0),
+        (true, Some(number)) => Some(if number & 0x80 != 0 { 0xFF } else { 0 }),
+        (true, None) => None,
+    };
+    let mut block = run_builder.current_block();
+    if let Some(extension) = extension {
+        block.add_statement(Statement::Copy(CopyData::new(
+            src_tag,
+            destination,
+            immediate_u8(extension),
+        )));
+    } else {
+        // Shift the sign bit into the carry, and then turn the carry into $00 or $FF
+        block
+            .add_statement(Statement::ShiftLeft(ShiftData::new(
+                src_tag,
+                destination.clone(),
+                value.clone(),
+                false,
+            )))
+            .add_statement(Statement::Add(BinaryOpData::new(
+                src_tag,
+                destination.clone(),
+                immediate_u8(0xFF),
+                immediate_u8(0),
+                CarryMode::DontCare,
+            )))
+            .add_statement(Statement::ExclusiveOr(BinaryOpData::new(
+                src_tag,
+                destination.clone(),
+                Value::Memory(MemoryData::new(BaseType::U8, destination, None)),
+                immediate_u8(0xFF),
+                CarryMode::DontCare,
+            )));
+    }
+}
+
+/// Splits a location into its individual bytes, least significant byte first
+fn location_bytes(location: &Location, byte_count: usize) -> Vec<Location> {
+    match byte_count {
+        1 => vec![location.clone()],
+        2 => vec![location.low_byte(), location.high_byte()],
+        _ => unreachable!(),
+    }
+}
+
+/// Splits a value into its individual bytes, least significant byte first
+fn value_bytes(value: &Value, byte_count: usize) -> Vec<Value> {
+    match byte_count {
+        1 => vec![value.clone()],
+        2 => vec![Value::low_byte(value), Value::high_byte(value)],
+        _ => unreachable!(),
+    }
+}
+
+fn memory_bytes(locations: &[Location]) -> Vec<Value> {
+    locations
+        .iter()
+        .map(|location| Value::Memory(MemoryData::new(BaseType::U8, location.clone(), None)))
+        .collect()
+}
+
+fn immediate_number(value: &Value) -> Option<i32> {
+    match *value {
+        Value::Immediate(_, ImmediateValue::Number(num)) => Some(num),
+        _ => None,
+    }
+}
+
+fn immediate_u8(value: i32) -> Value {
+    Value::Immediate(BaseType::U8, ImmediateValue::Number(value))
 }
 
 #[derive(new)]
@@ -67,12 +210,12 @@ impl<'a> TypeCoercer<'a> {
         let mut actual_right = Cow::Borrowed(right_value);
 
         if let Some(coerced_type) = BaseType::choose_type(&left_type, &right_type) {
-            if coerced_type == BaseType::U16 {
-                if left_type == BaseType::U8 {
-                    actual_left = Cow::Owned(self.convert_u8_into_u16(left_value)?);
+            if coerced_type == BaseType::U16 || coerced_type == BaseType::I16 {
+                if left_type == BaseType::U8 || left_type == BaseType::I8 {
+                    actual_left = Cow::Owned(self.convert_u8_into_u16(left_value, &coerced_type)?);
                 }
-                if right_type == BaseType::U8 {
-                    actual_right = Cow::Owned(self.convert_u8_into_u16(right_value)?);
+                if right_type == BaseType::U8 || right_type == BaseType::I8 {
+                    actual_right = Cow::Owned(self.convert_u8_into_u16(right_value, &coerced_type)?);
                 }
             }
         }
@@ -83,22 +226,18 @@ impl<'a> TypeCoercer<'a> {
         Ok((actual_left, actual_right))
     }
 
-    fn convert_u8_into_u16(&mut self, value: &Value) -> error::Result<Value> {
+    /// Widens a single byte value into a temporary of the given two byte type.
+    /// Signed values are sign extended.
+    fn convert_u8_into_u16(&mut self, value: &Value, into_type: &BaseType) -> error::Result<Value> {
         let temp_addr = convert_location(
             self.frame_ref,
             &self.run_builder
                 .symbol_table()
                 .write()
                 .unwrap()
-                .create_temporary_location(&BaseType::U16),
+                .create_temporary_location(into_type),
         );
-        self.run_builder
-            .current_block()
-            .add_statement(Statement::Copy(CopyData::new(
-                self.src_tag,
-                temp_addr.high_byte(),
-                Value::Immediate(BaseType::U8, ImmediateValue::Number(0)),
-            )));
+        generate_extension(self.run_builder, self.src_tag, temp_addr.high_byte(), value);
         self.run_builder
             .current_block()
             .add_statement(Statement::Copy(CopyData::new(
@@ -107,7 +246,7 @@ impl<'a> TypeCoercer<'a> {
                 value.clone(),
             )));
         Ok(Value::Memory(MemoryData::new(
-            BaseType::U16,
+            into_type.clone(),
             temp_addr,
             Some(Arc::new("tmp_u8_to_u16".into())),
         )))
@@ -0,0 +1,162 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use base_type::BaseType;
+use error;
+use llir::{BranchFlag, CompareBranchData, GoToData, ImmediateValue, Statement, Value};
+use super::{immediate_number, immediate_u8, location_bytes, memory_bytes, value_bytes, BinopGenerator, TypeCoercer};
+
+#[derive(new)]
+pub struct MultiplyGenerator<'a> {
+    binop: BinopGenerator<'a>,
+}
+
+impl<'a> MultiplyGenerator<'a> {
+    pub fn generate(mut self) -> error::Result<()> {
+        let (actual_left, actual_right) = {
+            let mut type_coercer = TypeCoercer::new(
+                self.binop.run_builder,
+                self.binop.frame_ref,
+                self.binop.src_tag,
+            );
+            type_coercer.coerce_values_to_same_types(self.binop.left_value, self.binop.right_value)?
+        };
+
+        let value_type = actual_left.value_type();
+        if !value_type.is_integer() {
+            panic!("something went wrong with type checking");
+        }
+        if *self.binop.dest_type != value_type {
+            panic!("type checking didn't catch coersion of {} to {}", value_type, self.binop.dest_type);
+        }
+        let byte_count = value_type.size().unwrap();
+
+        if let (Some(left), Some(right)) = (immediate_number(&actual_left), immediate_number(&actual_right)) {
+            let product = left.wrapping_mul(right) & ((1 << (byte_count * 8)) - 1);
+            let destination = location_bytes(self.binop.dest, byte_count);
+            let block_ref = self.binop.run_builder.current_block().block_ref;
+            let values = value_bytes(
+                &Value::Immediate(value_type, ImmediateValue::Number(product)),
+                byte_count,
+            );
+            self.binop
+                .generate_byte_copies(block_ref, &destination, &values);
+            return Ok(());
+        }
+
+        self.generate_shift_and_add(actual_left.as_ref(), actual_right.as_ref(), byte_count)
+    }
+
+    /// Multiplies by walking the multiplier from its most significant bit down. Each step doubles
+    /// the product, and adds the multiplicand into it when the current multiplier bit is set.
+    fn generate_shift_and_add(
+        &mut self,
+        multiplicand: &Value,
+        multiplier: &Value,
+        byte_count: usize,
+    ) -> error::Result<()> {
+        let src_tag = self.binop.src_tag;
+        let value_type = multiplicand.value_type();
+        let product = location_bytes(self.binop.dest, byte_count);
+        let multiplier_tmp = location_bytes(&self.binop.create_temporary(&value_type), byte_count);
+        let counter = self.binop.create_temporary(&BaseType::U8);
+        let counter_value = memory_bytes(&[counter.clone()]).remove(0);
+
+        let setup_block_ref = self.binop.run_builder.current_block().block_ref;
+        let loop_block_ref = self.binop.run_builder.new_block().block_ref;
+        let test_bit_block_ref = self.binop.run_builder.new_block().block_ref;
+        let add_block_ref = self.binop.run_builder.new_block().block_ref;
+        let shift_block_ref = self.binop.run_builder.new_block().block_ref;
+        let test_counter_block_ref = self.binop.run_builder.new_block().block_ref;
+        let jump_block_ref = self.binop.run_builder.new_block().block_ref;
+        let loop_block_symbol = self.binop.run_builder.block(loop_block_ref).symbol();
+        let shift_block_symbol = self.binop.run_builder.block(shift_block_ref).symbol();
+        let after_block_symbol = self.binop.run_builder.new_block().symbol();
+
+        let zeros = vec![immediate_u8(0); byte_count];
+        self.binop.generate_byte_copies(
+            setup_block_ref,
+            &multiplier_tmp,
+            &value_bytes(multiplier, byte_count),
+        );
+        self.binop
+            .generate_byte_copies(setup_block_ref, &product, &zeros);
+        self.binop.generate_byte_copies(
+            setup_block_ref,
+            &[counter.clone()],
+            &[immediate_u8((byte_count * 8) as i32)],
+        );
+
+        let product_values = memory_bytes(&product);
+        self.binop.generate_carry_chain(
+            loop_block_ref,
+            false,
+            &product,
+            &product_values,
+            &product_values,
+        );
+
+        // The most significant bit of the multiplier is set if its top byte is >= 0x80
+        let multiplier_values = memory_bytes(&multiplier_tmp);
+        self.binop
+            .run_builder
+            .block(test_bit_block_ref)
+            .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                src_tag,
+                multiplier_values[byte_count - 1].clone(),
+                immediate_u8(0x80),
+                BranchFlag::Carry,
+                None,
+                Some(shift_block_symbol),
+            )));
+
+        self.binop.generate_carry_chain(
+            add_block_ref,
+            false,
+            &product,
+            &product_values,
+            &value_bytes(multiplicand, byte_count),
+        );
+
+        self.binop.generate_carry_chain(
+            shift_block_ref,
+            false,
+            &multiplier_tmp,
+            &multiplier_values,
+            &multiplier_values,
+        );
+        self.binop.generate_carry_chain(
+            shift_block_ref,
+            true,
+            &[counter],
+            &[counter_value.clone()],
+            &[immediate_u8(1)],
+        );
+
+        self.binop
+            .run_builder
+            .block(test_counter_block_ref)
+            .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                src_tag,
+                counter_value,
+                immediate_u8(0),
+                BranchFlag::Zero,
+                Some(after_block_symbol),
+                None,
+            )));
+
+        // Relative branches can't reach very far, so jump back to the start of the loop
+        self.binop
+            .run_builder
+            .block(jump_block_ref)
+            .add_statement(Statement::GoTo(GoToData::new(src_tag, loop_block_symbol)));
+
+        Ok(())
+    }
+}
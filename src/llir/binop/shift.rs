@@ -0,0 +1,208 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use base_type::BaseType;
+use error;
+use llir::builder::BlockRef;
+use llir::{BranchFlag, CompareBranchData, GoToData, ImmediateValue, Location, ShiftData, Statement, Value};
+use super::{immediate_number, immediate_u8, location_bytes, memory_bytes, value_bytes, BinopGenerator};
+
+#[derive(new)]
+pub struct ShiftGenerator<'a> {
+    binop: BinopGenerator<'a>,
+}
+
+impl<'a> ShiftGenerator<'a> {
+    pub fn generate(mut self, left: bool) -> error::Result<()> {
+        let value = self.binop.left_value;
+        let amount = self.binop.right_value;
+
+        let value_type = value.value_type();
+        if !value_type.is_integer() {
+            panic!("something went wrong with type checking");
+        }
+        if *self.binop.dest_type != value_type {
+            panic!("type checking didn't catch coersion of {} to {}", value_type, self.binop.dest_type);
+        }
+        let byte_count = value_type.size().unwrap();
+        let bit_count = (byte_count * 8) as i32;
+        // Signed values are shifted right arithmetically, which needs somewhere to put the sign bit
+        let sign_scratch = if !left && value_type.is_signed() {
+            Some(self.binop.create_temporary(&BaseType::U8))
+        } else {
+            None
+        };
+        let destination = location_bytes(self.binop.dest, byte_count);
+        let block_ref = self.binop.run_builder.current_block().block_ref;
+
+        match (immediate_number(value), immediate_number(amount)) {
+            (Some(number), Some(shift_by)) => {
+                let result = if sign_scratch.is_some() {
+                    number >> shift_by.min(bit_count - 1)
+                } else if shift_by >= bit_count {
+                    0
+                } else if left {
+                    (number << shift_by) & ((1 << bit_count) - 1)
+                } else {
+                    number >> shift_by
+                };
+                let values = value_bytes(
+                    &Value::Immediate(value_type, ImmediateValue::Number(result)),
+                    byte_count,
+                );
+                self.binop
+                    .generate_byte_copies(block_ref, &destination, &values);
+            }
+            (_, Some(shift_by)) => {
+                // Shifting a signed value right by its whole width leaves only copies of the sign bit
+                let shift_by = if sign_scratch.is_some() {
+                    shift_by.min(bit_count - 1)
+                } else {
+                    shift_by
+                };
+                if shift_by >= bit_count {
+                    self.binop
+                        .generate_byte_copies(block_ref, &destination, &vec![immediate_u8(0); byte_count]);
+                } else if shift_by == 0 {
+                    self.binop
+                        .generate_byte_copies(block_ref, &destination, &value_bytes(value, byte_count));
+                } else {
+                    // Shift the original value into the destination first, and then shift it in place
+                    self.generate_single_shift(
+                        block_ref,
+                        left,
+                        &sign_scratch,
+                        &destination,
+                        &value_bytes(value, byte_count),
+                    );
+                    for _ in 1..shift_by {
+                        self.generate_single_shift(
+                            block_ref,
+                            left,
+                            &sign_scratch,
+                            &destination,
+                            &memory_bytes(&destination),
+                        );
+                    }
+                }
+            }
+            (_, None) => self.generate_shift_loop(left, &sign_scratch, value, amount, &destination),
+        }
+        Ok(())
+    }
+
+    /// Shifts by an amount that isn't known until runtime, one bit at a time. Only the
+    /// low byte of the amount is used.
+    fn generate_shift_loop(
+        &mut self,
+        left: bool,
+        sign_scratch: &Option<Location>,
+        value: &Value,
+        amount: &Value,
+        destination: &[Location],
+    ) {
+        let src_tag = self.binop.src_tag;
+        let counter = self.binop.create_temporary(&BaseType::U8);
+        let counter_value = memory_bytes(&[counter.clone()]).remove(0);
+
+        let setup_block_ref = self.binop.run_builder.current_block().block_ref;
+        let test_block_ref = self.binop.run_builder.new_block().block_ref;
+        let shift_block_ref = self.binop.run_builder.new_block().block_ref;
+        let test_block_symbol = self.binop.run_builder.block(test_block_ref).symbol();
+        let after_block_symbol = self.binop.run_builder.new_block().symbol();
+
+        self.binop.generate_byte_copies(
+            setup_block_ref,
+            destination,
+            &value_bytes(value, destination.len()),
+        );
+        self.binop.generate_byte_copies(
+            setup_block_ref,
+            &[counter.clone()],
+            &[Value::low_byte(amount)],
+        );
+
+        self.binop
+            .run_builder
+            .block(test_block_ref)
+            .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                src_tag,
+                counter_value.clone(),
+                immediate_u8(0),
+                BranchFlag::Zero,
+                Some(after_block_symbol),
+                None,
+            )));
+
+        self.generate_single_shift(
+            shift_block_ref,
+            left,
+            sign_scratch,
+            destination,
+            &memory_bytes(destination),
+        );
+        self.binop.generate_carry_chain(
+            shift_block_ref,
+            true,
+            &[counter],
+            &[counter_value],
+            &[immediate_u8(1)],
+        );
+        self.binop
+            .run_builder
+            .block(shift_block_ref)
+            .add_statement(Statement::GoTo(GoToData::new(src_tag, test_block_symbol)));
+    }
+
+    /// Shifts a value by one bit. Left shifts start at the least significant byte, and right
+    /// shifts start at the most significant byte, so that the carry moves bits between bytes.
+    /// When a sign scratch location is given, the right shift copies the sign bit into the top.
+    fn generate_single_shift(
+        &mut self,
+        block_ref: BlockRef,
+        left: bool,
+        sign_scratch: &Option<Location>,
+        destination: &[Location],
+        value: &[Value],
+    ) {
+        let mut indices: Vec<usize> = (0..destination.len()).collect();
+        if !left {
+            indices.reverse();
+        }
+        if let Some(ref scratch) = *sign_scratch {
+            // Shift the sign bit into the carry so that it gets rotated back into the top
+            self.binop
+                .run_builder
+                .block(block_ref)
+                .add_statement(Statement::ShiftLeft(ShiftData::new(
+                    self.binop.src_tag,
+                    scratch.clone(),
+                    value[indices[0]].clone(),
+                    false,
+                )));
+        }
+        let rotate_first = sign_scratch.is_some();
+        for (position, index) in indices.into_iter().enumerate() {
+            let data = ShiftData::new(
+                self.binop.src_tag,
+                destination[index].clone(),
+                value[index].clone(),
+                rotate_first || position > 0,
+            );
+            self.binop
+                .run_builder
+                .block(block_ref)
+                .add_statement(if left {
+                    Statement::ShiftLeft(data)
+                } else {
+                    Statement::ShiftRight(data)
+                });
+        }
+    }
+}
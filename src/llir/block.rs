@@ -11,16 +11,20 @@ use std::fmt;
 use std::sync::Arc;
 use src_tag::{SrcTag, SrcTagged};
 use symbol_table::{SymbolName, SymbolRef};
-use base_type::BaseType;
+use base_type::{BaseType, NativeType};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Location {
     DataStackOffset(i8),
     FrameOffset(SymbolRef, i8),
-    FrameOffsetIndirect(SymbolRef, i8),
+    /// Memory pointed to by pointers in the frame. There's one pointer for each byte of the value,
+    /// given by its frame offset, starting with the pointer to the first byte.
+    FrameOffsetIndirect(SymbolRef, Vec<i8>),
     FrameOffsetBeforeCall(SymbolRef, SymbolRef, i8),
     Global(u16),
     GlobalIndexed(u16, Box<Value>),
+    /// Memory that is the given number of bytes past the address held by a pointer in zero page
+    ZeroPageIndirect(u8, u8),
     UnresolvedGlobal(SymbolRef),
     UnresolvedGlobalIndexed(SymbolRef, Box<Value>),
     UnresolvedGlobalLowByte(SymbolRef),
@@ -40,12 +44,25 @@ impl Location {
     pub fn high_byte(&self) -> Location {
         use self::Location::*;
         match *self {
-            DataStackOffset(offset) => DataStackOffset(offset + 1),
-            FrameOffset(symbol, offset) => FrameOffset(symbol, offset + 1),
-            FrameOffsetBeforeCall(sym1, sym2, offset) => FrameOffsetBeforeCall(sym1, sym2, offset + 1),
-            Global(offset) => Global((offset as isize + 1) as u16),
-            GlobalIndexed(offset, ref index) => GlobalIndexed(offset + 1, index.clone()),
             UnresolvedGlobal(symbol) => UnresolvedGlobalHighByte(symbol),
+            _ => self.offset_by(1),
+        }
+    }
+
+    /// Returns the location of the byte that is `bytes` after this one
+    pub fn offset_by(&self, bytes: usize) -> Location {
+        use self::Location::*;
+        if bytes == 0 {
+            return self.clone();
+        }
+        match *self {
+            DataStackOffset(offset) => DataStackOffset(offset + bytes as i8),
+            FrameOffset(symbol, offset) => FrameOffset(symbol, offset + bytes as i8),
+            FrameOffsetIndirect(symbol, ref pointers) => FrameOffsetIndirect(symbol, pointers[bytes..].to_vec()),
+            FrameOffsetBeforeCall(sym1, sym2, offset) => FrameOffsetBeforeCall(sym1, sym2, offset + bytes as i8),
+            Global(offset) => Global((offset as usize + bytes) as u16),
+            GlobalIndexed(offset, ref index) => GlobalIndexed((offset as usize + bytes) as u16, index.clone()),
+            ZeroPageIndirect(pointer, offset) => ZeroPageIndirect(pointer, offset + bytes as u8),
             _ => unreachable!(),
         }
     }
@@ -62,6 +79,9 @@ pub struct MemoryData {
 pub enum ImmediateValue {
     Number(i32),
     Symbol(SymbolRef),
+    /// Distance from the data stack pointer to a frame slot, which
+    /// is only known once the size of each frame has been calculated
+    FrameOffset(SymbolRef, i8),
 }
 
 impl ImmediateValue {
@@ -87,18 +107,23 @@ impl Value {
         }
     }
 
+    /// Returns the same value, but treated as the given type
+    pub fn with_type(&self, base_type: &BaseType) -> Value {
+        match *self {
+            Value::Immediate(_, ref val) => Value::Immediate(base_type.clone(), val.clone()),
+            Value::Memory(ref data) => Value::Memory(MemoryData::new(
+                base_type.clone(),
+                data.location.clone(),
+                data.debug.clone(),
+            )),
+        }
+    }
+
     pub fn high_byte(value: &Value) -> Value {
         use self::Value::*;
         // 16-bit values on the 6502 are in little-endian
         match *value {
-            Immediate(BaseType::U16, ref val) => match *val {
-                ImmediateValue::Number(num) => Immediate(
-                    BaseType::U8,
-                    ImmediateValue::Number(((((num as u16) & 0xFF00) >> 8) as u8) as i32),
-                ),
-                ImmediateValue::Symbol(ref _sym) => unimplemented!(),
-            },
-            Immediate(BaseType::Pointer(_), ref val) => match *val {
+            Immediate(ref base_type, ref val) if base_type.as_native() == Some(NativeType::U16) => match *val {
                 ImmediateValue::Number(num) => Immediate(
                     BaseType::U8,
                     ImmediateValue::Number(((((num as u16) & 0xFF00) >> 8) as u8) as i32),
@@ -108,6 +133,7 @@ impl Value {
                     Location::UnresolvedGlobalHighByte(*sym),
                     None,
                 )),
+                ImmediateValue::FrameOffset(_, _) => unreachable!(),
             },
             Immediate(_, _) => unreachable!(),
             Memory(ref data) => Memory(MemoryData::new(
@@ -122,17 +148,14 @@ impl Value {
         use self::Value::*;
         // 16-bit values on the 6502 are in little-endian
         match *value {
-            Immediate(BaseType::U16, ref val) => match *val {
-                ImmediateValue::Number(num) => Immediate(BaseType::U8, ImmediateValue::Number((num as u8) as i32)),
-                ImmediateValue::Symbol(ref _sym) => unimplemented!(),
-            },
-            Immediate(BaseType::Pointer(_), ref val) => match *val {
+            Immediate(ref base_type, ref val) if base_type.as_native() == Some(NativeType::U16) => match *val {
                 ImmediateValue::Number(num) => Immediate(BaseType::U8, ImmediateValue::Number((num as u8) as i32)),
                 ImmediateValue::Symbol(ref sym) => Value::Memory(MemoryData::new(
                     BaseType::U8,
                     Location::UnresolvedGlobalLowByte(*sym),
                     None,
                 )),
+                ImmediateValue::FrameOffset(_, _) => unreachable!(),
             },
             Immediate(_, _) => unreachable!(),
             Memory(ref data) => Memory(MemoryData::new(
@@ -149,6 +172,10 @@ pub enum SPOffset {
     Immediate(i8),
     FrameSize(SymbolRef),
     NegativeFrameSize(SymbolRef),
+    /// Moves past the largest frame that interrupted code could be in the middle of
+    /// setting up, followed by the interrupt handler's own frame
+    InterruptFrameSize(SymbolRef),
+    NegativeInterruptFrameSize(SymbolRef),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, new)]
@@ -174,6 +201,41 @@ pub struct BinaryOpData {
     pub carry_mode: CarryMode,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct ShiftData {
+    pub tag: SrcTag,
+    pub destination: Location,
+    pub value: Value,
+    /// Shift the carry flag into the vacated bit rather than a zero, so that
+    /// shifts of multi-byte values can be chained together
+    pub rotate: bool,
+}
+
+/// Modifies a single byte in memory in place, such as by adding or subtracting one, without going through a register
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct StepData {
+    pub tag: SrcTag,
+    pub location: Location,
+}
+
+/// Reads the byte at `address` into `destination`, for `peek`. Only the accumulator is changed.
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct PeekData {
+    pub tag: SrcTag,
+    /// The byte that's read, which is usually reached through a pointer
+    pub address: Location,
+    pub destination: Location,
+}
+
+/// Writes `value` to the byte at `address`, for `poke`. Only the accumulator is changed.
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct PokeData {
+    pub tag: SrcTag,
+    /// The byte that's written, which is usually reached through a pointer
+    pub address: Location,
+    pub value: Value,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, new)]
 pub struct BranchIfZeroData {
     pub tag: SrcTag,
@@ -187,12 +249,60 @@ pub struct GoToData {
     pub destination: SymbolRef,
 }
 
+/// A flag in the processor status register that can be set or cleared on its own
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StatusFlag {
+    /// Goes into rotates, and into additions and subtractions that don't set it themselves
+    Carry,
+    /// Makes addition and subtraction work with binary-coded decimal
+    Decimal,
+    /// Keeps IRQs from interrupting the program
+    InterruptDisable,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct StatusFlagData {
+    pub tag: SrcTag,
+    pub flag: StatusFlag,
+}
+
+/// An instruction that doesn't touch any registers or memory
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct InstructionData {
+    pub tag: SrcTag,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, new)]
 pub struct InlineAsmData {
     pub tag: SrcTag,
     pub asm: Arc<String>,
 }
 
+/// Jumps to the address found at `index` in a pair of address tables
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct JumpIndirectData {
+    pub tag: SrcTag,
+    pub index: Value,
+    pub low_table: SymbolRef,
+    pub high_table: SymbolRef,
+    /// Where the address is stored before jumping through it
+    pub vector: Location,
+}
+
+/// One byte of each address in a list of addresses, emitted as data
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct AddressTableData {
+    pub tag: SrcTag,
+    pub addresses: Vec<SymbolRef>,
+}
+
+/// Jumps to the address stored at `vector`
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct JumpVectorData {
+    pub tag: SrcTag,
+    pub vector: Location,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, new)]
 pub struct JumpRoutineData {
     pub tag: SrcTag,
@@ -204,6 +314,14 @@ pub struct ReturnData {
     pub tag: SrcTag,
 }
 
+/// Memory that an interrupt handler saves along with the registers on entry,
+/// and restores on exit, since the interrupted code may be using it
+#[derive(Debug, Clone, Eq, PartialEq, new)]
+pub struct InterruptData {
+    pub tag: SrcTag,
+    pub saved: Vec<Location>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, new)]
 pub struct AddToDataStackPointerData {
     pub tag: SrcTag,
@@ -216,6 +334,9 @@ pub enum BranchFlag {
     Zero,
     /// Branch based on the carry flag
     Carry,
+    /// Branch based on the sign of the signed subtraction of right from left, corrected
+    /// for overflow. It is set when left is less than right as signed numbers.
+    Negative,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, new)]
@@ -232,6 +353,9 @@ pub struct CompareBranchData {
 pub enum Statement {
     Add(BinaryOpData),
     AddToDataStackPointer(AddToDataStackPointerData),
+    And(BinaryOpData),
+    Break(InstructionData),
+    ClearFlag(StatusFlagData),
 
     CompareBranch(CompareBranchData),
 
@@ -242,10 +366,28 @@ pub enum Statement {
     #[deprecated] CompareGte(BinaryOpData),
 
     Copy(CopyData),
+    Decrement(StepData),
+    EnterInterrupt(InterruptData),
+    ExclusiveOr(BinaryOpData),
     GoTo(GoToData),
+    HighByteTable(AddressTableData),
+    Increment(StepData),
     InlineAsm(InlineAsmData),
+    JumpIndirect(JumpIndirectData),
     JumpRoutine(JumpRoutineData),
+    JumpVector(JumpVectorData),
+    LowByteTable(AddressTableData),
+    NoOp(InstructionData),
+    Or(BinaryOpData),
+    Peek(PeekData),
+    Poke(PokeData),
     Return(ReturnData),
+    ReturnFromInterrupt(InterruptData),
+    RotateLeft(StepData),
+    RotateRight(StepData),
+    SetFlag(StatusFlagData),
+    ShiftLeft(ShiftData),
+    ShiftRight(ShiftData),
     Subtract(BinaryOpData),
 }
 
@@ -253,7 +395,8 @@ impl Statement {
     pub fn is_branch(&self) -> bool {
         use self::Statement::*;
         match *self {
-            BranchIfZero(_) | GoTo(_) | JumpRoutine { .. } | Return { .. } => true,
+            BranchIfZero(_) | GoTo(_) | JumpIndirect(_) | JumpRoutine { .. } | JumpVector(_) | Return { .. }
+            | ReturnFromInterrupt(_) => true,
             _ => false,
         }
     }
@@ -263,15 +406,25 @@ impl SrcTagged for Statement {
     fn src_tag(&self) -> SrcTag {
         use self::Statement::*;
         match *self {
-            Add(ref d) | CompareEq(ref d) | CompareNotEq(ref d) | CompareLt(ref d) | CompareGte(ref d)
-            | Subtract(ref d) => d.tag,
+            Add(ref d) | And(ref d) | CompareEq(ref d) | CompareNotEq(ref d) | CompareLt(ref d) | CompareGte(ref d)
+            | ExclusiveOr(ref d) | Or(ref d) | Subtract(ref d) => d.tag,
+            ShiftLeft(ref d) | ShiftRight(ref d) => d.tag,
+            Decrement(ref d) | Increment(ref d) | RotateLeft(ref d) | RotateRight(ref d) => d.tag,
+            Break(ref d) | NoOp(ref d) => d.tag,
+            ClearFlag(ref d) | SetFlag(ref d) => d.tag,
+            HighByteTable(ref d) | LowByteTable(ref d) => d.tag,
             CompareBranch(ref d) => d.tag,
             AddToDataStackPointer(ref d) => d.tag,
             BranchIfZero(ref d) => d.tag,
             Copy(ref d) => d.tag,
+            EnterInterrupt(ref d) | ReturnFromInterrupt(ref d) => d.tag,
             GoTo(ref d) => d.tag,
             InlineAsm(ref d) => d.tag,
+            JumpIndirect(ref d) => d.tag,
             JumpRoutine(ref d) => d.tag,
+            JumpVector(ref d) => d.tag,
+            Peek(ref d) => d.tag,
+            Poke(ref d) => d.tag,
             Return(ref d) => d.tag,
         }
     }
@@ -286,6 +439,12 @@ impl fmt::Debug for Statement {
                 data.left, data.right, data.destination
             )?,
             Statement::AddToDataStackPointer(ref offset) => write!(f, "add_dsp {:?}", offset)?,
+            Statement::And(ref data) => write!(
+                f,
+                "and {:?} & {:?} => {:?}",
+                data.left, data.right, data.destination
+            )?,
+            Statement::Break(_) => write!(f, "brk")?,
             Statement::BranchIfZero(ref data) => write!(
                 f,
                 "branch to {:?} if {:?} == 0",
@@ -316,11 +475,50 @@ impl fmt::Debug for Statement {
                 "compare {:?} >= {:?} => {:?}",
                 data.left, data.right, data.destination
             )?,
+            Statement::ClearFlag(ref data) => write!(f, "clear {:?} flag", data.flag)?,
             Statement::Copy(ref data) => write!(f, "copy {:?} => {:?}", data.value, data.destination)?,
+            Statement::Decrement(ref data) => write!(f, "decrement {:?}", data.location)?,
+            Statement::EnterInterrupt(ref data) => write!(f, "enter interrupt, saving {:?}", data.saved)?,
+            Statement::ExclusiveOr(ref data) => write!(
+                f,
+                "xor {:?} ^ {:?} => {:?}",
+                data.left, data.right, data.destination
+            )?,
             Statement::GoTo(ref data) => write!(f, "goto {}", data.destination)?,
+            Statement::HighByteTable(ref data) => write!(f, "high bytes of {:?}", data.addresses)?,
+            Statement::Increment(ref data) => write!(f, "increment {:?}", data.location)?,
             Statement::InlineAsm(_) => write!(f, "inline_asm")?,
+            Statement::JumpIndirect(ref data) => write!(
+                f,
+                "jump to address {:?} in {} and {}",
+                data.index, data.low_table, data.high_table
+            )?,
             Statement::JumpRoutine(ref location) => write!(f, "jsr {:?}", location)?,
+            Statement::JumpVector(ref data) => write!(f, "jump to address in {:?}", data.vector)?,
+            Statement::LowByteTable(ref data) => write!(f, "low bytes of {:?}", data.addresses)?,
+            Statement::NoOp(_) => write!(f, "nop")?,
+            Statement::Or(ref data) => write!(
+                f,
+                "or {:?} | {:?} => {:?}",
+                data.left, data.right, data.destination
+            )?,
+            Statement::Peek(ref data) => write!(f, "peek {:?} => {:?}", data.address, data.destination)?,
+            Statement::Poke(ref data) => write!(f, "poke {:?} => {:?}", data.value, data.address)?,
             Statement::Return(_) => write!(f, "rts")?,
+            Statement::ReturnFromInterrupt(ref data) => write!(f, "rti, restoring {:?}", data.saved)?,
+            Statement::RotateLeft(ref data) => write!(f, "rotate left {:?}", data.location)?,
+            Statement::RotateRight(ref data) => write!(f, "rotate right {:?}", data.location)?,
+            Statement::SetFlag(ref data) => write!(f, "set {:?} flag", data.flag)?,
+            Statement::ShiftLeft(ref data) => write!(
+                f,
+                "shift left {:?} (rotate: {}) => {:?}",
+                data.value, data.rotate, data.destination
+            )?,
+            Statement::ShiftRight(ref data) => write!(
+                f,
+                "shift right {:?} (rotate: {}) => {:?}",
+                data.value, data.rotate, data.destination
+            )?,
             Statement::Subtract(ref data) => write!(
                 f,
                 "subtract {:?} - {:?} => {:?}",
@@ -69,6 +69,23 @@ impl RunBuilder {
         }
     }
 
+    /// Reserves the symbol for a block that doesn't exist yet so that it can be branched to.
+    /// The block gets created in order later on with `new_reserved_block`.
+    pub fn reserve_block(&mut self) -> SymbolRef {
+        self.symbol_table.write().unwrap().new_block_name().1
+    }
+
+    pub fn new_reserved_block<'a>(&'a mut self, symbol: SymbolRef) -> BlockBuilder<'a> {
+        let block_name = self.symbol_table.read().unwrap().get_symbol_name(symbol).unwrap();
+        let block = RunBlock::new(block_name, symbol);
+        self.blocks.push(block);
+        self.current_block = self.blocks.len() - 1;
+        BlockBuilder {
+            block_ref: self.current_block,
+            block: &mut self.blocks[self.current_block],
+        }
+    }
+
     pub fn block<'a>(&'a mut self, block_ref: BlockRef) -> BlockBuilder<'a> {
         BlockBuilder {
             block_ref: block_ref,
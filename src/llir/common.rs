@@ -12,7 +12,9 @@ use llir::Location;
 
 pub fn convert_location(frame: SymbolRef, input: &symbol_table::Location) -> Location {
     match *input {
-        symbol_table::Location::UndeterminedGlobal => unreachable!(),
+        symbol_table::Location::UndeterminedGlobal | symbol_table::Location::UndeterminedZeroPage => {
+            unreachable!()
+        }
         symbol_table::Location::Global(addr) => Location::Global(addr),
         symbol_table::Location::FrameOffset(offset) => Location::FrameOffset(frame, offset),
     }
@@ -9,17 +9,24 @@
 
 use std::sync::{Arc, RwLock};
 use error;
-use ir;
+use ir::{self, TypeChecking};
 use llir::builder::RunBuilder;
 use llir::common::convert_location;
-use llir::{binop, AddToDataStackPointerData, BranchIfZeroData, CopyData, FrameBlock, GoToData, ImmediateValue,
-           InlineAsmData, JumpRoutineData, Location, MemoryData, ReturnData, RunBlock, SPOffset, Statement, Value};
+use code::{DATA_STACK_POINTER_LOCATION, RETURN_VALUE_LOCATION};
+use llir::{binop, AddToDataStackPointerData, AddressTableData, BinaryOpData, BranchFlag, CarryMode, CompareBranchData,
+           CopyData, FrameBlock, GoToData, ImmediateValue, InlineAsmData, InstructionData, InterruptData,
+           JumpIndirectData, JumpRoutineData, JumpVectorData, Location, MemoryData, PeekData, PokeData, ReturnData,
+           RunBlock, SPOffset, Statement, StatusFlag, StatusFlagData, StepData, Value};
 use parse::ast;
-use symbol_table::{self, SymbolName, SymbolRef, SymbolTable};
+use symbol_table::{self, ConstantValue, SymbolName, SymbolRef, SymbolTable, MAX_FRAME_SIZE};
 use src_tag::{SrcTag, SrcTagged};
 use base_type::BaseType;
+use zero_page::ZERO_PAGE_POINTER_NAME;
 
-const RETURN_LOCATION_LO: Location = Location::Global(0x0001);
+const RETURN_LOCATION_LO: Location = Location::Global(RETURN_VALUE_LOCATION);
+
+/// Name of the routine that calls through a function pointer by jumping to the address in the return location
+const CALL_INDIRECT_NAME: &str = "__CALL_INDIRECT_";
 
 pub fn generate_llir(input: &[ir::Block]) -> error::Result<Vec<FrameBlock>> {
     let mut blocks = Vec::new();
@@ -40,25 +47,159 @@ pub fn generate_llir(input: &[ir::Block]) -> error::Result<Vec<FrameBlock>> {
             Arc::clone(&irblock.symbol_table),
             irblock.symbol,
             &irblock.body,
+            None,
         )?;
-        block.frame_size = calculate_frame_size(&*irblock.symbol_table.read().unwrap());
+        if irblock.metadata.read().unwrap().interrupt {
+            block.runs = generate_interrupt_handler(&irblock.symbol_table, irblock.symbol, block.runs);
+        }
+        block.frame_size = calculate_frame_size(&name, &*irblock.symbol_table.read().unwrap())?;
         blocks.push(block);
     }
+
+    for irblock in input {
+        if let Some(indirect_entry) = irblock.metadata.read().unwrap().indirect_entry {
+            blocks.push(generate_indirect_entry(
+                &irblock.symbol_table,
+                irblock.symbol,
+                indirect_entry,
+            ));
+        }
+    }
+    if let Some(irblock) = input.first() {
+        let call_indirect = irblock
+            .symbol_table
+            .read()
+            .unwrap()
+            .find_symbol(&Arc::new(CALL_INDIRECT_NAME.into()));
+        if let Some(call_indirect) = call_indirect {
+            let mut block = FrameBlock::new(
+                Arc::new(CALL_INDIRECT_NAME.into()),
+                call_indirect,
+                Location::UnresolvedGlobal(call_indirect),
+            );
+            let mut run_builder = RunBuilder::new(Arc::clone(&irblock.symbol_table));
+            run_builder
+                .current_block()
+                .add_statement(Statement::JumpVector(JumpVectorData::new(
+                    SrcTag::invalid(),
+                    RETURN_LOCATION_LO,
+                )));
+            block.runs = run_builder.build();
+            blocks.push(block);
+        }
+    }
     Ok(blocks)
 }
 
-fn calculate_frame_size(symbol_table: &SymbolTable) -> i8 {
+/// Generates the entry point that's used when calling a function through a pointer. The caller
+/// doesn't know how big the function's frame is, so it places the arguments right after its own
+/// frame, and the entry point moves the data stack pointer past the function's frame around the call.
+fn generate_indirect_entry(
+    symbol_table: &Arc<RwLock<SymbolTable>>,
+    function_ref: SymbolRef,
+    indirect_entry: SymbolRef,
+) -> FrameBlock {
+    let tag = SrcTag::invalid();
+    let name = symbol_table
+        .read()
+        .unwrap()
+        .get_symbol_name(indirect_entry)
+        .unwrap();
+    let mut block = FrameBlock::new(name, indirect_entry, Location::UnresolvedGlobal(indirect_entry));
+    let mut run_builder = RunBuilder::new(Arc::clone(symbol_table));
+    run_builder
+        .current_block()
+        .add_statement(Statement::AddToDataStackPointer(
+            AddToDataStackPointerData::new(tag, SPOffset::FrameSize(function_ref)),
+        ))
+        .add_statement(Statement::JumpRoutine(JumpRoutineData::new(
+            tag,
+            Location::UnresolvedGlobal(function_ref),
+        )))
+        .add_statement(Statement::AddToDataStackPointer(
+            AddToDataStackPointerData::new(tag, SPOffset::NegativeFrameSize(function_ref)),
+        ))
+        .add_statement(Statement::Return(ReturnData::new(tag)));
+    block.runs = run_builder.build();
+    block
+}
+
+/// Surrounds the body of an interrupt handler with code that saves and restores everything the
+/// interrupted code could be using. A handler that runs off the end still returns from the interrupt.
+fn generate_interrupt_handler(
+    symbol_table: &Arc<RwLock<SymbolTable>>,
+    frame_ref: SymbolRef,
+    body: Vec<RunBlock>,
+) -> Vec<RunBlock> {
+    let tag = SrcTag::invalid();
+    let saved = interrupt_saved_locations(&*symbol_table.read().unwrap());
+    let mut entry_builder = RunBuilder::new(Arc::clone(symbol_table));
+    entry_builder
+        .current_block()
+        .add_statement(Statement::EnterInterrupt(InterruptData::new(tag, saved)))
+        .add_statement(Statement::AddToDataStackPointer(
+            AddToDataStackPointerData::new(tag, SPOffset::InterruptFrameSize(frame_ref)),
+        ));
+    let mut exit_builder = RunBuilder::new(Arc::clone(symbol_table));
+    generate_interrupt_return(&mut exit_builder, tag, frame_ref);
+
+    let mut runs = entry_builder.build();
+    runs.extend(body);
+    runs.extend(exit_builder.build());
+    runs
+}
+
+fn generate_interrupt_return(run_builder: &mut RunBuilder, tag: SrcTag, frame_ref: SymbolRef) {
+    let saved = interrupt_saved_locations(&*run_builder.symbol_table().read().unwrap());
+    run_builder
+        .current_block()
+        .add_statement(Statement::AddToDataStackPointer(
+            AddToDataStackPointerData::new(tag, SPOffset::NegativeInterruptFrameSize(frame_ref)),
+        ))
+        .add_statement(Statement::ReturnFromInterrupt(InterruptData::new(tag, saved)));
+}
+
+/// Scratch memory outside of the data stack that the interrupted code could be in the middle of using
+fn interrupt_saved_locations(symbol_table: &SymbolTable) -> Vec<Location> {
+    let mut saved = vec![RETURN_LOCATION_LO, RETURN_LOCATION_LO.offset_by(1)];
+    let zero_page_pointer = symbol_table.variable_by_name(&Arc::new(ZERO_PAGE_POINTER_NAME.into()));
+    if let Some(symbol_table::Variable {
+        location: symbol_table::Location::Global(pointer),
+        ..
+    }) = zero_page_pointer
+    {
+        saved.push(Location::Global(pointer));
+        saved.push(Location::Global(pointer + 1));
+    }
+    saved
+}
+
+fn calculate_frame_size(name: &SymbolName, symbol_table: &SymbolTable) -> error::Result<i8> {
     let mut size = 0;
     for variable in symbol_table.variables() {
-        size += variable.base_type.size().unwrap() as i8;
+        if let symbol_table::Location::FrameOffset(_) = variable.location {
+            size += variable.base_type.size().unwrap();
+        }
     }
-    size
+    // Temporaries can push a frame past the limit even when the locals alone fit
+    if size > MAX_FRAME_SIZE {
+        return Err(error::ErrorKind::OutOfFrame(SymbolName::clone(name), size).into());
+    }
+    Ok(size as i8)
+}
+
+/// The blocks that `continue` and `break` jump to from inside of a loop
+#[derive(Copy, Clone, new)]
+struct LoopTargets {
+    continue_symbol: SymbolRef,
+    break_symbol: SymbolRef,
 }
 
 fn generate_runs(
     symbol_table: Arc<RwLock<SymbolTable>>,
     frame_ref: SymbolRef,
     input: &[ir::Statement],
+    loop_targets: Option<LoopTargets>,
 ) -> error::Result<Vec<RunBlock>> {
     let mut run_builder = RunBuilder::new(Arc::clone(&symbol_table));
     for irstmt in input {
@@ -75,42 +216,58 @@ fn generate_runs(
                     left_location,
                 )?;
             }
+            ir::Statement::Break(ref data) => {
+                let break_symbol = loop_targets.unwrap().break_symbol;
+                run_builder
+                    .current_block()
+                    .add_statement(Statement::GoTo(GoToData::new(data.tag, break_symbol)));
+            }
             ir::Statement::Call(ref data) => {
                 generate_function_call(&mut run_builder, frame_ref, data)?;
             }
+            ir::Statement::CompoundAssign(ref data) => {
+                generate_compound_assign(&mut run_builder, frame_ref, data)?;
+            }
             ir::Statement::Conditional(ref data) => {
-                let _start_condition_block_ref = run_builder.new_block().block_ref;
-                let condition = resolve_expr_to_value(&mut run_builder, frame_ref, &data.condition)?;
-                let end_condition_block_ref = run_builder.current_block().block_ref;
+                let true_block_symbol = run_builder.reserve_block();
+                let false_block_symbol = run_builder.reserve_block();
+                let after_both_block_symbol = run_builder.reserve_block();
+
+                // Relative branches can't reach very far, so only branch over a jump to the false block
+                run_builder.new_block();
+                generate_branch(&mut run_builder, frame_ref, &data.condition, true, true_block_symbol)?;
+                run_builder
+                    .current_block()
+                    .add_statement(Statement::GoTo(GoToData::new(data.tag, false_block_symbol)));
 
-                let true_block_ref = run_builder.append_blocks(generate_runs(
+                run_builder.new_reserved_block(true_block_symbol);
+                run_builder.append_blocks(generate_runs(
                     Arc::clone(&symbol_table),
                     frame_ref,
                     &data.when_true,
+                    loop_targets,
                 )?);
-                let false_block_ref = run_builder.append_blocks(generate_runs(
-                    Arc::clone(&symbol_table),
-                    frame_ref,
-                    &data.when_false,
-                )?);
-                let after_both_block_ref = run_builder.new_block().symbol();
-
                 run_builder
-                    .block(true_block_ref)
+                    .current_block()
                     .add_statement(Statement::GoTo(GoToData::new(
                         data.tag,
-                        after_both_block_ref,
+                        after_both_block_symbol,
                     )));
 
-                let false_block_symbol = run_builder.block(false_block_ref).symbol();
+                run_builder.new_reserved_block(false_block_symbol);
+                run_builder.append_blocks(generate_runs(
+                    Arc::clone(&symbol_table),
+                    frame_ref,
+                    &data.when_false,
+                    loop_targets,
+                )?);
+                run_builder.new_reserved_block(after_both_block_symbol);
+            }
+            ir::Statement::Continue(ref data) => {
+                let continue_symbol = loop_targets.unwrap().continue_symbol;
                 run_builder
-                    .block(end_condition_block_ref)
-                    .add_statement(Statement::BranchIfZero(BranchIfZeroData::new(
-                        data.tag,
-                        condition,
-                        false_block_symbol,
-                    )));
-                run_builder.new_block();
+                    .current_block()
+                    .add_statement(Statement::GoTo(GoToData::new(data.tag, continue_symbol)));
             }
             ir::Statement::InlineAsm(ref data) => {
                 run_builder
@@ -120,34 +277,89 @@ fn generate_runs(
                         Arc::clone(&data.asm),
                     )));
             }
-            ir::Statement::WhileLoop(ref data) => {
-                let start_condition_block_ref = run_builder.new_block().block_ref;
-                let start_condition_block_symbol = run_builder.block(start_condition_block_ref).symbol();
-                let condition = resolve_expr_to_value(&mut run_builder, frame_ref, &data.condition)?;
-                let end_condition_block_ref = run_builder.current_block().block_ref;
-                let body_block_ref = run_builder.append_blocks(generate_runs(
+            ir::Statement::Intrinsic(ref data) => {
+                generate_intrinsic(&mut run_builder, frame_ref, data)?;
+            }
+            ir::Statement::Poke(ref data) => {
+                let value = resolve_expr_to_value(&mut run_builder, frame_ref, &data.value)?;
+                let address = generate_byte_address(&mut run_builder, frame_ref, data.tag, &data.address)?;
+                run_builder
+                    .current_block()
+                    .add_statement(Statement::Poke(PokeData::new(data.tag, address, value)));
+            }
+            ir::Statement::Match(ref data) => {
+                let case_block_symbols: Vec<SymbolRef> =
+                    data.cases.iter().map(|_| run_builder.reserve_block()).collect();
+                let default_block_symbol = run_builder.reserve_block();
+                let after_match_block_symbol = run_builder.reserve_block();
+
+                run_builder.new_block();
+                if use_jump_table(data) {
+                    generate_jump_table(
+                        &mut run_builder,
+                        frame_ref,
+                        data,
+                        &case_block_symbols,
+                        default_block_symbol,
+                    )?;
+                } else {
+                    generate_compare_chain(
+                        &mut run_builder,
+                        frame_ref,
+                        data,
+                        &case_block_symbols,
+                        default_block_symbol,
+                    )?;
+                }
+
+                for (case, case_block_symbol) in data.cases.iter().zip(case_block_symbols.iter()) {
+                    run_builder.new_reserved_block(*case_block_symbol);
+                    run_builder.append_blocks(generate_runs(
+                        Arc::clone(&symbol_table),
+                        frame_ref,
+                        &case.body,
+                        loop_targets,
+                    )?);
+                    run_builder
+                        .current_block()
+                        .add_statement(Statement::GoTo(GoToData::new(case.tag, after_match_block_symbol)));
+                }
+
+                run_builder.new_reserved_block(default_block_symbol);
+                run_builder.append_blocks(generate_runs(
                     Arc::clone(&symbol_table),
                     frame_ref,
-                    &data.body,
+                    &data.default,
+                    loop_targets,
                 )?);
-                let after_body_block_symbol = run_builder.new_block().symbol();
+                run_builder.new_reserved_block(after_match_block_symbol);
+            }
+            ir::Statement::WhileLoop(ref data) => {
+                let start_condition_block_symbol = run_builder.new_block().symbol();
+                let body_block_symbol = run_builder.reserve_block();
+                let after_body_block_symbol = run_builder.reserve_block();
+                generate_branch(&mut run_builder, frame_ref, &data.condition, true, body_block_symbol)?;
+                run_builder
+                    .current_block()
+                    .add_statement(Statement::GoTo(GoToData::new(data.tag, after_body_block_symbol)));
 
-                {
-                    let mut end_condition_block = run_builder.block(end_condition_block_ref);
-                    end_condition_block.add_statement(Statement::BranchIfZero(BranchIfZeroData::new(
-                        data.tag,
-                        condition,
+                run_builder.new_reserved_block(body_block_symbol);
+                run_builder.append_blocks(generate_runs(
+                    Arc::clone(&symbol_table),
+                    frame_ref,
+                    &data.body,
+                    Some(LoopTargets::new(
+                        start_condition_block_symbol,
                         after_body_block_symbol,
-                    )));
-                }
-
+                    )),
+                )?);
                 run_builder
-                    .block(body_block_ref)
+                    .current_block()
                     .add_statement(Statement::GoTo(GoToData::new(
                         data.tag,
                         start_condition_block_symbol,
                     )));
-                run_builder.new_block();
+                run_builder.new_reserved_block(after_body_block_symbol);
             }
             ir::Statement::Return(ref data) => {
                 if let Some(ref expr) = data.value {
@@ -160,9 +372,18 @@ fn generate_runs(
                         RETURN_LOCATION_LO,
                     )?;
                 }
-                run_builder
-                    .current_block()
-                    .add_statement(Statement::Return(ReturnData::new(data.tag)));
+                let interrupt = symbol_table
+                    .read()
+                    .unwrap()
+                    .function(frame_ref)
+                    .map_or(false, |function| function.read().unwrap().interrupt);
+                if interrupt {
+                    generate_interrupt_return(&mut run_builder, data.tag, frame_ref);
+                } else {
+                    run_builder
+                        .current_block()
+                        .add_statement(Statement::Return(ReturnData::new(data.tag)));
+                }
             }
             ir::Statement::GoTo(ref data) => {
                 if let Some(symbol_ref) = symbol_table.read().unwrap().find_symbol(&data.destination) {
@@ -173,7 +394,6 @@ fn generate_runs(
                     return Err(error::ErrorKind::SymbolNotFound(data.tag, SymbolName::clone(&data.destination)).into());
                 }
             }
-            _ => unimplemented!("llir_gen: generate_runs statement"),
         }
     }
 
@@ -187,12 +407,22 @@ fn generate_copy(
     value: Value,
     destination: Location,
 ) -> error::Result<()> {
-    let mut block = run_builder.current_block();
     match *value_type {
-        BaseType::Bool | BaseType::U8 => {
-            block.add_statement(Statement::Copy(CopyData::new(tag, destination, value)));
+        BaseType::Bool | BaseType::I8 | BaseType::U8 => {
+            run_builder
+                .current_block()
+                .add_statement(Statement::Copy(CopyData::new(tag, destination, value)));
         }
-        BaseType::U16 | BaseType::Pointer(_) => {
+        BaseType::I16 | BaseType::U16 | BaseType::Pointer(_) | BaseType::Function(_, _)
+            if value.value_type().size() == Some(1) =>
+        {
+            binop::generate_extension(run_builder, tag, destination.high_byte(), &value);
+            run_builder
+                .current_block()
+                .add_statement(Statement::Copy(CopyData::new(tag, destination.low_byte(), value)));
+        }
+        BaseType::I16 | BaseType::U16 | BaseType::Pointer(_) | BaseType::Function(_, _) => {
+            let mut block = run_builder.current_block();
             block.add_statement(Statement::Copy(CopyData::new(
                 tag,
                 destination.high_byte(),
@@ -204,124 +434,613 @@ fn generate_copy(
                 Value::low_byte(&value),
             )));
         }
-        BaseType::Void => unreachable!(),
+        BaseType::Struct(ref struct_type) => {
+            let source = match value {
+                Value::Memory(ref data) => data.location.clone(),
+                Value::Immediate(_, _) => unreachable!(),
+            };
+            let mut block = run_builder.current_block();
+            for index in 0..struct_type.size() {
+                block.add_statement(Statement::Copy(CopyData::new(
+                    tag,
+                    destination.offset_by(index),
+                    Value::Memory(MemoryData::new(BaseType::U8, source.offset_by(index), None)),
+                )));
+            }
+        }
+        BaseType::Enum(ref enum_type) => generate_copy(run_builder, tag, &enum_type.base_type, value, destination)?,
+        BaseType::Array(_, _) | BaseType::Named(_) | BaseType::Void => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Matches with at least this many values may be compiled into a jump table
+const JUMP_TABLE_MIN_VALUES: usize = 4;
+
+/// Jump tables are used when at least half of the table entries are for one of the cases
+fn use_jump_table(data: &ir::MatchData) -> bool {
+    let values: Vec<u8> = data.cases
+        .iter()
+        .flat_map(|case| case.values.iter().map(|case_value| case_value.value))
+        .collect();
+    if values.len() < JUMP_TABLE_MIN_VALUES {
+        return false;
+    }
+    let min = *values.iter().min().unwrap() as usize;
+    let max = *values.iter().max().unwrap() as usize;
+    max - min + 1 <= 2 * values.len()
+}
+
+/// Copies the matched value into a temporary so that it's only evaluated once,
+/// subtracting `offset` from it along the way
+fn generate_match_value(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    data: &ir::MatchData,
+    offset: u8,
+) -> error::Result<Value> {
+    let value = resolve_expr_to_value(run_builder, frame_ref, &data.value)?;
+    let temp = convert_location(
+        frame_ref,
+        &run_builder
+            .symbol_table()
+            .write()
+            .unwrap()
+            .create_temporary_location(&BaseType::U8),
+    );
+    let statement = if offset == 0 {
+        Statement::Copy(CopyData::new(data.tag, temp.clone(), value))
+    } else {
+        Statement::Subtract(BinaryOpData::new(
+            data.tag,
+            temp.clone(),
+            value,
+            Value::Immediate(BaseType::U8, ImmediateValue::Number(offset as i32)),
+            CarryMode::SetCarry,
+        ))
+    };
+    run_builder.current_block().add_statement(statement);
+    Ok(Value::Memory(MemoryData::new(BaseType::U8, temp, None)))
+}
+
+/// Compares the value against each case in turn. Relative branches can't reach very far,
+/// so each comparison only branches over the jump to its case.
+fn generate_compare_chain(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    data: &ir::MatchData,
+    case_block_symbols: &[SymbolRef],
+    default_block_symbol: SymbolRef,
+) -> error::Result<()> {
+    let value = generate_match_value(run_builder, frame_ref, data, 0)?;
+    for (case, case_block_symbol) in data.cases.iter().zip(case_block_symbols.iter()) {
+        for case_value in &case.values {
+            let next_compare_symbol = run_builder.reserve_block();
+            run_builder
+                .current_block()
+                .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                    case.tag,
+                    value.clone(),
+                    Value::Immediate(BaseType::U8, ImmediateValue::Number(case_value.value as i32)),
+                    BranchFlag::Zero,
+                    None,
+                    Some(next_compare_symbol),
+                )))
+                .add_statement(Statement::GoTo(GoToData::new(case.tag, *case_block_symbol)));
+            run_builder.new_reserved_block(next_compare_symbol);
+        }
+    }
+    run_builder
+        .current_block()
+        .add_statement(Statement::GoTo(GoToData::new(data.tag, default_block_symbol)));
+    Ok(())
+}
+
+/// Looks up the case to jump to in a table of addresses indexed by the value. Values
+/// outside of the table's range, or that fall in a gap between cases, go to the default.
+fn generate_jump_table(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    data: &ir::MatchData,
+    case_block_symbols: &[SymbolRef],
+    default_block_symbol: SymbolRef,
+) -> error::Result<()> {
+    let values = data.cases.iter().flat_map(|case| case.values.iter().map(|case_value| case_value.value));
+    let min = values.clone().min().unwrap();
+    let max = values.max().unwrap();
+
+    let mut addresses = vec![default_block_symbol; (max - min) as usize + 1];
+    for (case, case_block_symbol) in data.cases.iter().zip(case_block_symbols.iter()) {
+        for case_value in &case.values {
+            addresses[(case_value.value - min) as usize] = *case_block_symbol;
+        }
+    }
+
+    let index = generate_match_value(run_builder, frame_ref, data, min)?;
+    let low_table_symbol = run_builder.reserve_block();
+    let high_table_symbol = run_builder.reserve_block();
+    // A table with an entry for every byte value doesn't need a bounds check
+    if addresses.len() <= 0xFF {
+        let jump_block_symbol = run_builder.reserve_block();
+        run_builder
+            .current_block()
+            .add_statement(Statement::CompareBranch(CompareBranchData::new(
+                data.tag,
+                index.clone(),
+                Value::Immediate(BaseType::U8, ImmediateValue::Number(addresses.len() as i32)),
+                BranchFlag::Carry,
+                None,
+                Some(jump_block_symbol),
+            )))
+            .add_statement(Statement::GoTo(GoToData::new(data.tag, default_block_symbol)));
+        run_builder.new_reserved_block(jump_block_symbol);
+    }
+
+    // The return location is free to use as the vector since nothing is being returned
+    run_builder
+        .current_block()
+        .add_statement(Statement::JumpIndirect(JumpIndirectData::new(
+            data.tag,
+            index,
+            low_table_symbol,
+            high_table_symbol,
+            RETURN_LOCATION_LO,
+        )));
+    run_builder
+        .new_reserved_block(low_table_symbol)
+        .add_statement(Statement::LowByteTable(AddressTableData::new(
+            data.tag,
+            addresses.clone(),
+        )));
+    run_builder
+        .new_reserved_block(high_table_symbol)
+        .add_statement(Statement::HighByteTable(AddressTableData::new(data.tag, addresses)));
+    Ok(())
+}
+
+/// Reads, modifies and writes back the left value of a compound assignment. The left value is
+/// evaluated once, after the right value, and is both the left operand and the destination.
+fn generate_compound_assign(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    data: &ir::CompoundAssignData,
+) -> error::Result<()> {
+    let value_type = data.value_type.as_ref().unwrap();
+    let right_value = resolve_expr_to_value(run_builder, frame_ref, &data.right_value)?;
+    let location = resolve_expr_to_location(run_builder, frame_ref, &data.left_value)?;
+    if generate_step(run_builder, data, &location, &right_value) {
+        return Ok(());
+    }
+
+    let left_value = Value::Memory(MemoryData::new(value_type.clone(), location.clone(), None));
+    match data.op {
+        // These build up their result in the destination while they still need the left operand
+        ast::BinaryOperator::Mul | ast::BinaryOperator::Div => {
+            let result = convert_location(
+                frame_ref,
+                &run_builder
+                    .symbol_table()
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(value_type),
+            );
+            generate_binop(
+                run_builder,
+                frame_ref,
+                data.tag,
+                data.op,
+                value_type,
+                &result,
+                &left_value,
+                &right_value,
+            )?;
+            let result_value = Value::Memory(MemoryData::new(value_type.clone(), result, None));
+            generate_copy(run_builder, data.tag, value_type, result_value, location)
+        }
+        _ => generate_binop(
+            run_builder,
+            frame_ref,
+            data.tag,
+            data.op,
+            value_type,
+            &location,
+            &left_value,
+            &right_value,
+        ),
+    }
+}
+
+/// Adding or subtracting 1 from a byte in memory that can be addressed directly is a single instruction.
+/// Returns false if the assignment can't be done as a step.
+fn generate_step(
+    run_builder: &mut RunBuilder,
+    data: &ir::CompoundAssignData,
+    location: &Location,
+    amount: &Value,
+) -> bool {
+    if data.value_type.as_ref().unwrap().size() != Some(1) {
+        return false;
+    }
+    match *amount {
+        Value::Immediate(_, ImmediateValue::Number(1)) => {}
+        _ => return false,
     }
+    match *location {
+        Location::Global(_) | Location::FrameOffset(_, _) | Location::UnresolvedGlobal(_) => {}
+        _ => return false,
+    }
+    let step_data = StepData::new(data.tag, location.clone());
+    let statement = match data.op {
+        ast::BinaryOperator::Add => Statement::Increment(step_data),
+        ast::BinaryOperator::Sub => Statement::Decrement(step_data),
+        _ => return false,
+    };
+    run_builder.current_block().add_statement(statement);
+    true
+}
+
+fn generate_intrinsic(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    data: &ir::IntrinsicData,
+) -> error::Result<()> {
+    let statement = match data.intrinsic {
+        ir::Intrinsic::Break => Statement::Break(InstructionData::new(data.tag)),
+        ir::Intrinsic::ClearCarry => Statement::ClearFlag(StatusFlagData::new(data.tag, StatusFlag::Carry)),
+        ir::Intrinsic::ClearDecimal => Statement::ClearFlag(StatusFlagData::new(data.tag, StatusFlag::Decimal)),
+        ir::Intrinsic::ClearInterruptDisable => {
+            Statement::ClearFlag(StatusFlagData::new(data.tag, StatusFlag::InterruptDisable))
+        }
+        ir::Intrinsic::NoOp => Statement::NoOp(InstructionData::new(data.tag)),
+        ir::Intrinsic::SetCarry => Statement::SetFlag(StatusFlagData::new(data.tag, StatusFlag::Carry)),
+        ir::Intrinsic::SetDecimal => Statement::SetFlag(StatusFlagData::new(data.tag, StatusFlag::Decimal)),
+        ir::Intrinsic::SetInterruptDisable => {
+            Statement::SetFlag(StatusFlagData::new(data.tag, StatusFlag::InterruptDisable))
+        }
+        ir::Intrinsic::RotateLeft | ir::Intrinsic::RotateRight => {
+            let value = data.value.as_ref().unwrap();
+            let location = resolve_expr_to_location(run_builder, frame_ref, value)?;
+            let mut bytes = vec![location.clone()];
+            if value.base_type().unwrap().size() == Some(2) {
+                bytes.push(location.high_byte());
+            }
+            // The carry flag moves from byte to byte, so left rotates start at the low byte, and right at the high
+            if data.intrinsic == ir::Intrinsic::RotateRight {
+                bytes.reverse();
+            }
+            for byte in bytes {
+                let step_data = StepData::new(data.tag, byte);
+                run_builder
+                    .current_block()
+                    .add_statement(if data.intrinsic == ir::Intrinsic::RotateLeft {
+                        Statement::RotateLeft(step_data)
+                    } else {
+                        Statement::RotateRight(step_data)
+                    });
+            }
+            return Ok(());
+        }
+    };
+    run_builder.current_block().add_statement(statement);
     Ok(())
 }
 
+/// Stores the address of a slot in the frame into a pointer. The
+/// data stack lives in zero page, so the high byte is always zero.
+fn generate_frame_address(
+    run_builder: &mut RunBuilder,
+    tag: SrcTag,
+    frame_ref: SymbolRef,
+    offset: i8,
+    destination: &Location,
+) {
+    run_builder
+        .current_block()
+        .add_statement(Statement::Add(BinaryOpData::new(
+            tag,
+            destination.low_byte(),
+            Value::Memory(MemoryData::new(
+                BaseType::U8,
+                Location::Global(DATA_STACK_POINTER_LOCATION),
+                None,
+            )),
+            Value::Immediate(BaseType::U8, ImmediateValue::FrameOffset(frame_ref, offset)),
+            CarryMode::ClearCarry,
+        )))
+        .add_statement(Statement::Copy(CopyData::new(
+            tag,
+            destination.high_byte(),
+            Value::Immediate(BaseType::U8, ImmediateValue::Number(0)),
+        )));
+}
+
+/// Generates branches that jump to `target` when the given condition evaluates to `branch_when`,
+/// and otherwise fall through. Logical operators short-circuit.
+fn generate_branch(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    condition: &ir::Expr,
+    branch_when: bool,
+    target: SymbolRef,
+) -> error::Result<()> {
+    use parse::ast::BinaryOperator::*;
+    if let ir::Expr::UnaryOp(ref data) = *condition {
+        if data.op == ast::UnaryOperator::LogicalNot {
+            return generate_branch(run_builder, frame_ref, &*data.value, !branch_when, target);
+        }
+    }
+    if let ir::Expr::BinaryOp(ref data) = *condition {
+        match data.op {
+            LogicalAnd | LogicalOr => {
+                // When the left side alone decides the outcome, the right side can be skipped
+                let decided_by_left = data.op == LogicalOr;
+                if decided_by_left == branch_when {
+                    generate_branch(run_builder, frame_ref, &data.left, branch_when, target)?;
+                    generate_branch(run_builder, frame_ref, &data.right, branch_when, target)?;
+                } else {
+                    let skip_block_symbol = run_builder.reserve_block();
+                    generate_branch(run_builder, frame_ref, &data.left, decided_by_left, skip_block_symbol)?;
+                    generate_branch(run_builder, frame_ref, &data.right, branch_when, target)?;
+                    run_builder.new_reserved_block(skip_block_symbol);
+                }
+                return Ok(());
+            }
+            LessThan | GreaterThan | LessThanEqual | GreaterThanEqual | Equal | NotEqual => {
+                let left_value = resolve_expr_to_value(run_builder, frame_ref, &*data.left)?;
+                let right_value = resolve_expr_to_value(run_builder, frame_ref, &*data.right)?;
+                return binop::compare::generate_compare_branch(
+                    run_builder,
+                    frame_ref,
+                    data.tag,
+                    if branch_when { data.op.inverse() } else { data.op },
+                    &left_value,
+                    &right_value,
+                    target,
+                );
+            }
+            Add | Sub | Mul | Div | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => {}
+        }
+    }
+
+    // Anything else is true when it's non-zero
+    let value = resolve_expr_to_value(run_builder, frame_ref, condition)?;
+    let zero = Value::Immediate(value.value_type(), ImmediateValue::Number(0));
+    binop::compare::generate_compare_branch(
+        run_builder,
+        frame_ref,
+        condition.src_tag(),
+        if branch_when { Equal } else { NotEqual },
+        &value,
+        &zero,
+        target,
+    )
+}
+
 fn resolve_expr_to_location(
     run_builder: &mut RunBuilder,
     frame_ref: SymbolRef,
     expr: &ir::Expr,
 ) -> error::Result<Location> {
-    match resolve_expr_to_value(run_builder, frame_ref, expr)? {
+    match resolve_expr_to_place(run_builder, frame_ref, expr)? {
         Value::Immediate(_, _) => Err(error::ErrorKind::InvalidLeftValue(expr.src_tag()).into()),
+        // Constant tables are in ROM
+        Value::Memory(MemoryData {
+            location: Location::UnresolvedGlobalIndexed(_, _),
+            ..
+        }) => Err(error::ErrorKind::InvalidLeftValue(expr.src_tag()).into()),
         Value::Memory(data) => Ok(data.location),
     }
 }
 
+/// Resolves an expression to a value that stays valid while other expressions are evaluated. Anything
+/// read through the zero page pointer is copied out, since the next pointer access will repoint it.
 fn resolve_expr_to_value(run_builder: &mut RunBuilder, frame_ref: SymbolRef, expr: &ir::Expr) -> error::Result<Value> {
+    let value = resolve_expr_to_place(run_builder, frame_ref, expr)?;
+    if let Value::Memory(ref data) = value {
+        if let Location::ZeroPageIndirect(_, _) = data.location {
+            let dest = convert_location(
+                frame_ref,
+                &run_builder
+                    .symbol_table()
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(&data.base_type),
+            );
+            generate_copy(run_builder, expr.src_tag(), &data.base_type, value.clone(), dest.clone())?;
+            return Ok(Value::Memory(MemoryData::new(data.base_type.clone(), dest, None)));
+        }
+    }
+    Ok(value)
+}
+
+/// Resolves an expression to the value or memory it refers to
+fn resolve_expr_to_place(run_builder: &mut RunBuilder, frame_ref: SymbolRef, expr: &ir::Expr) -> error::Result<Value> {
     let symbol_table = Arc::clone(run_builder.symbol_table());
     match *expr {
         ir::Expr::ArrayIndex(ref data) => {
-            let array = symbol_table.write().unwrap().variable(data.array).unwrap();
             let array_name = symbol_table
                 .write()
                 .unwrap()
                 .get_symbol_name(data.array)
                 .unwrap();
+            let array_type = data.array_type.as_ref().unwrap();
+            let element_type = array_type.underlying_type().unwrap().clone();
+            let element_size = element_type.size().unwrap();
             let index_value = resolve_expr_to_value(run_builder, frame_ref, &data.index)?;
-            match array.location {
-                symbol_table::Location::UndeterminedGlobal => unreachable!(),
-                symbol_table::Location::Global(addr) => Ok(Value::Memory(MemoryData::new(
-                    data.array_type
-                        .as_ref()
-                        .unwrap()
-                        .underlying_type()
+            let constant = symbol_table.read().unwrap().constant(data.array);
+            if constant.as_ref().map_or(false, |constant| constant.value.is_table()) {
+                if element_size == 1 {
+                    // Constant tables are emitted with the program, so they're addressed by their label
+                    return Ok(Value::Memory(MemoryData::new(
+                        element_type,
+                        Location::UnresolvedGlobalIndexed(data.array, Box::new(index_value)),
+                        Some(Arc::new(format!("{}[]", array_name))),
+                    )));
+                }
+                let addr = convert_location(
+                    frame_ref,
+                    &symbol_table
+                        .write()
                         .unwrap()
-                        .clone(),
-                    Location::GlobalIndexed(addr, Box::new(index_value)),
-                    Some(Arc::new(format!("{}[]", array_name))),
-                ))),
-                symbol_table::Location::FrameOffset(_) => {
-                    // Copy the pointer address to a new temporary
-                    let addr = convert_location(
-                        frame_ref,
-                        &symbol_table
-                            .write()
-                            .unwrap()
-                            .create_temporary_location(data.array_type.as_ref().unwrap()),
-                    );
-                    generate_copy(
+                        .create_temporary_location(&BaseType::U16),
+                );
+                generate_copy(
+                    run_builder,
+                    data.tag,
+                    array_type,
+                    Value::Immediate(array_type.clone(), ImmediateValue::Symbol(data.array)),
+                    addr.clone(),
+                )?;
+                let offset = scale_index(
+                    run_builder,
+                    frame_ref,
+                    data.tag,
+                    &index_value,
+                    element_size,
+                    &BaseType::U16,
+                )?;
+                let location = generate_byte_pointers(
+                    run_builder,
+                    frame_ref,
+                    data.tag,
+                    &Value::Memory(MemoryData::new(BaseType::U16, addr, None)),
+                    &offset,
+                    element_size,
+                )?;
+                return Ok(Value::Memory(MemoryData::new(
+                    element_type,
+                    location,
+                    Some(Arc::new(format!("indexed:{}", array_name))),
+                )));
+            }
+            let array_location = match constant {
+                // Arrays in memory are at a fixed address
+                Some(ref constant) => symbol_table::Location::Global(constant.value.number() as u16),
+                None => symbol_table.write().unwrap().variable(data.array).unwrap().location,
+            };
+            match array_location {
+                symbol_table::Location::UndeterminedGlobal | symbol_table::Location::UndeterminedZeroPage => {
+                    unreachable!()
+                }
+                symbol_table::Location::Global(addr) if constant.is_some() || array_type.is_array() => {
+                    // The Y register holds the scaled index, so only the first 256 bytes can be reached
+                    let offset = scale_index(
                         run_builder,
+                        frame_ref,
                         data.tag,
-                        data.array_type.as_ref().unwrap(),
+                        &index_value,
+                        element_size,
+                        &BaseType::U8,
+                    )?;
+                    Ok(Value::Memory(MemoryData::new(
+                        element_type,
+                        Location::GlobalIndexed(addr, Box::new(offset)),
+                        Some(Arc::new(format!("{}[]", array_name))),
+                    )))
+                }
+                // Pointer variables hold the address of the array, so they're indexed like any other pointer
+                _ => {
+                    let base = if let BaseType::Array(_, count) = *array_type {
+                        let offset = match array_location {
+                            symbol_table::Location::FrameOffset(offset) => offset,
+                            _ => unreachable!(),
+                        };
+                        // Constant indices into an array in the frame don't need a pointer
+                        if let Value::Immediate(_, ImmediateValue::Number(index)) = index_value {
+                            if index < 0 || index as usize >= count {
+                                return Err(error::ErrorKind::OutOfBounds(
+                                    data.index.src_tag(),
+                                    index as isize,
+                                    0,
+                                    count as isize - 1,
+                                ).into());
+                            }
+                            return Ok(Value::Memory(MemoryData::new(
+                                element_type,
+                                Location::FrameOffset(frame_ref, offset + (index as usize * element_size) as i8),
+                                Some(Arc::new(format!("{}[{}]", array_name, index))),
+                            )));
+                        }
+
+                        let addr = convert_location(
+                            frame_ref,
+                            &symbol_table
+                                .write()
+                                .unwrap()
+                                .create_temporary_location(&BaseType::U16),
+                        );
+                        generate_frame_address(run_builder, data.tag, frame_ref, offset, &addr);
+                        Value::Memory(MemoryData::new(BaseType::U16, addr, None))
+                    } else {
                         Value::Memory(MemoryData::new(
-                            data.array_type.as_ref().unwrap().clone(),
-                            convert_location(frame_ref, &array.location),
+                            BaseType::U16,
+                            convert_location(frame_ref, &array_location),
                             Some(Arc::new(format!("{}[]", array_name))),
-                        )),
-                        addr.clone(),
-                    )?;
+                        ))
+                    };
 
-                    // Add the index to the pointer temporary
-                    binop::BinopGenerator::new(
+                    let offset = scale_index(
                         run_builder,
                         frame_ref,
                         data.tag,
-                        &BaseType::U16,
-                        &addr,
-                        &Value::Memory(MemoryData::new(
-                            BaseType::U16,
-                            addr.clone(),
-                            Some(Arc::new(format!("tmp_{}[]", array_name))),
-                        )),
                         &index_value,
-                    ).generate(ast::BinaryOperator::Add)?;
-
+                        element_size,
+                        &BaseType::U16,
+                    )?;
+                    let location =
+                        generate_byte_pointers(run_builder, frame_ref, data.tag, &base, &offset, element_size)?;
                     Ok(Value::Memory(MemoryData::new(
-                        data.array_type
-                            .as_ref()
-                            .unwrap()
-                            .underlying_type()
-                            .unwrap()
-                            .clone(),
-                        match addr {
-                            Location::FrameOffset(sym_ref, offset) => Location::FrameOffsetIndirect(sym_ref, offset),
-                            _ => unreachable!(),
-                        },
+                        element_type,
+                        location,
                         Some(Arc::new(format!("indexed:{}", array_name))),
                     )))
                 }
             }
         }
-        ir::Expr::Number(ref data) => Ok(Value::Immediate(
-            data.value_type.as_ref().unwrap().clone(),
-            ImmediateValue::Number(data.value),
-        )),
+        ir::Expr::Number(ref data) => {
+            let value_type = match *data.value_type.as_ref().unwrap() {
+                // Function pointers are plain addresses once they're in memory
+                BaseType::Function(_, _) => BaseType::U16,
+                ref value_type => value_type.clone(),
+            };
+            Ok(Value::Immediate(value_type, ImmediateValue::Number(data.value)))
+        }
         ir::Expr::Symbol(ref data) => {
-            if let Some(ref variable) = symbol_table.read().unwrap().variable(data.symbol) {
+            let optional_variable = symbol_table.read().unwrap().variable(data.symbol);
+            if let Some(ref variable) = optional_variable {
                 match variable.base_type {
-                    BaseType::Pointer(_) => {
-                        match variable.location {
-                            // Return the address as a U16 when naming an array without an index
-                            symbol_table::Location::Global(addr) => Ok(Value::Immediate(
-                                BaseType::U16,
-                                ImmediateValue::Number(addr as i32),
-                            )),
-                            _ => Ok(Value::Memory(MemoryData::new(
-                                BaseType::U16,
-                                convert_location(frame_ref, &variable.location),
-                                Some(
-                                    symbol_table
-                                        .read()
-                                        .unwrap()
-                                        .get_symbol_name(data.symbol)
-                                        .unwrap(),
-                                ),
-                            ))),
+                    // Arrays decay into a pointer to their first element
+                    BaseType::Array(_, _) => match variable.location {
+                        symbol_table::Location::Global(addr) => Ok(Value::Immediate(
+                            BaseType::U16,
+                            ImmediateValue::Number(addr as i32),
+                        )),
+                        symbol_table::Location::FrameOffset(offset) => {
+                            let addr = convert_location(
+                                frame_ref,
+                                &symbol_table
+                                    .write()
+                                    .unwrap()
+                                    .create_temporary_location(&BaseType::U16),
+                            );
+                            generate_frame_address(run_builder, data.tag, frame_ref, offset, &addr);
+                            Ok(Value::Memory(MemoryData::new(BaseType::U16, addr, None)))
                         }
-                    }
+                        symbol_table::Location::UndeterminedGlobal | symbol_table::Location::UndeterminedZeroPage => {
+                            unreachable!()
+                        }
+                    },
+                    BaseType::Pointer(_) | BaseType::Function(_, _) => Ok(Value::Memory(MemoryData::new(
+                        BaseType::U16,
+                        convert_location(frame_ref, &variable.location),
+                        Some(
+                            symbol_table
+                                .read()
+                                .unwrap()
+                                .get_symbol_name(data.symbol)
+                                .unwrap(),
+                        ),
+                    ))),
                     _ => Ok(Value::Memory(MemoryData::new(
                         variable.base_type.clone(),
                         convert_location(frame_ref, &variable.location),
@@ -335,21 +1054,60 @@ fn resolve_expr_to_value(run_builder: &mut RunBuilder, frame_ref: SymbolRef, exp
                     ))),
                 }
             } else if let Some(ref constant) = symbol_table.read().unwrap().constant(data.symbol) {
-                if constant.base_type.is_pointer() {
-                    Ok(Value::Immediate(
+                match constant.value {
+                    // Return the address as a U16 when naming an array in memory without an index
+                    ConstantValue::Number(addr) if constant.base_type.is_pointer() => {
+                        Ok(Value::Immediate(BaseType::U16, ImmediateValue::Number(addr)))
+                    }
+                    ConstantValue::Number(number) => Ok(Value::Immediate(
                         data.value_type.as_ref().unwrap().clone(),
-                        ImmediateValue::Symbol(data.symbol),
-                    ))
-                } else {
-                    Ok(Value::Immediate(
+                        ImmediateValue::Number(number),
+                    )),
+                    ConstantValue::Bytes(_) | ConstantValue::Functions(_) => Ok(Value::Immediate(
                         data.value_type.as_ref().unwrap().clone(),
-                        ImmediateValue::Number(constant.value.number()),
-                    ))
+                        ImmediateValue::Symbol(data.symbol),
+                    )),
                 }
+            } else if let Some(function) = symbol_table.read().unwrap().function(data.symbol) {
+                // Functions are called through a pointer at their indirect entry point
+                let indirect_entry = function.read().unwrap().indirect_entry.unwrap();
+                Ok(Value::Immediate(BaseType::U16, ImmediateValue::Symbol(indirect_entry)))
             } else {
                 unreachable!()
             }
         }
+        ir::Expr::BinaryOp(ref data) if data.op.is_logical() => {
+            let dest = convert_location(
+                frame_ref,
+                &symbol_table
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(&BaseType::Bool),
+            );
+            let false_block_symbol = run_builder.reserve_block();
+            let after_block_symbol = run_builder.reserve_block();
+
+            run_builder.new_block();
+            generate_branch(run_builder, frame_ref, expr, false, false_block_symbol)?;
+            run_builder
+                .current_block()
+                .add_statement(Statement::Copy(CopyData::new(
+                    data.tag,
+                    dest.clone(),
+                    Value::Immediate(BaseType::Bool, ImmediateValue::Number(1)),
+                )))
+                .add_statement(Statement::GoTo(GoToData::new(data.tag, after_block_symbol)));
+            run_builder
+                .new_reserved_block(false_block_symbol)
+                .add_statement(Statement::Copy(CopyData::new(
+                    data.tag,
+                    dest.clone(),
+                    Value::Immediate(BaseType::Bool, ImmediateValue::Number(0)),
+                )));
+            run_builder.new_reserved_block(after_block_symbol);
+
+            Ok(Value::Memory(MemoryData::new(BaseType::Bool, dest, None)))
+        }
         ir::Expr::BinaryOp(ref data) => {
             let dest_type = data.result_type.as_ref().unwrap();
             let dest = convert_location(
@@ -362,20 +1120,522 @@ fn resolve_expr_to_value(run_builder: &mut RunBuilder, frame_ref: SymbolRef, exp
 
             let left_value = resolve_expr_to_value(run_builder, frame_ref, &*data.left)?;
             let right_value = resolve_expr_to_value(run_builder, frame_ref, &*data.right)?;
-
-            binop::BinopGenerator::new(
+            generate_binop(
                 run_builder,
                 frame_ref,
                 data.tag,
+                data.op,
                 dest_type,
                 &dest,
                 &left_value,
                 &right_value,
-            ).generate(data.op)?;
+            )?;
 
+            let value_type = if data.op.is_arithmetic() {
+                dest_type.clone()
+            } else {
+                BaseType::U8
+            };
+            Ok(Value::Memory(MemoryData::new(value_type, dest, None)))
+        }
+        ir::Expr::UnaryOp(ref data) if data.op.is_byte_select() => {
+            let value = resolve_expr_to_value(run_builder, frame_ref, &*data.value)?;
+            let value_type = value.value_type();
+            if value_type.is_array() || value_type.size() == Some(2) {
+                // Arrays decay into their address, which is always two bytes
+                let value = value.with_type(&BaseType::U16);
+                return Ok(if data.op == ast::UnaryOperator::LowByte {
+                    Value::low_byte(&value)
+                } else {
+                    Value::high_byte(&value)
+                });
+            }
+            if data.op == ast::UnaryOperator::LowByte {
+                return Ok(value.with_type(&BaseType::U8));
+            }
+            if !value_type.is_signed() {
+                return Ok(Value::Immediate(BaseType::U8, ImmediateValue::Number(0)));
+            }
+            // The high byte of a signed byte is its sign extension
+            let dest = convert_location(
+                frame_ref,
+                &symbol_table
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(&BaseType::U8),
+            );
+            binop::generate_extension(run_builder, data.tag, dest.clone(), &value);
             Ok(Value::Memory(MemoryData::new(BaseType::U8, dest, None)))
         }
+        ir::Expr::UnaryOp(ref data) => {
+            let dest_type = data.result_type.as_ref().unwrap();
+            let dest = convert_location(
+                frame_ref,
+                &symbol_table
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(dest_type),
+            );
+
+            let value = resolve_expr_to_value(run_builder, frame_ref, &*data.value)?;
+            match data.op {
+                ast::UnaryOperator::BitNot => {
+                    binop::bitwise::generate_bit_not(run_builder, data.tag, &dest, &value)
+                }
+                ast::UnaryOperator::LogicalNot => {
+                    // Only zero is false
+                    let value = if value.value_type().is_pointer() {
+                        value.with_type(&BaseType::U16)
+                    } else {
+                        value
+                    };
+                    binop::BinopGenerator::new(
+                        run_builder,
+                        frame_ref,
+                        data.tag,
+                        dest_type,
+                        &dest,
+                        &value,
+                        &Value::Immediate(value.value_type(), ImmediateValue::Number(0)),
+                    ).generate(ast::BinaryOperator::Equal)?
+                }
+                ast::UnaryOperator::Negate => binop::add::generate_negate(run_builder, data.tag, &dest, &value),
+                ast::UnaryOperator::HighByte | ast::UnaryOperator::LowByte => unreachable!(),
+            }
+            Ok(Value::Memory(MemoryData::new(dest_type.clone(), dest, None)))
+        }
         ir::Expr::Call(ref data) => generate_function_call(run_builder, frame_ref, data),
+        ir::Expr::Peek(ref data) => {
+            let address = generate_byte_address(run_builder, frame_ref, data.tag, &data.address)?;
+            let dest = convert_location(
+                frame_ref,
+                &symbol_table
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(&BaseType::U8),
+            );
+            run_builder
+                .current_block()
+                .add_statement(Statement::Peek(PeekData::new(data.tag, address, dest.clone())));
+            Ok(Value::Memory(MemoryData::new(BaseType::U8, dest, None)))
+        }
+        ir::Expr::Word(ref data) => {
+            let dest = convert_location(
+                frame_ref,
+                &symbol_table
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(&BaseType::U16),
+            );
+            let high = resolve_expr_to_value(run_builder, frame_ref, &*data.high)?;
+            let low = resolve_expr_to_value(run_builder, frame_ref, &*data.low)?;
+            run_builder
+                .current_block()
+                .add_statement(Statement::Copy(CopyData::new(data.tag, dest.low_byte(), low)))
+                .add_statement(Statement::Copy(CopyData::new(data.tag, dest.high_byte(), high)));
+            Ok(Value::Memory(MemoryData::new(BaseType::U16, dest, None)))
+        }
+        ir::Expr::Cast(ref data) => {
+            let value = resolve_expr_to_value(run_builder, frame_ref, &*data.value)?;
+            let value_type = value.value_type();
+            let value_size = value_type.size().unwrap();
+            let cast_type = &data.cast_type;
+            let cast_size = cast_type.size().unwrap();
+            if *cast_type == BaseType::Bool && value_type != BaseType::Bool {
+                // Anything non-zero is true
+                let value = if value_type.is_pointer() {
+                    value.with_type(&BaseType::U16)
+                } else {
+                    value
+                };
+                let dest = convert_location(
+                    frame_ref,
+                    &symbol_table
+                        .write()
+                        .unwrap()
+                        .create_temporary_location(cast_type),
+                );
+                binop::BinopGenerator::new(
+                    run_builder,
+                    frame_ref,
+                    data.tag,
+                    cast_type,
+                    &dest,
+                    &value,
+                    &Value::Immediate(value.value_type(), ImmediateValue::Number(0)),
+                ).generate(ast::BinaryOperator::NotEqual)?;
+                Ok(Value::Memory(MemoryData::new(cast_type.clone(), dest, None)))
+            } else if cast_size < value_size {
+                // Truncation keeps the low byte
+                Ok(Value::low_byte(&value).with_type(cast_type))
+            } else if cast_size > value_size {
+                let dest = convert_location(
+                    frame_ref,
+                    &symbol_table
+                        .write()
+                        .unwrap()
+                        .create_temporary_location(cast_type),
+                );
+                generate_copy(run_builder, data.tag, cast_type, value, dest.clone())?;
+                Ok(Value::Memory(MemoryData::new(cast_type.clone(), dest, None)))
+            } else {
+                Ok(value.with_type(cast_type))
+            }
+        }
+        ir::Expr::AddressOf(ref data) => {
+            let pointer_type = data.value_type.as_ref().unwrap();
+            // Arrays already decay into the address of their first element
+            if data.value.base_type().unwrap().is_array() {
+                return Ok(resolve_expr_to_value(run_builder, frame_ref, &data.value)?.with_type(pointer_type));
+            }
+            let location = match resolve_expr_to_place(run_builder, frame_ref, &data.value)? {
+                Value::Memory(memory) => memory.location,
+                Value::Immediate(_, _) => unreachable!(),
+            };
+            let address = match location {
+                Location::Global(addr) => {
+                    return Ok(Value::Immediate(
+                        pointer_type.clone(),
+                        ImmediateValue::Number(addr as i32),
+                    ))
+                }
+                // The first byte pointer already holds the address
+                Location::FrameOffsetIndirect(sym_ref, ref pointers) => Location::FrameOffset(sym_ref, pointers[0]),
+                location => {
+                    let address = convert_location(
+                        frame_ref,
+                        &symbol_table
+                            .write()
+                            .unwrap()
+                            .create_temporary_location(&BaseType::U16),
+                    );
+                    generate_address(run_builder, frame_ref, data.tag, location, &address)?;
+                    address
+                }
+            };
+            Ok(Value::Memory(MemoryData::new(pointer_type.clone(), address, None)))
+        }
+        ir::Expr::Dereference(ref data) => {
+            let value_type = data.value_type.as_ref().unwrap();
+            let pointer = resolve_expr_to_value(run_builder, frame_ref, &data.value)?;
+            if let Value::Immediate(_, ImmediateValue::Number(addr)) = pointer {
+                return Ok(Value::Memory(MemoryData::new(
+                    value_type.clone(),
+                    Location::Global(addr as u16),
+                    None,
+                )));
+            }
+            let location = generate_byte_pointers(
+                run_builder,
+                frame_ref,
+                data.tag,
+                &pointer.with_type(&BaseType::U16),
+                &Value::Immediate(BaseType::U16, ImmediateValue::Number(0)),
+                value_type.size().unwrap(),
+            )?;
+            Ok(Value::Memory(MemoryData::new(value_type.clone(), location, None)))
+        }
+        ir::Expr::FieldAccess(ref data) => {
+            let field_type = data.value_type.as_ref().unwrap();
+            let debug = Some(Arc::new(format!("field:{}", data.field)));
+            match resolve_field_place(run_builder, frame_ref, expr)? {
+                FieldPlace::Direct(location) => Ok(Value::Memory(MemoryData::new(field_type.clone(), location, debug))),
+                FieldPlace::Indirect(pointer, offset) => {
+                    if let Value::Immediate(_, ImmediateValue::Number(addr)) = pointer {
+                        return Ok(Value::Memory(MemoryData::new(
+                            field_type.clone(),
+                            Location::Global((addr as usize + offset) as u16),
+                            debug,
+                        )));
+                    }
+
+                    let location = generate_byte_pointers(
+                        run_builder,
+                        frame_ref,
+                        data.tag,
+                        &pointer,
+                        &Value::Immediate(BaseType::U16, ImmediateValue::Number(offset as i32)),
+                        field_type.size().unwrap(),
+                    )?;
+                    Ok(Value::Memory(MemoryData::new(field_type.clone(), location, debug)))
+                }
+            }
+        }
+    }
+}
+
+/// Stores the result of `op` on the two values into `dest`, scaling the offset of pointer arithmetic
+fn generate_binop(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    tag: SrcTag,
+    op: ast::BinaryOperator,
+    dest_type: &BaseType,
+    dest: &Location,
+    left_value: &Value,
+    right_value: &Value,
+) -> error::Result<()> {
+    match *dest_type {
+        // The type checker leaves the pointer on the left, and the offset counts elements rather than bytes
+        BaseType::Pointer(ref element_type) if op.is_arithmetic() => {
+            let offset = scale_index(
+                run_builder,
+                frame_ref,
+                tag,
+                right_value,
+                element_type.size().unwrap(),
+                &BaseType::U16,
+            )?;
+            binop::BinopGenerator::new(
+                run_builder,
+                frame_ref,
+                tag,
+                &BaseType::U16,
+                dest,
+                &left_value.with_type(&BaseType::U16),
+                &offset,
+            ).generate(op)
+        }
+        _ => binop::BinopGenerator::new(run_builder, frame_ref, tag, dest_type, dest, left_value, right_value)
+            .generate(op),
+    }
+}
+
+/// Stores the address of a location that isn't at a fixed address into `destination`
+fn generate_address(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    tag: SrcTag,
+    location: Location,
+    destination: &Location,
+) -> error::Result<()> {
+    let (base, offset) = match location {
+        Location::FrameOffset(_, offset) => {
+            generate_frame_address(run_builder, tag, frame_ref, offset, destination);
+            return Ok(());
+        }
+        Location::GlobalIndexed(addr, index) => (
+            Value::Immediate(BaseType::U16, ImmediateValue::Number(addr as i32)),
+            *index,
+        ),
+        Location::UnresolvedGlobalIndexed(symbol, index) => {
+            (Value::Immediate(BaseType::U16, ImmediateValue::Symbol(symbol)), *index)
+        }
+        Location::ZeroPageIndirect(pointer, offset) => (
+            Value::Memory(MemoryData::new(BaseType::U16, Location::Global(pointer as u16), None)),
+            Value::Immediate(BaseType::U8, ImmediateValue::Number(offset as i32)),
+        ),
+        _ => unreachable!(),
+    };
+    binop::BinopGenerator::new(run_builder, frame_ref, tag, &BaseType::U16, destination, &base, &offset)
+        .generate(ast::BinaryOperator::Add)
+}
+
+/// Multiplies an array index by the size of the elements to get a byte offset
+fn scale_index(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    tag: SrcTag,
+    index: &Value,
+    element_size: usize,
+    offset_type: &BaseType,
+) -> error::Result<Value> {
+    if let Value::Immediate(_, ImmediateValue::Number(number)) = *index {
+        return Ok(Value::Immediate(
+            offset_type.clone(),
+            ImmediateValue::Number(number * element_size as i32),
+        ));
+    }
+    if element_size == 1 {
+        return Ok(index.clone());
+    }
+
+    let offset = convert_location(
+        frame_ref,
+        &run_builder
+            .symbol_table()
+            .write()
+            .unwrap()
+            .create_temporary_location(offset_type),
+    );
+    let index = if index.value_type().size() == Some(2) && offset_type.size() == Some(1) {
+        Value::low_byte(index)
+    } else {
+        index.clone()
+    };
+    generate_copy(run_builder, tag, offset_type, index, offset.clone())?;
+
+    let offset_value = Value::Memory(MemoryData::new(offset_type.clone(), offset.clone(), None));
+    let (op, amount) = if element_size.is_power_of_two() {
+        (ast::BinaryOperator::ShiftLeft, element_size.trailing_zeros() as i32)
+    } else {
+        (ast::BinaryOperator::Mul, element_size as i32)
+    };
+    let amount_type = if op == ast::BinaryOperator::Mul {
+        offset_type.clone()
+    } else {
+        BaseType::U8
+    };
+    binop::BinopGenerator::new(
+        run_builder,
+        frame_ref,
+        tag,
+        offset_type,
+        &offset,
+        &offset_value,
+        &Value::Immediate(amount_type, ImmediateValue::Number(amount)),
+    ).generate(op)?;
+    Ok(offset_value)
+}
+
+/// Returns the location of the byte at an address for `peek` or `poke`. Constant addresses are used
+/// directly, and anything else is reached through a pointer.
+fn generate_byte_address(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    tag: SrcTag,
+    address: &ir::Expr,
+) -> error::Result<Location> {
+    match resolve_expr_to_value(run_builder, frame_ref, address)? {
+        Value::Immediate(_, ImmediateValue::Number(number)) => Ok(Location::Global(number as u16)),
+        address => generate_byte_pointers(
+            run_builder,
+            frame_ref,
+            tag,
+            &address.with_type(&BaseType::U16),
+            &Value::Immediate(BaseType::U16, ImmediateValue::Number(0)),
+            1,
+        ),
+    }
+}
+
+/// Points one temporary at each byte of a value that is `offset` bytes past the address in `base`,
+/// and returns the location to access the value through them
+fn generate_byte_pointers(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    tag: SrcTag,
+    base: &Value,
+    offset: &Value,
+    byte_count: usize,
+) -> error::Result<Location> {
+    let symbol_table = Arc::clone(run_builder.symbol_table());
+    let zero_page_pointer = symbol_table
+        .read()
+        .unwrap()
+        .variable_by_name(&Arc::new(ZERO_PAGE_POINTER_NAME.into()));
+    if let Some(symbol_table::Variable {
+        location: symbol_table::Location::Global(pointer),
+        ..
+    }) = zero_page_pointer
+    {
+        return generate_zero_page_pointer(run_builder, frame_ref, tag, base, offset, byte_count, pointer as u8);
+    }
+
+    let pointers: Vec<Location> = (0..byte_count)
+        .map(|_| {
+            convert_location(
+                frame_ref,
+                &symbol_table
+                    .write()
+                    .unwrap()
+                    .create_temporary_location(&BaseType::U16),
+            )
+        })
+        .collect();
+    for (index, location) in pointers.iter().enumerate() {
+        let (base, amount) = if index == 0 {
+            (base.clone(), offset.clone())
+        } else {
+            let previous = pointers[index - 1].clone();
+            (
+                Value::Memory(MemoryData::new(BaseType::U16, previous, None)),
+                Value::Immediate(BaseType::U16, ImmediateValue::Number(1)),
+            )
+        };
+        if let Value::Immediate(_, ImmediateValue::Number(0)) = amount {
+            generate_copy(run_builder, tag, &BaseType::U16, base, location.clone())?;
+        } else {
+            binop::BinopGenerator::new(run_builder, frame_ref, tag, &BaseType::U16, location, &base, &amount)
+                .generate(ast::BinaryOperator::Add)?;
+        }
+    }
+
+    let offsets = pointers
+        .iter()
+        .map(|pointer| match *pointer {
+            Location::FrameOffset(_, offset) => offset,
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok(Location::FrameOffsetIndirect(frame_ref, offsets))
+}
+
+/// Points the zero page pointer at the address in `base`, and returns the location
+/// to access the value `offset` bytes past it with `(zp),Y` addressing
+fn generate_zero_page_pointer(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    tag: SrcTag,
+    base: &Value,
+    offset: &Value,
+    byte_count: usize,
+    pointer: u8,
+) -> error::Result<Location> {
+    let pointer_location = Location::Global(pointer as u16);
+    // Small constant offsets fit in the Y register, so they don't need to be added to the pointer
+    if let Value::Immediate(_, ImmediateValue::Number(number)) = *offset {
+        if number >= 0 && number as usize + byte_count <= 256 {
+            match *base {
+                Value::Memory(ref data) if data.location == pointer_location => {}
+                _ => generate_copy(run_builder, tag, &BaseType::U16, base.clone(), pointer_location)?,
+            }
+            return Ok(Location::ZeroPageIndirect(pointer, number as u8));
+        }
+    }
+    binop::BinopGenerator::new(run_builder, frame_ref, tag, &BaseType::U16, &pointer_location, base, offset)
+        .generate(ast::BinaryOperator::Add)?;
+    Ok(Location::ZeroPageIndirect(pointer, 0))
+}
+
+/// Where the bytes of a struct field live
+enum FieldPlace {
+    Direct(Location),
+    /// The field is `usize` bytes after the address held by the pointer value
+    Indirect(Value, usize),
+}
+
+/// Finds the field being accessed, folding nested field accesses into a single offset
+fn resolve_field_place(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    expr: &ir::Expr,
+) -> error::Result<FieldPlace> {
+    if let ir::Expr::FieldAccess(ref data) = *expr {
+        let offset = data.offset.unwrap();
+        if data.indirect {
+            let pointer = resolve_expr_to_value(run_builder, frame_ref, &data.value)?;
+            return Ok(FieldPlace::Indirect(pointer, offset));
+        }
+        return Ok(match resolve_field_place(run_builder, frame_ref, &data.value)? {
+            FieldPlace::Direct(location) => FieldPlace::Direct(location.offset_by(offset)),
+            FieldPlace::Indirect(pointer, base) => FieldPlace::Indirect(pointer, base + offset),
+        });
+    }
+    match resolve_expr_to_place(run_builder, frame_ref, expr)? {
+        Value::Memory(data) => Ok(match data.location {
+            // Indexing through a pointer leaves the address of the struct in a temporary
+            Location::FrameOffsetIndirect(sym_ref, ref pointers) => FieldPlace::Indirect(
+                Value::Memory(MemoryData::new(BaseType::U16, Location::FrameOffset(sym_ref, pointers[0]), None)),
+                0,
+            ),
+            Location::ZeroPageIndirect(pointer, offset) => FieldPlace::Indirect(
+                Value::Memory(MemoryData::new(BaseType::U16, Location::Global(pointer as u16), None)),
+                offset as usize,
+            ),
+            location => FieldPlace::Direct(location),
+        }),
+        Value::Immediate(_, _) => unreachable!(),
     }
 }
 
@@ -384,6 +1644,10 @@ fn generate_function_call(
     frame_ref: SymbolRef,
     call_data: &ir::CallData,
 ) -> error::Result<Value> {
+    if let Some(ref pointer) = call_data.pointer {
+        return generate_pointer_call(run_builder, frame_ref, call_data, pointer);
+    }
+
     let symbol_table = Arc::clone(run_builder.symbol_table());
     let optional_function_ref = symbol_table
         .read()
@@ -418,14 +1682,14 @@ fn generate_function_call(
         if !metadata.parameters.is_empty() {
             let mut frame_offset = 0;
             for (i, argument_value) in argument_values.into_iter().enumerate() {
+                let name_type = &metadata.parameters[i];
                 generate_copy(
                     run_builder,
                     call_data.tag,
-                    &argument_value.value_type(),
+                    &name_type.base_type,
                     offset_call(function_ref, argument_value),
                     Location::FrameOffset(function_ref, frame_offset),
                 )?;
-                let name_type = &metadata.parameters[i];
                 frame_offset += name_type.base_type.size().unwrap() as i8;
             }
         }
@@ -445,40 +1709,97 @@ fn generate_function_call(
                 AddToDataStackPointerData::new(call_data.tag, SPOffset::NegativeFrameSize(function_ref)),
             ));
 
-        if call_data.return_type.as_ref().unwrap().size().is_some() {
-            let dest = convert_location(
-                frame_ref,
-                &symbol_table
-                    .write()
-                    .unwrap()
-                    .create_temporary_location(&BaseType::U8),
-            );
-            generate_copy(
-                run_builder,
-                call_data.tag,
-                call_data.return_type.as_ref().unwrap(),
-                Value::Memory(MemoryData::new(
-                    call_data.return_type.as_ref().unwrap().clone(),
-                    RETURN_LOCATION_LO,
-                    None,
-                )),
-                dest.clone(),
-            )?;
+        generate_return_value(run_builder, frame_ref, call_data)
+    } else {
+        Err(error::ErrorKind::SymbolNotFound(call_data.tag, SymbolName::clone(&call_data.function)).into())
+    }
+}
 
-            Ok(Value::Memory(MemoryData::new(
-                call_data.return_type.as_ref().unwrap().clone(),
-                dest,
-                None,
-            )))
-        } else {
-            Ok(Value::Memory(MemoryData::new(
-                call_data.return_type.as_ref().unwrap().clone(),
-                RETURN_LOCATION_LO,
-                None,
-            )))
+/// Calls a function through a pointer. The arguments go right after the current frame, which is where
+/// the function's frame starts once its indirect entry point has moved the data stack pointer.
+fn generate_pointer_call(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    call_data: &ir::CallData,
+    pointer: &ir::Expr,
+) -> error::Result<Value> {
+    let parameters = match *pointer.base_type().unwrap() {
+        BaseType::Function(ref parameters, _) => parameters.clone(),
+        _ => unreachable!(),
+    };
+
+    let mut argument_values = Vec::new();
+    for argument in &call_data.arguments {
+        argument_values.push(resolve_expr_to_value(run_builder, frame_ref, argument)?)
+    }
+    let pointer_value = resolve_expr_to_value(run_builder, frame_ref, pointer)?;
+
+    let mut stack_offset = 0;
+    for (parameter_type, argument_value) in parameters.iter().zip(argument_values.into_iter()) {
+        generate_copy(
+            run_builder,
+            call_data.tag,
+            parameter_type,
+            argument_value,
+            Location::DataStackOffset(stack_offset),
+        )?;
+        stack_offset += parameter_type.size().unwrap() as i8;
+    }
+
+    // The return location isn't written until the function returns, so it holds the address to jump to
+    generate_copy(
+        run_builder,
+        call_data.tag,
+        &BaseType::U16,
+        pointer_value,
+        RETURN_LOCATION_LO,
+    )?;
+    let call_indirect = {
+        let symbol_table = run_builder.symbol_table();
+        let name = Arc::new(String::from(CALL_INDIRECT_NAME));
+        let existing = symbol_table.read().unwrap().find_symbol(&name);
+        match existing {
+            Some(symbol_ref) => symbol_ref,
+            None => symbol_table.write().unwrap().insert_block(name).unwrap(),
         }
+    };
+    run_builder
+        .current_block()
+        .add_statement(Statement::JumpRoutine(JumpRoutineData::new(
+            call_data.tag,
+            Location::UnresolvedGlobal(call_indirect),
+        )));
+
+    generate_return_value(run_builder, frame_ref, call_data)
+}
+
+/// Copies the value returned by a function out of the return location so that it isn't overwritten by another call
+fn generate_return_value(
+    run_builder: &mut RunBuilder,
+    frame_ref: SymbolRef,
+    call_data: &ir::CallData,
+) -> error::Result<Value> {
+    let return_type = call_data.return_type.as_ref().unwrap();
+    if return_type.size().is_some() {
+        let dest = convert_location(
+            frame_ref,
+            &run_builder
+                .symbol_table()
+                .write()
+                .unwrap()
+                .create_temporary_location(return_type),
+        );
+        generate_copy(
+            run_builder,
+            call_data.tag,
+            return_type,
+            Value::Memory(MemoryData::new(return_type.clone(), RETURN_LOCATION_LO, None)),
+            dest.clone(),
+        )?;
+
+        Ok(Value::Memory(MemoryData::new(return_type.clone(), dest, None)))
     } else {
-        Err(error::ErrorKind::SymbolNotFound(call_data.tag, SymbolName::clone(&call_data.function)).into())
+        Ok(Value::Memory(MemoryData::new(return_type.clone(), RETURN_LOCATION_LO, None)))
     }
 }
 
@@ -32,16 +32,76 @@ pub enum BinaryOperator {
     GreaterThanEqual,
     Equal,
     NotEqual,
+    LogicalAnd,
+    LogicalOr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl BinaryOperator {
     pub fn is_arithmetic(&self) -> bool {
         use self::BinaryOperator::*;
         match *self {
-            Add | Sub | Mul | Div => true,
+            Add | Sub | Mul | Div | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => true,
             _ => false,
         }
     }
+
+    pub fn is_shift(&self) -> bool {
+        use self::BinaryOperator::*;
+        match *self {
+            ShiftLeft | ShiftRight => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_logical(&self) -> bool {
+        use self::BinaryOperator::*;
+        match *self {
+            LogicalAnd | LogicalOr => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the comparison that holds exactly when this one doesn't
+    pub fn inverse(&self) -> BinaryOperator {
+        use self::BinaryOperator::*;
+        match *self {
+            LessThan => GreaterThanEqual,
+            GreaterThan => LessThanEqual,
+            LessThanEqual => GreaterThan,
+            GreaterThanEqual => LessThan,
+            Equal => NotEqual,
+            NotEqual => Equal,
+            _ => panic!("{:?} is not a comparison", self),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum UnaryOperator {
+    BitNot,
+    /// Written as `hi(value)`
+    HighByte,
+    LogicalNot,
+    /// Written as `lo(value)`
+    LowByte,
+    Negate,
+}
+
+impl UnaryOperator {
+    pub fn is_byte_select(&self) -> bool {
+        *self == UnaryOperator::LowByte || *self == UnaryOperator::HighByte
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct AddressOfData {
+    pub tag: SrcTag,
+    pub value: Box<Expression>,
 }
 
 #[derive(Debug, Eq, PartialEq, new)]
@@ -51,6 +111,12 @@ pub struct ArrayIndexData {
     pub index: Box<Expression>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct ArrayLiteralData {
+    pub tag: SrcTag,
+    pub values: Vec<Expression>,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct AssignmentData {
     pub tag: SrcTag,
@@ -66,6 +132,17 @@ pub struct BinaryOpData {
     pub right: Box<Expression>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct BoolData {
+    pub tag: SrcTag,
+    pub value: bool,
+}
+
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct BreakData {
+    pub tag: SrcTag,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct CallFunctionData {
     pub tag: SrcTag,
@@ -73,6 +150,23 @@ pub struct CallFunctionData {
     pub arguments: Vec<Expression>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct CastData {
+    pub tag: SrcTag,
+    pub value: Box<Expression>,
+    pub base_type: BaseType,
+}
+
+/// Assignment that combines the current value with the right value, such as `a += 2`.
+/// Increments and decrements are compound assignments of 1.
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct CompoundAssignmentData {
+    pub tag: SrcTag,
+    pub op: BinaryOperator,
+    pub left_value: Box<Expression>,
+    pub right_value: Box<Expression>,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct ConditionalData {
     pub tag: SrcTag,
@@ -81,6 +175,11 @@ pub struct ConditionalData {
     pub when_false: Vec<Expression>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct ContinueData {
+    pub tag: SrcTag,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct DeclareConstData {
     pub tag: SrcTag,
@@ -95,6 +194,8 @@ pub struct DeclareFunctionData {
     pub parameters: Vec<NameType>,
     pub return_type: BaseType,
     pub body: Vec<Expression>,
+    pub interrupt: bool,
+    pub inline: bool,
 }
 
 #[derive(Debug, Eq, PartialEq, new)]
@@ -104,13 +205,63 @@ pub struct DeclareRegisterData {
     pub location: i32,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct DeclareStaticData {
+    pub variable: DeclareVariableData,
+    /// True when the static was marked with `zp` and must be placed in zero page
+    pub zero_page: bool,
+}
+
+/// A constant in an enum. Variants without a value are one more than the variant before them.
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct EnumVariant {
+    pub name: Arc<String>,
+    pub value: Option<i32>,
+}
+
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct DeclareEnumData {
+    pub tag: SrcTag,
+    pub name: Arc<String>,
+    pub base_type: BaseType,
+    pub variants: Vec<EnumVariant>,
+}
+
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct DeclareStructData {
+    pub tag: SrcTag,
+    pub name: Arc<String>,
+    pub fields: Vec<NameType>,
+}
+
+/// Gives another name to an existing type
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct DeclareTypeData {
+    pub tag: SrcTag,
+    pub name: Arc<String>,
+    pub base_type: BaseType,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct DeclareVariableData {
     pub tag: SrcTag,
     pub name_type: NameType,
+    pub value: Option<Box<Expression>>,
+}
+
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct DereferenceData {
+    pub tag: SrcTag,
     pub value: Box<Expression>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct FieldAccessData {
+    pub tag: SrcTag,
+    pub value: Box<Expression>,
+    pub field: Arc<String>,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct GoToData {
     pub tag: SrcTag,
@@ -123,6 +274,21 @@ pub struct InlineAsmData {
     pub asm: Arc<String>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct MatchCaseData {
+    pub tag: SrcTag,
+    pub values: Vec<Expression>,
+    pub body: Vec<Expression>,
+}
+
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct MatchData {
+    pub tag: SrcTag,
+    pub value: Box<Expression>,
+    pub cases: Vec<MatchCaseData>,
+    pub default: Vec<Expression>,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct NameData {
     pub tag: SrcTag,
@@ -135,6 +301,13 @@ pub struct NumberData {
     pub value: i32,
 }
 
+/// Pulls in the declarations of another source file
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct ImportData {
+    pub tag: SrcTag,
+    pub path: Arc<String>,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct OrgData {
     pub tag: SrcTag,
@@ -147,12 +320,26 @@ pub struct ReturnData {
     pub value: Option<Box<Expression>>,
 }
 
+/// The size in bytes of a type, or of the type of a variable or constant
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct SizeOfData {
+    pub tag: SrcTag,
+    pub base_type: BaseType,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct TextData {
     pub tag: SrcTag,
     pub value: Arc<String>,
 }
 
+#[derive(Debug, Eq, PartialEq, new)]
+pub struct UnaryOpData {
+    pub tag: SrcTag,
+    pub op: UnaryOperator,
+    pub value: Box<Expression>,
+}
+
 #[derive(Debug, Eq, PartialEq, new)]
 pub struct WhileLoopData {
     pub tag: SrcTag,
@@ -162,25 +349,41 @@ pub struct WhileLoopData {
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Expression {
+    AddressOf(AddressOfData),
     ArrayIndex(ArrayIndexData),
+    ArrayLiteral(ArrayLiteralData),
     Assignment(AssignmentData),
     BinaryOp(BinaryOpData),
-    Break,
+    Bool(BoolData),
+    Break(BreakData),
     CallFunction(CallFunctionData),
+    Cast(CastData),
     Comment,
+    CompoundAssignment(CompoundAssignmentData),
     Conditional(ConditionalData),
+    Continue(ContinueData),
     DeclareConst(DeclareConstData),
+    DeclareEnum(DeclareEnumData),
     DeclareFunction(DeclareFunctionData),
     DeclareRegister(DeclareRegisterData),
+    DeclareStatic(DeclareStaticData),
+    DeclareStruct(DeclareStructData),
+    DeclareType(DeclareTypeData),
     DeclareVariable(DeclareVariableData),
+    Dereference(DereferenceData),
     Error,
+    FieldAccess(FieldAccessData),
     GoTo(GoToData),
+    Import(ImportData),
     InlineAsm(InlineAsmData),
+    Match(MatchData),
     Name(NameData),
     Number(NumberData),
     Org(OrgData),
     Return(ReturnData),
+    SizeOf(SizeOfData),
     Text(TextData),
+    UnaryOp(UnaryOpData),
     WhileLoop(WhileLoopData),
 }
 
@@ -190,11 +393,13 @@ impl Expression {
             return Ok(Vec::new());
         }
 
-        let mut errors: Vec<lalrpop_util::ErrorRecovery<usize, (usize, &'a str), ()>> = Vec::new();
+        let mut errors: Vec<lalrpop_util::ErrorRecovery<usize, (usize, &'a str), error::ErrorKind>> = Vec::new();
         let ast = ::parse::grammar::parse_Program(src_unit.id, &mut errors, &src_unit.source);
         if errors.is_empty() {
             match ast {
                 Ok(expression) => Ok(expression),
+                // Errors raised by the grammar's actions already know where they happened
+                Err(lalrpop_util::ParseError::User { error }) => Err(error.into()),
                 Err(err) => Err(translate_errors(src_unit, [err].iter()).into()),
             }
         } else {
@@ -207,25 +412,41 @@ impl SrcTagged for Expression {
     fn src_tag(&self) -> SrcTag {
         use self::Expression::*;
         match *self {
+            AddressOf(ref d) => d.tag,
             ArrayIndex(ref d) => d.tag,
+            ArrayLiteral(ref d) => d.tag,
             Assignment(ref d) => d.tag,
             BinaryOp(ref d) => d.tag,
-            Break => unimplemented!(),
+            Bool(ref d) => d.tag,
+            Break(ref d) => d.tag,
             CallFunction(ref d) => d.tag,
+            Cast(ref d) => d.tag,
             Comment => unimplemented!(),
+            CompoundAssignment(ref d) => d.tag,
             Conditional(ref d) => d.tag,
+            Continue(ref d) => d.tag,
             DeclareConst(ref d) => d.tag,
+            DeclareEnum(ref d) => d.tag,
             DeclareFunction(ref d) => d.tag,
             DeclareRegister(ref d) => d.tag,
+            DeclareStatic(ref d) => d.variable.tag,
+            DeclareStruct(ref d) => d.tag,
+            DeclareType(ref d) => d.tag,
             DeclareVariable(ref d) => d.tag,
+            Dereference(ref d) => d.tag,
             Error => unimplemented!(),
+            FieldAccess(ref d) => d.tag,
             InlineAsm(ref d) => d.tag,
+            Match(ref d) => d.tag,
             GoTo(ref d) => d.tag,
             Name(ref d) => d.tag,
             Number(ref d) => d.tag,
+            Import(ref d) => d.tag,
             Org(ref d) => d.tag,
             Return(ref d) => d.tag,
+            SizeOf(ref d) => d.tag,
             Text(ref d) => d.tag,
+            UnaryOp(ref d) => d.tag,
             WhileLoop(ref d) => d.tag,
         }
     }
@@ -233,7 +454,7 @@ impl SrcTagged for Expression {
 
 fn translate_errors<'a, I>(unit: &SrcUnit, errors: I) -> error::ErrorKind
 where
-    I: Iterator<Item = &'a lalrpop_util::ParseError<usize, (usize, &'a str), ()>>,
+    I: Iterator<Item = &'a lalrpop_util::ParseError<usize, (usize, &'a str), error::ErrorKind>>,
 {
     let mut messages = Vec::new();
     for error in errors {
@@ -374,6 +595,83 @@ mod test {
         assert_eq!(expected, ast);
     }
 
+    #[test]
+    fn operator_precedence_logical() {
+        let program = "a = b || c && d == 1;";
+        let ast = Expression::parse(&SrcUnit::new(0, "".into(), program.into())).expect("parse");
+
+        let expected = vec![
+            Expression::Assignment(AssignmentData::new(
+                SrcTag::new(0, 0),
+                Box::new(Expression::Name(NameData::new(
+                    SrcTag::new(0, 0),
+                    Arc::new("a".into()),
+                ))),
+                Box::new(Expression::BinaryOp(BinaryOpData::new(
+                    SrcTag::new(0, 4),
+                    BinaryOperator::LogicalOr,
+                    Box::new(Expression::Name(NameData::new(
+                        SrcTag::new(0, 4),
+                        Arc::new("b".into()),
+                    ))),
+                    Box::new(Expression::BinaryOp(BinaryOpData::new(
+                        SrcTag::new(0, 9),
+                        BinaryOperator::LogicalAnd,
+                        Box::new(Expression::Name(NameData::new(
+                            SrcTag::new(0, 9),
+                            Arc::new("c".into()),
+                        ))),
+                        Box::new(Expression::BinaryOp(BinaryOpData::new(
+                            SrcTag::new(0, 14),
+                            BinaryOperator::Equal,
+                            Box::new(Expression::Name(NameData::new(
+                                SrcTag::new(0, 14),
+                                Arc::new("d".into()),
+                            ))),
+                            Box::new(Expression::Number(NumberData::new(SrcTag::new(0, 19), 1))),
+                        ))),
+                    ))),
+                ))),
+            )),
+        ];
+
+        assert_eq!(expected, ast);
+    }
+
+    #[test]
+    fn operator_precedence_cast() {
+        let program = "a = b * c as u16;";
+        let ast = Expression::parse(&SrcUnit::new(0, "".into(), program.into())).expect("parse");
+
+        let expected = vec![
+            Expression::Assignment(AssignmentData::new(
+                SrcTag::new(0, 0),
+                Box::new(Expression::Name(NameData::new(
+                    SrcTag::new(0, 0),
+                    Arc::new("a".into()),
+                ))),
+                Box::new(Expression::BinaryOp(BinaryOpData::new(
+                    SrcTag::new(0, 4),
+                    BinaryOperator::Mul,
+                    Box::new(Expression::Name(NameData::new(
+                        SrcTag::new(0, 4),
+                        Arc::new("b".into()),
+                    ))),
+                    Box::new(Expression::Cast(CastData::new(
+                        SrcTag::new(0, 8),
+                        Box::new(Expression::Name(NameData::new(
+                            SrcTag::new(0, 8),
+                            Arc::new("c".into()),
+                        ))),
+                        BaseType::U16,
+                    ))),
+                ))),
+            )),
+        ];
+
+        assert_eq!(expected, ast);
+    }
+
     #[test]
     fn parse_const() {
         let program = "register test_register: u8 @ 0x8000;";
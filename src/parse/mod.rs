@@ -7,6 +7,9 @@
 // copied, modified, or distributed except according to those terms.
 //
 
+use error::ErrorKind;
+use src_tag::SrcTag;
+
 pub mod ast;
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -18,3 +21,41 @@ fn unescape_string(s: &str) -> String {
         .replace("\\t", "\t")
         .replace("\\r", "\r")
 }
+
+/// The largest value that a literal can have, since no type is wider than 16 bits
+const MAX_LITERAL: i64 = 0xFFFF;
+
+/// Parses the digits of a number literal in the given radix, skipping `_` separators
+fn parse_number(tag: SrcTag, digits: &str, radix: u32) -> Result<i32, ErrorKind> {
+    let mut value: i64 = 0;
+    for digit in digits.chars().filter(|&c| c != '_') {
+        value = value
+            .saturating_mul(i64::from(radix))
+            .saturating_add(i64::from(digit.to_digit(radix).unwrap()));
+    }
+    if value > MAX_LITERAL {
+        Err(ErrorKind::OutOfBounds(tag, value as isize, 0, MAX_LITERAL as isize))
+    } else {
+        Ok(value as i32)
+    }
+}
+
+/// Parses the inside of a character literal into its byte value
+fn parse_character(tag: SrcTag, s: &str) -> Result<i32, ErrorKind> {
+    let mut chars = s.chars();
+    let value = match chars.next().unwrap() {
+        '\\' => match chars.next().unwrap() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            escaped => escaped,
+        },
+        c => c,
+    } as i32;
+    if value > 0xFF {
+        Err(ErrorKind::OutOfBounds(tag, value as isize, 0, 0xFF))
+    } else {
+        Ok(value)
+    }
+}
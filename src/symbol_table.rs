@@ -16,6 +16,8 @@ use std::fmt::Debug;
 #[derive(Debug, Copy, Clone)]
 pub enum Location {
     UndeterminedGlobal,
+    /// A static that was marked to be placed in zero page
+    UndeterminedZeroPage,
     Global(u16),
     FrameOffset(i8),
 }
@@ -30,6 +32,22 @@ pub struct FunctionMetadata {
     pub parameters: Vec<NameType>,
     pub return_type: BaseType,
     pub frame_size: i8,
+    /// Entry point used when the function is called through a pointer, if its address is ever taken
+    pub indirect_entry: Option<SymbolRef>,
+    /// Whether this function is an interrupt handler, which can only be entered through a vector
+    pub interrupt: bool,
+    /// Whether calls to this function should be replaced with its body
+    pub inline: bool,
+}
+
+impl FunctionMetadata {
+    /// Returns the type of a pointer to this function
+    pub fn function_type(&self) -> BaseType {
+        BaseType::Function(
+            self.parameters.iter().map(|p| p.base_type.clone()).collect(),
+            Box::new(self.return_type.clone()),
+        )
+    }
 }
 
 pub type FunctionMetadataPtr = Arc<RwLock<FunctionMetadata>>;
@@ -44,6 +62,8 @@ pub struct Variable {
 pub enum ConstantValue {
     Number(i32),
     Bytes(Arc<Vec<u8>>),
+    /// A table of function addresses
+    Functions(Arc<Vec<SymbolRef>>),
 }
 
 impl ConstantValue {
@@ -53,6 +73,14 @@ impl ConstantValue {
             _ => panic!("attempt to take a number from non-number constant value"),
         }
     }
+
+    /// Returns true for values that are emitted with the program and referred to by their label
+    pub fn is_table(&self) -> bool {
+        match *self {
+            ConstantValue::Number(_) => false,
+            ConstantValue::Bytes(_) | ConstantValue::Functions(_) => true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, new)]
@@ -66,6 +94,7 @@ enum Symbol {
     Constant(Constant),
     Variable(Variable),
     Function(FunctionMetadataPtr),
+    Type(BaseType),
     Block,
 }
 
@@ -119,15 +148,47 @@ impl SymbolMap {
         }))
     }
 
-    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, Arc<Vec<u8>>)> + 'a> {
+    fn constants<'a>(&'a self) -> Box<Iterator<Item = &'a Constant> + 'a> {
+        Box::new(self.by_ref.values().filter_map(|symbol| match *symbol {
+            Symbol::Constant(ref constant) => Some(constant),
+            _ => None,
+        }))
+    }
+
+    /// Lists the variables that don't have an address yet in the order they were declared
+    fn undetermined_globals(&self) -> Vec<SymbolRef> {
+        let mut symbol_refs: Vec<SymbolRef> = self.by_ref
+            .iter()
+            .filter_map(|(symbol_ref, symbol)| match *symbol {
+                Symbol::Variable(Variable {
+                    location: Location::UndeterminedGlobal,
+                    ..
+                })
+                | Symbol::Variable(Variable {
+                    location: Location::UndeterminedZeroPage,
+                    ..
+                }) => Some(*symbol_ref),
+                _ => None,
+            })
+            .collect();
+        symbol_refs.sort();
+        symbol_refs
+    }
+
+    fn set_variable_location(&mut self, symbol_ref: SymbolRef, location: Location) {
+        if let Some(&mut Symbol::Variable(ref mut variable)) = self.by_ref.get_mut(&symbol_ref) {
+            variable.location = location;
+        }
+    }
+
+    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, ConstantValue)> + 'a> {
         Box::new(
             self.by_ref
                 .iter()
                 .filter_map(|(symbol_ref, symbol)| match *symbol {
-                    Symbol::Constant(ref constant) => match constant.value {
-                        ConstantValue::Bytes(ref bytes) => Some((*symbol_ref, Arc::clone(bytes))),
-                        _ => None,
-                    },
+                    Symbol::Constant(ref constant) if constant.value.is_table() => {
+                        Some((*symbol_ref, constant.value.clone()))
+                    }
                     _ => None,
                 }),
         )
@@ -151,6 +212,9 @@ impl HandleGenerator {
     }
 }
 
+/// Frame offsets are signed bytes, so no frame can be larger than this
+pub const MAX_FRAME_SIZE: usize = 127;
+
 pub trait SymbolTable: Send + Sync + Debug {
     fn new_block_name(&mut self) -> (SymbolName, SymbolRef);
     fn insert_block(&mut self, symbol_name: SymbolName) -> Option<SymbolRef>;
@@ -160,6 +224,7 @@ pub trait SymbolTable: Send + Sync + Debug {
     fn get_symbol_name(&self, symbol_ref: SymbolRef) -> Option<SymbolName>;
 
     fn next_frame_offset(&mut self, local_size: usize) -> i8;
+    fn frame_size(&self) -> usize;
 
     fn create_temporary(&mut self, base_type: &BaseType) -> SymbolRef;
     fn create_temporary_location(&mut self, base_type: &BaseType) -> Location;
@@ -173,6 +238,7 @@ pub trait SymbolTable: Send + Sync + Debug {
     fn insert_unnamed_constant(&mut self, base_type: &BaseType, value: ConstantValue) -> Option<SymbolRef>;
     fn constant_by_name(&self, symbol_name: &SymbolName) -> Option<Constant>;
     fn constant(&self, symbol_ref: SymbolRef) -> Option<Constant>;
+    fn constants<'a>(&'a self) -> Box<Iterator<Item = &'a Constant> + 'a>;
 
     fn insert_function(&mut self, symbol_name: SymbolName, metadata: FunctionMetadataPtr) -> Option<SymbolRef>;
     fn function_by_name(&self, symbol_name: &SymbolName) -> Option<FunctionMetadataPtr>;
@@ -182,8 +248,13 @@ pub trait SymbolTable: Send + Sync + Debug {
     fn variable_by_name(&self, symbol_name: &SymbolName) -> Option<Variable>;
     fn variable(&self, symbol_ref: SymbolRef) -> Option<Variable>;
     fn variables<'a>(&'a self) -> Box<Iterator<Item = &'a Variable> + 'a>;
+    fn undetermined_globals(&self) -> Vec<SymbolRef>;
+    fn set_variable_location(&mut self, symbol_ref: SymbolRef, location: Location);
 
-    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, Arc<Vec<u8>>)> + 'a>;
+    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, ConstantValue)> + 'a>;
+
+    fn insert_type(&mut self, symbol_name: SymbolName, base_type: BaseType) -> Option<SymbolRef>;
+    fn type_by_name(&self, symbol_name: &SymbolName) -> Option<BaseType>;
 
     fn type_of(&self, symbol_ref: SymbolRef) -> Option<BaseType>;
     fn type_of_by_name(&self, symbol_name: &SymbolName) -> Option<BaseType>;
@@ -195,11 +266,11 @@ pub trait SymbolTable: Send + Sync + Debug {
 pub struct DefaultSymbolTable {
     handle_gen: Arc<RwLock<HandleGenerator>>,
     symbols: SymbolMap,
-    next_frame_offset: i8,
+    next_frame_offset: usize,
 }
 
 impl DefaultSymbolTable {
-    pub fn new(handle_gen: Arc<RwLock<HandleGenerator>>, next_frame_offset: i8) -> DefaultSymbolTable {
+    pub fn new(handle_gen: Arc<RwLock<HandleGenerator>>, next_frame_offset: usize) -> DefaultSymbolTable {
         DefaultSymbolTable {
             handle_gen: handle_gen,
             symbols: SymbolMap::new(),
@@ -236,13 +307,22 @@ impl SymbolTable for DefaultSymbolTable {
 
     fn next_frame_offset(&mut self, local_size: usize) -> i8 {
         let result = self.next_frame_offset;
-        self.next_frame_offset += local_size as i8;
-        result
+        self.next_frame_offset += local_size;
+        if self.next_frame_offset > MAX_FRAME_SIZE {
+            // The frame is rejected once its size is checked, so only keep the offsets in range until then
+            0
+        } else {
+            result as i8
+        }
+    }
+
+    fn frame_size(&self) -> usize {
+        self.next_frame_offset
     }
 
     fn create_temporary(&mut self, base_type: &BaseType) -> SymbolRef {
+        let symbol_name = SymbolName::new(format!("tmp#{}", self.next_frame_offset));
         let next_location = self.next_frame_offset(base_type.size().unwrap());
-        let symbol_name = SymbolName::new(format!("tmp#{}", next_location));
         let symbol_ref = self.handle_gen.write().unwrap().new_handle();
         self.symbols.insert(
             SymbolName::clone(&symbol_name),
@@ -342,21 +422,47 @@ impl SymbolTable for DefaultSymbolTable {
         }
     }
 
+    fn constants<'a>(&'a self) -> Box<Iterator<Item = &'a Constant> + 'a> {
+        self.symbols.constants()
+    }
+
     fn variables<'a>(&'a self) -> Box<Iterator<Item = &'a Variable> + 'a> {
         self.symbols.variables()
     }
 
-    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, Arc<Vec<u8>>)> + 'a> {
+    fn undetermined_globals(&self) -> Vec<SymbolRef> {
+        self.symbols.undetermined_globals()
+    }
+
+    fn set_variable_location(&mut self, symbol_ref: SymbolRef, location: Location) {
+        self.symbols.set_variable_location(symbol_ref, location)
+    }
+
+    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, ConstantValue)> + 'a> {
         self.symbols.data_constants()
     }
 
+    fn insert_type(&mut self, symbol_name: SymbolName, base_type: BaseType) -> Option<SymbolRef> {
+        let symbol_ref = self.handle_gen.write().unwrap().new_handle();
+        self.symbols
+            .insert(symbol_name, symbol_ref, Symbol::Type(base_type))
+    }
+
+    fn type_by_name(&self, symbol_name: &SymbolName) -> Option<BaseType> {
+        if let Some(&Symbol::Type(ref base_type)) = self.symbols.find_by_name(symbol_name) {
+            Some(base_type.clone())
+        } else {
+            None
+        }
+    }
+
     fn type_of(&self, symbol_ref: SymbolRef) -> Option<BaseType> {
         if let Some(symbol) = self.symbols.find_by_ref(symbol_ref) {
             match *symbol {
                 Symbol::Constant(ref data) => Some(data.base_type.clone()),
                 Symbol::Variable(ref data) => Some(data.base_type.clone()),
                 Symbol::Function(ref data) => Some(data.read().unwrap().return_type.clone()),
-                Symbol::Block => None,
+                Symbol::Type(_) | Symbol::Block => None,
             }
         } else {
             None
@@ -423,6 +529,10 @@ impl SymbolTable for ParentedSymbolTableWrapper {
         self.child.next_frame_offset(local_size)
     }
 
+    fn frame_size(&self) -> usize {
+        self.child.frame_size()
+    }
+
     fn create_temporary(&mut self, base_type: &BaseType) -> SymbolRef {
         self.child.create_temporary(base_type)
     }
@@ -500,14 +610,38 @@ impl SymbolTable for ParentedSymbolTableWrapper {
         }
     }
 
+    fn constants<'a>(&'a self) -> Box<Iterator<Item = &'a Constant> + 'a> {
+        self.child.constants()
+    }
+
     fn variables<'a>(&'a self) -> Box<Iterator<Item = &'a Variable> + 'a> {
         self.child.variables()
     }
 
-    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, Arc<Vec<u8>>)> + 'a> {
+    fn undetermined_globals(&self) -> Vec<SymbolRef> {
+        self.child.undetermined_globals()
+    }
+
+    fn set_variable_location(&mut self, symbol_ref: SymbolRef, location: Location) {
+        self.child.set_variable_location(symbol_ref, location)
+    }
+
+    fn data_constants<'a>(&'a self) -> Box<Iterator<Item = (SymbolRef, ConstantValue)> + 'a> {
         self.child.data_constants()
     }
 
+    fn insert_type(&mut self, symbol_name: SymbolName, base_type: BaseType) -> Option<SymbolRef> {
+        self.child.insert_type(symbol_name, base_type)
+    }
+
+    fn type_by_name(&self, symbol_name: &SymbolName) -> Option<BaseType> {
+        if let Some(base_type) = self.child.type_by_name(symbol_name) {
+            Some(base_type)
+        } else {
+            self.parent.read().unwrap().type_by_name(symbol_name)
+        }
+    }
+
     fn type_of(&self, symbol_ref: SymbolRef) -> Option<BaseType> {
         if let Some(typ) = self.child.type_of(symbol_ref) {
             Some(typ)
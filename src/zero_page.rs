@@ -0,0 +1,133 @@
+//
+// Copyright 2017 hasselc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//
+
+use code::{DATA_STACK_POINTER_LOCATION, RETURN_VALUE_LOCATION};
+use error::{self, ErrorKind};
+
+/// Name of the pointer in zero page that's used for `(zp),Y` addressing
+pub const ZERO_PAGE_POINTER_NAME: &str = "__ZP_POINTER_";
+
+/// Locations in zero page that the generated code always uses
+const RESERVED: [(u16, u16, &str); 2] = [
+    (DATA_STACK_POINTER_LOCATION, DATA_STACK_POINTER_LOCATION, "data stack pointer"),
+    (RETURN_VALUE_LOCATION, RETURN_VALUE_LOCATION + 1, "return value"),
+];
+
+/// The data stack starts right after the return value and grows up from there
+const DATA_STACK_START: u16 = RETURN_VALUE_LOCATION + 2;
+
+/// Hands out addresses from the range of zero page that the compiler owns.
+/// An empty range (where the start is after the end) never hands anything out.
+#[derive(Debug)]
+pub struct ZeroPageAllocator {
+    next_address: usize,
+    end_address: usize,
+    /// Address ranges declared by the program itself, which are skipped over
+    declared: Vec<(usize, usize)>,
+}
+
+impl ZeroPageAllocator {
+    pub fn new(start: u8, end: u8, data_stack_end: u8) -> error::Result<ZeroPageAllocator> {
+        if start <= end {
+            let data_stack = (DATA_STACK_START, data_stack_end as u16, "data stack");
+            for &(reserved_start, reserved_end, reserved) in RESERVED.iter().chain(Some(&data_stack)) {
+                if reserved_start <= end as u16 && start as u16 <= reserved_end {
+                    let address = ::std::cmp::max(reserved_start, start as u16);
+                    return Err(ErrorKind::ZeroPageConflict(start, end, reserved, address).into());
+                }
+            }
+        }
+        Ok(ZeroPageAllocator {
+            next_address: start as usize,
+            end_address: end as usize,
+            declared: Vec::new(),
+        })
+    }
+
+    /// Keeps the `size` bytes at `address` from being handed out, since the program already uses them
+    pub fn declare(&mut self, address: u16, size: usize) {
+        if size > 0 {
+            self.declared.push((address as usize, address as usize + size - 1));
+        }
+    }
+
+    /// Returns the address of `size` unused bytes, or None if there isn't enough room left
+    pub fn allocate(&mut self, size: usize) -> Option<u16> {
+        let address = skip_declared(&self.declared, self.next_address, size);
+        if address + size > self.end_address + 1 {
+            None
+        } else {
+            self.next_address = address + size;
+            Some(address as u16)
+        }
+    }
+}
+
+/// Returns the first address at or after `address` where `size` bytes don't overlap any of the
+/// `declared` ranges, which are inclusive
+pub fn skip_declared(declared: &[(usize, usize)], address: usize, size: usize) -> usize {
+    let mut address = address;
+    while let Some(&(_, declared_end)) = declared
+        .iter()
+        .find(|&&(declared_start, declared_end)| declared_start < address + size && address <= declared_end)
+    {
+        address = declared_end + 1;
+    }
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_until_full() {
+        let mut allocator = ZeroPageAllocator::new(0xFC, 0xFF, 0x7F).unwrap();
+        assert_eq!(Some(0xFC), allocator.allocate(2));
+        assert_eq!(None, allocator.allocate(3));
+        assert_eq!(Some(0xFE), allocator.allocate(1));
+        assert_eq!(Some(0xFF), allocator.allocate(1));
+        assert_eq!(None, allocator.allocate(1));
+    }
+
+    #[test]
+    fn empty_range() {
+        let mut allocator = ZeroPageAllocator::new(0x80, 0x00, 0x7F).unwrap();
+        assert_eq!(None, allocator.allocate(1));
+    }
+
+    #[test]
+    fn conflicts_with_reserved() {
+        let err = ZeroPageAllocator::new(0x02, 0x10, 0x7F).unwrap_err();
+        assert_eq!(
+            "Zero page range $02-$10 overlaps the return value at $02",
+            format!("{}", err.0)
+        );
+    }
+
+    #[test]
+    fn conflicts_with_data_stack() {
+        let err = ZeroPageAllocator::new(0x40, 0xFF, 0x7F).unwrap_err();
+        assert_eq!(
+            "Zero page range $40-$FF overlaps the data stack at $40",
+            format!("{}", err.0)
+        );
+        assert!(ZeroPageAllocator::new(0x40, 0xFF, 0x3F).is_ok());
+    }
+
+    #[test]
+    fn skips_declared() {
+        let mut allocator = ZeroPageAllocator::new(0xF0, 0xFF, 0x7F).unwrap();
+        allocator.declare(0xF1, 2);
+        allocator.declare(0xF4, 1);
+        assert_eq!(Some(0xF0), allocator.allocate(1));
+        assert_eq!(Some(0xF5), allocator.allocate(2));
+        assert_eq!(Some(0xF7), allocator.allocate(1));
+    }
+}
@@ -12,6 +12,7 @@ extern crate hassel_emu;
 
 use std::fs;
 use std::io::prelude::*;
+use std::path::PathBuf;
 use hassel_emu::{Cpu, MemoryMap};
 use hasselc::error;
 
@@ -61,24 +62,36 @@ impl Emulator {
     }
 }
 
+fn compiler_options(optimize_llir: bool, optimize_code: bool) -> hasselc::CompilerOptionsBuilder {
+    let mut builder = hasselc::CompilerOptionsBuilder::default();
+    builder
+        .optimize_llir(optimize_llir)
+        .optimize_code(optimize_code)
+        .inline_functions(optimize_code);
+    builder
+}
+
 fn compile(
     name: &str,
     program: &str,
     optimize_llir: bool,
     optimize_code: bool,
 ) -> error::Result<hasselc::CompilerOutput> {
-    let compiler_options = hasselc::CompilerOptionsBuilder::default()
-        .optimize_llir(optimize_llir)
-        .optimize_code(optimize_code)
-        .build()
-        .unwrap();
+    compile_with_options(name, program, compiler_options(optimize_llir, optimize_code).build().unwrap())
+}
+
+fn compile_with_options(
+    name: &str,
+    program: &str,
+    compiler_options: hasselc::CompilerOptions,
+) -> error::Result<hasselc::CompilerOutput> {
     let mut compiler = hasselc::Compiler::new(compiler_options);
     compiler.parse_unit(name, program)?;
     compiler.compile()
 }
 
-fn assemble(name: &str, program: &str, optimize_llir: bool, optimize_code: bool) -> Vec<u8> {
-    let compiler_output = match compile(name, program, optimize_llir, optimize_code) {
+fn assemble(name: &str, program: &str, compiler_options: hasselc::CompilerOptions) -> Vec<u8> {
+    let compiler_output = match compile_with_options(name, program, compiler_options) {
         Ok(output) => output,
         Err(err) => panic!(format!("{}", err.0)),
     };
@@ -108,11 +121,19 @@ fn assemble(name: &str, program: &str, optimize_llir: bool, optimize_code: bool)
 }
 
 fn run_test(name: &str, program_raw: &[u8], optimize_llir: bool, optimize_code: bool) -> Emulator {
+    run_test_with_options(
+        name,
+        program_raw,
+        compiler_options(optimize_llir, optimize_code).build().unwrap(),
+    )
+}
+
+fn run_test_with_options(name: &str, program_raw: &[u8], compiler_options: hasselc::CompilerOptions) -> Emulator {
     let mut program_bytes = Vec::new();
     program_bytes.extend(program_raw.iter());
 
     let program = String::from_utf8(program_bytes).unwrap();
-    let assembled = assemble(name, &program, optimize_llir, optimize_code);
+    let assembled = assemble(name, &program, compiler_options);
 
     let mut step_num = 0;
     let mut emulator = Emulator::new(assembled);
@@ -121,7 +142,8 @@ fn run_test(name: &str, program_raw: &[u8], optimize_llir: bool, optimize_code:
         emulator.step();
         step_num += 1;
 
-        if step_num > 1_000 {
+        // Multiplication and division loop over every bit, so programs using them run for a while
+        if step_num > 100_000 {
             panic!("code under test is probably infinite looping");
         }
     }
@@ -390,3 +412,1436 @@ pub fn memcpy_test_optimized() {
     assert_eq!('o', emulator.memory().debug_read().byte(0x0204) as char);
     assert_eq!(0, emulator.memory().debug_read().byte(0x0205));
 }
+
+#[test]
+pub fn multiply_test_unoptimized() {
+    let emulator = emulate!(unoptimized: multiply_test);
+    assert_eq!(132u8, emulator.memory().debug_read().byte(0x0200), "mul8");
+    assert_eq!(88u8, emulator.memory().debug_read().byte(0x0201), "mul8_overflow");
+    assert_eq!(42u8, emulator.memory().debug_read().byte(0x0202), "constant");
+    assert_eq!(100u8, emulator.memory().debug_read().byte(0x0203), "variable_by_constant");
+    assert_eq!(96u8, emulator.memory().debug_read().byte(0x0204), "mul16_lo");
+    assert_eq!(234u8, emulator.memory().debug_read().byte(0x0205), "mul16_hi");
+    assert_eq!(160u8, emulator.memory().debug_read().byte(0x0206), "mul16_overflow_lo");
+    assert_eq!(134u8, emulator.memory().debug_read().byte(0x0207), "mul16_overflow_hi");
+    assert_eq!(63u8, emulator.memory().debug_read().byte(0x0208), "mul16_expr_lo");
+    assert_eq!(43u8, emulator.memory().debug_read().byte(0x0209), "mul16_expr_hi");
+    assert_eq!(96u8, emulator.memory().debug_read().byte(0x020A), "mul8x16_lo");
+    assert_eq!(234u8, emulator.memory().debug_read().byte(0x020B), "mul8x16_hi");
+    assert_eq!(64u8, emulator.memory().debug_read().byte(0x020C), "mul16x8_lo");
+    assert_eq!(66u8, emulator.memory().debug_read().byte(0x020D), "mul16x8_hi");
+}
+
+#[test]
+pub fn multiply_test_optimized() {
+    let emulator = emulate!(optimized: multiply_test);
+    assert_eq!(132u8, emulator.memory().debug_read().byte(0x0200), "mul8");
+    assert_eq!(88u8, emulator.memory().debug_read().byte(0x0201), "mul8_overflow");
+    assert_eq!(42u8, emulator.memory().debug_read().byte(0x0202), "constant");
+    assert_eq!(100u8, emulator.memory().debug_read().byte(0x0203), "variable_by_constant");
+    assert_eq!(96u8, emulator.memory().debug_read().byte(0x0204), "mul16_lo");
+    assert_eq!(234u8, emulator.memory().debug_read().byte(0x0205), "mul16_hi");
+    assert_eq!(160u8, emulator.memory().debug_read().byte(0x0206), "mul16_overflow_lo");
+    assert_eq!(134u8, emulator.memory().debug_read().byte(0x0207), "mul16_overflow_hi");
+    assert_eq!(63u8, emulator.memory().debug_read().byte(0x0208), "mul16_expr_lo");
+    assert_eq!(43u8, emulator.memory().debug_read().byte(0x0209), "mul16_expr_hi");
+    assert_eq!(96u8, emulator.memory().debug_read().byte(0x020A), "mul8x16_lo");
+    assert_eq!(234u8, emulator.memory().debug_read().byte(0x020B), "mul8x16_hi");
+    assert_eq!(64u8, emulator.memory().debug_read().byte(0x020C), "mul16x8_lo");
+    assert_eq!(66u8, emulator.memory().debug_read().byte(0x020D), "mul16x8_hi");
+}
+
+#[test]
+pub fn divide_test_unoptimized() {
+    let emulator = emulate!(unoptimized: divide_test);
+    assert_eq!(28u8, emulator.memory().debug_read().byte(0x0200), "div8");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "div8_large_divisor");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0202), "constant");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x0203), "variable_by_constant");
+    assert_eq!(200u8, emulator.memory().debug_read().byte(0x0204), "div16_lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0205), "div16_hi");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0206), "div16_large_divisor_lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0207), "div16_large_divisor_hi");
+    assert_eq!(230u8, emulator.memory().debug_read().byte(0x0208), "div16_remainder_lo");
+    assert_eq!(27u8, emulator.memory().debug_read().byte(0x0209), "div16_remainder_hi");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x020A), "div_by_zero");
+    assert_eq!(123u8, emulator.memory().debug_read().byte(0x020B), "div16x8_lo");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x020C), "div16x8_hi");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x020D), "div8x16_lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x020E), "div8x16_hi");
+}
+
+#[test]
+pub fn divide_test_optimized() {
+    let emulator = emulate!(optimized: divide_test);
+    assert_eq!(28u8, emulator.memory().debug_read().byte(0x0200), "div8");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "div8_large_divisor");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0202), "constant");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x0203), "variable_by_constant");
+    assert_eq!(200u8, emulator.memory().debug_read().byte(0x0204), "div16_lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0205), "div16_hi");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0206), "div16_large_divisor_lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0207), "div16_large_divisor_hi");
+    assert_eq!(230u8, emulator.memory().debug_read().byte(0x0208), "div16_remainder_lo");
+    assert_eq!(27u8, emulator.memory().debug_read().byte(0x0209), "div16_remainder_hi");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x020A), "div_by_zero");
+    assert_eq!(123u8, emulator.memory().debug_read().byte(0x020B), "div16x8_lo");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x020C), "div16x8_hi");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x020D), "div8x16_lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x020E), "div8x16_hi");
+}
+
+#[test]
+pub fn logical_test_unoptimized() {
+    let emulator = emulate!(unoptimized: logical_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0200), "and");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "or");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0202), "or_false");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0203), "short_circuit");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0204), "while_not");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0205), "and_value");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0206), "or_value");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0207), "in_range");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0208), "nested");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0209), "calls");
+}
+
+#[test]
+pub fn logical_test_optimized() {
+    let emulator = emulate!(optimized: logical_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0200), "and");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "or");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0202), "or_false");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0203), "short_circuit");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0204), "while_not");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0205), "and_value");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0206), "or_value");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0207), "in_range");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0208), "nested");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0209), "calls");
+}
+
+#[test]
+pub fn break_test_unoptimized() {
+    let emulator = emulate!(unoptimized: break_test);
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0200), "break");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0201), "continue");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0202), "nested_break");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0203), "outer_loop");
+}
+
+#[test]
+pub fn break_test_optimized() {
+    let emulator = emulate!(optimized: break_test);
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0200), "break");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0201), "continue");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0202), "nested_break");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0203), "outer_loop");
+}
+
+#[test]
+pub fn break_outside_of_loop_test() {
+    let program = "def main(): void\n    if 1 then\n        break;\n    end\nend\n";
+    match compile("break_outside_of_loop_test", program, false, false) {
+        Ok(_) => panic!("break outside of a loop should fail to compile"),
+        Err(err) => assert_eq!(
+            "break_outside_of_loop_test:3:9: \"break\" can only be used inside of a loop",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn bitwise_test_unoptimized() {
+    let emulator = emulate!(unoptimized: bitwise_test);
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0200), "and");
+    assert_eq!(207u8, emulator.memory().debug_read().byte(0x0201), "or");
+    assert_eq!(195u8, emulator.memory().debug_read().byte(0x0202), "xor");
+    assert_eq!(51u8, emulator.memory().debug_read().byte(0x0203), "not");
+    assert_eq!(240u8, emulator.memory().debug_read().byte(0x0204), "shift_left");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0205), "shift_right");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0206), "and_u16 lo");
+    assert_eq!(19u8, emulator.memory().debug_read().byte(0x0207), "and_u16 hi");
+    assert_eq!(64u8, emulator.memory().debug_read().byte(0x0208), "shift_left_u16 lo");
+    assert_eq!(35u8, emulator.memory().debug_read().byte(0x0209), "shift_left_u16 hi");
+    assert_eq!(70u8, emulator.memory().debug_read().byte(0x020A), "shift_right_u16 lo");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x020B), "shift_right_u16 hi");
+    assert_eq!(203u8, emulator.memory().debug_read().byte(0x020C), "shift_call lo");
+    assert_eq!(133u8, emulator.memory().debug_read().byte(0x020D), "shift_call hi");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x020E), "flag");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x020F), "precedence");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0210), "precedence_bitwise");
+}
+
+#[test]
+pub fn bitwise_test_optimized() {
+    let emulator = emulate!(optimized: bitwise_test);
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0200), "and");
+    assert_eq!(207u8, emulator.memory().debug_read().byte(0x0201), "or");
+    assert_eq!(195u8, emulator.memory().debug_read().byte(0x0202), "xor");
+    assert_eq!(51u8, emulator.memory().debug_read().byte(0x0203), "not");
+    assert_eq!(240u8, emulator.memory().debug_read().byte(0x0204), "shift_left");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0205), "shift_right");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0206), "and_u16 lo");
+    assert_eq!(19u8, emulator.memory().debug_read().byte(0x0207), "and_u16 hi");
+    assert_eq!(64u8, emulator.memory().debug_read().byte(0x0208), "shift_left_u16 lo");
+    assert_eq!(35u8, emulator.memory().debug_read().byte(0x0209), "shift_left_u16 hi");
+    assert_eq!(70u8, emulator.memory().debug_read().byte(0x020A), "shift_right_u16 lo");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x020B), "shift_right_u16 hi");
+    assert_eq!(203u8, emulator.memory().debug_read().byte(0x020C), "shift_call lo");
+    assert_eq!(133u8, emulator.memory().debug_read().byte(0x020D), "shift_call hi");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x020E), "flag");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x020F), "precedence");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0210), "precedence_bitwise");
+}
+
+#[test]
+pub fn signed_test_unoptimized() {
+    let emulator = emulate!(unoptimized: signed_test);
+    assert_eq!(251u8, emulator.memory().debug_read().byte(0x0200), "negated");
+    assert_eq!(248u8, emulator.memory().debug_read().byte(0x0201), "sum");
+    assert_eq!(221u8, emulator.memory().debug_read().byte(0x0202), "product");
+    assert_eq!(242u8, emulator.memory().debug_read().byte(0x0203), "quotient");
+    assert_eq!(231u8, emulator.memory().debug_read().byte(0x0204), "shifted");
+    assert_eq!(23u8, emulator.memory().debug_read().byte(0x0205), "compares");
+    assert_eq!(11u8, emulator.memory().debug_read().byte(0x0206), "compares16");
+    assert_eq!(156u8, emulator.memory().debug_read().byte(0x0208), "extended lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x0209), "extended hi");
+    assert_eq!(112u8, emulator.memory().debug_read().byte(0x020A), "widened_sum lo");
+    assert_eq!(254u8, emulator.memory().debug_read().byte(0x020B), "widened_sum hi");
+    assert_eq!(214u8, emulator.memory().debug_read().byte(0x020C), "quotient16 lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x020D), "quotient16 hi");
+    assert_eq!(218u8, emulator.memory().debug_read().byte(0x020E), "shifted16 lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x020F), "shifted16 hi");
+    assert_eq!(56u8, emulator.memory().debug_read().byte(0x0210), "passed lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x0211), "passed hi");
+    assert_eq!(44u8, emulator.memory().debug_read().byte(0x0212), "negated16 lo");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0213), "negated16 hi");
+}
+
+#[test]
+pub fn signed_test_optimized() {
+    let emulator = emulate!(optimized: signed_test);
+    assert_eq!(251u8, emulator.memory().debug_read().byte(0x0200), "negated");
+    assert_eq!(248u8, emulator.memory().debug_read().byte(0x0201), "sum");
+    assert_eq!(221u8, emulator.memory().debug_read().byte(0x0202), "product");
+    assert_eq!(242u8, emulator.memory().debug_read().byte(0x0203), "quotient");
+    assert_eq!(231u8, emulator.memory().debug_read().byte(0x0204), "shifted");
+    assert_eq!(23u8, emulator.memory().debug_read().byte(0x0205), "compares");
+    assert_eq!(11u8, emulator.memory().debug_read().byte(0x0206), "compares16");
+    assert_eq!(156u8, emulator.memory().debug_read().byte(0x0208), "extended lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x0209), "extended hi");
+    assert_eq!(112u8, emulator.memory().debug_read().byte(0x020A), "widened_sum lo");
+    assert_eq!(254u8, emulator.memory().debug_read().byte(0x020B), "widened_sum hi");
+    assert_eq!(214u8, emulator.memory().debug_read().byte(0x020C), "quotient16 lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x020D), "quotient16 hi");
+    assert_eq!(218u8, emulator.memory().debug_read().byte(0x020E), "shifted16 lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x020F), "shifted16 hi");
+    assert_eq!(56u8, emulator.memory().debug_read().byte(0x0210), "passed lo");
+    assert_eq!(255u8, emulator.memory().debug_read().byte(0x0211), "passed hi");
+    assert_eq!(44u8, emulator.memory().debug_read().byte(0x0212), "negated16 lo");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0213), "negated16 hi");
+}
+
+#[test]
+pub fn negate_pointer_test() {
+    let program = "def main(): void\n    var a: &[u8] = 1;\n    var b: u16 = -a;\nend\n";
+    match compile("negate_pointer_test", program, false, false) {
+        Ok(_) => panic!("negating a pointer should fail to compile"),
+        Err(err) => assert_eq!(
+            "negate_pointer_test:3:19: Expected an integer, but got &[u8]",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn duplicate_case_test() {
+    let program = "def main(): void\n    var a: u8 = 1;\n    match a\n    case 1, 2 then\n        a = 2;\n    \
+                   case 2 then\n        a = 3;\n    end\nend\n";
+    match compile("duplicate_case_test", program, false, false) {
+        Ok(_) => panic!("matching the same value twice should fail to compile"),
+        Err(err) => assert_eq!(
+            "duplicate_case_test:6:10: Duplicate case for value 2",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn match_number_on_enum_test() {
+    let program = "enum Dir: u8 { Up, Down }\ndef main(): void\n    var dir: Dir = Dir.Up;\n    match dir\n    \
+                   case 1 then\n        dir = Dir.Up;\n    end\nend\n";
+    match compile("match_number_on_enum_test", program, false, false) {
+        Ok(_) => panic!("matching an enum against a number should fail to compile"),
+        Err(err) => assert_eq!(
+            "match_number_on_enum_test:5:10: Can't match u8 against Dir",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn match_enum_on_number_test() {
+    let program = "enum Dir: u8 { Up, Down }\ndef main(): void\n    var a: u8 = 1;\n    match a\n    \
+                   case Dir.Down then\n        a = 2;\n    end\nend\n";
+    match compile("match_enum_on_number_test", program, false, false) {
+        Ok(_) => panic!("matching a u8 against an enum variant should fail to compile"),
+        Err(err) => assert_eq!(
+            "match_enum_on_number_test:5:10: Can't match Dir against u8",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn out_of_static_ram_test() {
+    let program = "static big: [u8; 1000];\nstatic bigger: [u8; 1000];\n";
+    match compile("out_of_static_ram_test", program, false, false) {
+        Ok(_) => panic!("statics that don't fit in RAM should fail to compile"),
+        Err(err) => assert_eq!(
+            "Static variables need 2000 bytes of RAM, but only 1280 bytes are available",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn out_of_static_ram_around_memory_test() {
+    let program = "memory screen: [u8; 256] @ 0x0400;\nstatic big: [u8; 1100];\n";
+    match compile("out_of_static_ram_around_memory_test", program, false, false) {
+        Ok(_) => panic!("statics that don't fit around declared memory should fail to compile"),
+        Err(err) => assert_eq!(
+            "Static variables need 1100 bytes of RAM, but only 1024 bytes are available",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn struct_test_unoptimized() {
+    let emulator = emulate!(unoptimized: struct_test);
+    assert_eq!(13u8, emulator.memory().debug_read().byte(0x0200), "player.position.x");
+    assert_eq!(15u8, emulator.memory().debug_read().byte(0x0201), "player.position.y");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0202), "player.tile");
+    assert_eq!(53u8, emulator.memory().debug_read().byte(0x0203), "player.speed lo");
+    assert_eq!(19u8, emulator.memory().debug_read().byte(0x0204), "player.speed hi");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0206), "copied.x");
+    assert_eq!(15u8, emulator.memory().debug_read().byte(0x0207), "copied.y");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0208), "sum");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x020A), "local_speed lo");
+    assert_eq!(67u8, emulator.memory().debug_read().byte(0x020B), "local_speed hi");
+    assert_eq!(13u8, emulator.memory().debug_read().byte(0x0211), "sprites.position.y");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0212), "sprites.tile");
+}
+
+#[test]
+pub fn struct_test_optimized() {
+    let emulator = emulate!(optimized: struct_test);
+    assert_eq!(13u8, emulator.memory().debug_read().byte(0x0200), "player.position.x");
+    assert_eq!(15u8, emulator.memory().debug_read().byte(0x0201), "player.position.y");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0202), "player.tile");
+    assert_eq!(53u8, emulator.memory().debug_read().byte(0x0203), "player.speed lo");
+    assert_eq!(19u8, emulator.memory().debug_read().byte(0x0204), "player.speed hi");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0206), "copied.x");
+    assert_eq!(15u8, emulator.memory().debug_read().byte(0x0207), "copied.y");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0208), "sum");
+    assert_eq!(33u8, emulator.memory().debug_read().byte(0x020A), "local_speed lo");
+    assert_eq!(67u8, emulator.memory().debug_read().byte(0x020B), "local_speed hi");
+    assert_eq!(13u8, emulator.memory().debug_read().byte(0x0211), "sprites.position.y");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0212), "sprites.tile");
+}
+
+#[test]
+pub fn fixed_array_test_unoptimized() {
+    let emulator = emulate!(unoptimized: fixed_array_test);
+    assert_eq!('h' as u8, emulator.memory().debug_read().byte(0x0200), "output[0]");
+    assert_eq!('o' as u8, emulator.memory().debug_read().byte(0x0204), "output[4]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0205), "output[5]");
+    assert_eq!('H' as u8, emulator.memory().debug_read().byte(0x0208), "partial[0]");
+    assert_eq!('e' as u8, emulator.memory().debug_read().byte(0x0209), "partial[1]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0300), "table[0]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0301), "table[1]");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0302), "table[2]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0303), "table[3]");
+    assert_eq!('o' as u8, emulator.memory().debug_read().byte(0x0304), "table[4]");
+    assert_eq!(109u8, emulator.memory().debug_read().byte(0x0210), "sum");
+}
+
+#[test]
+pub fn fixed_array_test_optimized() {
+    let emulator = emulate!(optimized: fixed_array_test);
+    assert_eq!('h' as u8, emulator.memory().debug_read().byte(0x0200), "output[0]");
+    assert_eq!('o' as u8, emulator.memory().debug_read().byte(0x0204), "output[4]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0205), "output[5]");
+    assert_eq!('H' as u8, emulator.memory().debug_read().byte(0x0208), "partial[0]");
+    assert_eq!('e' as u8, emulator.memory().debug_read().byte(0x0209), "partial[1]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0300), "table[0]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0301), "table[1]");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0302), "table[2]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0303), "table[3]");
+    assert_eq!('o' as u8, emulator.memory().debug_read().byte(0x0304), "table[4]");
+    assert_eq!(109u8, emulator.memory().debug_read().byte(0x0210), "sum");
+}
+
+#[test]
+pub fn large_local_test() {
+    let program = "def main(): void\n    var buf: [u8; 200];\nend\n";
+    match compile("large_local_test", program, false, false) {
+        Ok(_) => panic!("a local larger than a frame should fail to compile"),
+        Err(err) => assert_eq!(
+            "large_local_test:2:5: Locals need a 200 byte frame, but frames can hold at most 127 bytes",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn large_frame_test() {
+    let program = "def main(a: u16): void\n    var first: [u8; 100];\n    var second: [u8; 30];\nend\n";
+    match compile("large_frame_test", program, false, false) {
+        Ok(_) => panic!("locals that don't fit in a frame should fail to compile"),
+        Err(err) => assert_eq!(
+            "large_frame_test:3:5: Locals need a 132 byte frame, but frames can hold at most 127 bytes",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn out_of_frame_test() {
+    let program = "def main(a: u16, b: u16): u16\n    var buf: [u8; 122];\n    return a * b + a;\nend\n";
+    match compile("out_of_frame_test", program, false, false) {
+        Ok(_) => panic!("temporaries that don't fit in a frame should fail to compile"),
+        Err(err) => assert_eq!(
+            "Function \"main\" needs a 133 byte frame, but frames can hold at most 127 bytes",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn const_table_test_unoptimized() {
+    let emulator = emulate!(unoptimized: const_table_test);
+    let expected_squares = [0u8, 1, 4, 9, 16, 25];
+    for (index, expected) in expected_squares.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0200 + index as u16));
+    }
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0208));
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0209));
+    assert_eq!(0xCDu8, emulator.memory().debug_read().byte(0x020A));
+    assert_eq!(0xABu8, emulator.memory().debug_read().byte(0x020B));
+    assert_eq!(34u8, emulator.memory().debug_read().byte(0x0210));
+    assert_eq!(0xFCu8, emulator.memory().debug_read().byte(0x0211));
+    assert_eq!(0xEFu8, emulator.memory().debug_read().byte(0x0214), "wide_second lo");
+    assert_eq!(0xBEu8, emulator.memory().debug_read().byte(0x0215), "wide_second hi");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0216), "last_out");
+}
+
+#[test]
+pub fn const_table_test_optimized() {
+    let emulator = emulate!(optimized: const_table_test);
+    let expected_squares = [0u8, 1, 4, 9, 16, 25];
+    for (index, expected) in expected_squares.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0200 + index as u16));
+    }
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0208));
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0209));
+    assert_eq!(0xCDu8, emulator.memory().debug_read().byte(0x020A));
+    assert_eq!(0xABu8, emulator.memory().debug_read().byte(0x020B));
+    assert_eq!(34u8, emulator.memory().debug_read().byte(0x0210));
+    assert_eq!(0xFCu8, emulator.memory().debug_read().byte(0x0211));
+    assert_eq!(0xEFu8, emulator.memory().debug_read().byte(0x0214), "wide_second lo");
+    assert_eq!(0xBEu8, emulator.memory().debug_read().byte(0x0215), "wide_second hi");
+    assert_eq!(25u8, emulator.memory().debug_read().byte(0x0216), "last_out");
+}
+
+#[test]
+pub fn const_table_arithmetic_test() {
+    let program = "const squares: &[u8] = [0, 1, 4];\nconst second: &[u8] = squares + 1;\n";
+    match compile("const_table_arithmetic_test", program, false, false) {
+        Ok(_) => panic!("offsetting a table that isn't placed yet should fail to compile"),
+        Err(err) => assert_eq!(
+            "const_table_arithmetic_test:2:23: Constant evaluation failed",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn u16_array_test_unoptimized() {
+    let emulator = emulate!(unoptimized: u16_array_test);
+    let expected = [
+        (0x0200, 0x0100u16, "words[0]"),
+        (0x0202, 0x0101u16, "words[1]"),
+        (0x0204, 0x0102u16, "words[2]"),
+        (0x0210, 0x1235u16, "copied[0]"),
+        (0x0212, 0xABCEu16, "copied[1]"),
+        (0x0214, 0x0F10u16, "copied[2]"),
+        (0x0220, 0xCD10u16, "total"),
+        (0x0222, 0x0F0Fu16, "last"),
+    ];
+    for &(addr, value, name) in expected.iter() {
+        assert_eq!(value as u8, emulator.memory().debug_read().byte(addr), "{} lo", name);
+        assert_eq!((value >> 8) as u8, emulator.memory().debug_read().byte(addr + 1), "{} hi", name);
+    }
+}
+
+#[test]
+pub fn u16_array_test_optimized() {
+    let emulator = emulate!(optimized: u16_array_test);
+    let expected = [
+        (0x0200, 0x0100u16, "words[0]"),
+        (0x0202, 0x0101u16, "words[1]"),
+        (0x0204, 0x0102u16, "words[2]"),
+        (0x0210, 0x1235u16, "copied[0]"),
+        (0x0212, 0xABCEu16, "copied[1]"),
+        (0x0214, 0x0F10u16, "copied[2]"),
+        (0x0220, 0xCD10u16, "total"),
+        (0x0222, 0x0F0Fu16, "last"),
+    ];
+    for &(addr, value, name) in expected.iter() {
+        assert_eq!(value as u8, emulator.memory().debug_read().byte(addr), "{} lo", name);
+        assert_eq!((value >> 8) as u8, emulator.memory().debug_read().byte(addr + 1), "{} hi", name);
+    }
+}
+
+/// Without room for the zero page pointer, each byte of a u16 is reached through its own pointer in the frame
+#[test]
+pub fn u16_array_frame_pointer_test() {
+    let emulator = run_test_with_options(
+        "u16_array_frame_pointer_test",
+        include_bytes!("./u16_array_test.hsl"),
+        compiler_options(false, false)
+            .zero_page_start(0xFF)
+            .zero_page_end(0xFF)
+            .build()
+            .unwrap(),
+    );
+    let expected = [
+        (0x0200, 0x0100u16, "words[0]"),
+        (0x0202, 0x0101u16, "words[1]"),
+        (0x0204, 0x0102u16, "words[2]"),
+        (0x0210, 0x1235u16, "copied[0]"),
+        (0x0212, 0xABCEu16, "copied[1]"),
+        (0x0214, 0x0F10u16, "copied[2]"),
+        (0x0220, 0xCD10u16, "total"),
+        (0x0222, 0x0F0Fu16, "last"),
+    ];
+    for &(addr, value, name) in expected.iter() {
+        assert_eq!(value as u8, emulator.memory().debug_read().byte(addr), "{} lo", name);
+        assert_eq!((value >> 8) as u8, emulator.memory().debug_read().byte(addr + 1), "{} hi", name);
+    }
+}
+
+#[test]
+pub fn cast_test_unoptimized() {
+    let emulator = emulate!(unoptimized: cast_test);
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0200), "truncated");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0201), "high");
+    assert_eq!(0x2Cu8, emulator.memory().debug_read().byte(0x0202), "extended lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0203), "extended hi");
+    assert_eq!(0xFEu8, emulator.memory().debug_read().byte(0x0204), "sign_extended lo");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0205), "sign_extended hi");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0206), "literal");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0207), "pointed");
+    assert_eq!(198u8, emulator.memory().debug_read().byte(0x0208), "signed_sum lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0209), "signed_sum hi");
+}
+
+#[test]
+pub fn cast_test_optimized() {
+    let emulator = emulate!(optimized: cast_test);
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0200), "truncated");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0201), "high");
+    assert_eq!(0x2Cu8, emulator.memory().debug_read().byte(0x0202), "extended lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0203), "extended hi");
+    assert_eq!(0xFEu8, emulator.memory().debug_read().byte(0x0204), "sign_extended lo");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0205), "sign_extended hi");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0206), "literal");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0207), "pointed");
+    assert_eq!(198u8, emulator.memory().debug_read().byte(0x0208), "signed_sum lo");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0209), "signed_sum hi");
+}
+
+#[test]
+pub fn unary_test_unoptimized() {
+    let emulator = emulate!(unoptimized: unary_test);
+    assert_eq!(251u8, emulator.memory().debug_read().byte(0x0200), "negated");
+    assert_eq!(0x00u8, emulator.memory().debug_read().byte(0x0202), "negated16 lo");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0203), "negated16 hi");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0204), "inverted16 lo");
+    assert_eq!(0xFEu8, emulator.memory().debug_read().byte(0x0205), "inverted16 hi");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0206), "not_zero");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0207), "not_five");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0208), "not_word");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0209), "branched");
+}
+
+#[test]
+pub fn unary_test_optimized() {
+    let emulator = emulate!(optimized: unary_test);
+    assert_eq!(251u8, emulator.memory().debug_read().byte(0x0200), "negated");
+    assert_eq!(0x00u8, emulator.memory().debug_read().byte(0x0202), "negated16 lo");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0203), "negated16 hi");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0204), "inverted16 lo");
+    assert_eq!(0xFEu8, emulator.memory().debug_read().byte(0x0205), "inverted16 hi");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0206), "not_zero");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0207), "not_five");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0208), "not_word");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0209), "branched");
+}
+
+#[test]
+pub fn compound_assignment_test_unoptimized() {
+    let emulator = emulate!(unoptimized: compound_assignment_test);
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0200), "counted");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "counted_down");
+    assert_eq!(253u8, emulator.memory().debug_read().byte(0x0202), "total");
+    assert_eq!(0x07u8, emulator.memory().debug_read().byte(0x0203), "bits");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0204), "product");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0206), "word lo");
+    assert_eq!(0x02u8, emulator.memory().debug_read().byte(0x0207), "word hi");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0208), "elements[0]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0209), "elements[1]");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x020A), "calls");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x020B), "slots[0]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x020C), "slots[1]");
+    assert_eq!(0x31u8, emulator.memory().debug_read().byte(0x020D), "slots[2]");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x020E), "slots[3]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0210), "wide[0] lo");
+    assert_eq!(0x00u8, emulator.memory().debug_read().byte(0x0211), "wide[0] hi");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0212), "wide[1] lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0213), "wide[1] hi");
+}
+
+#[test]
+pub fn compound_assignment_test_optimized() {
+    let emulator = emulate!(optimized: compound_assignment_test);
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0200), "counted");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "counted_down");
+    assert_eq!(253u8, emulator.memory().debug_read().byte(0x0202), "total");
+    assert_eq!(0x07u8, emulator.memory().debug_read().byte(0x0203), "bits");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0204), "product");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0206), "word lo");
+    assert_eq!(0x02u8, emulator.memory().debug_read().byte(0x0207), "word hi");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0208), "elements[0]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0209), "elements[1]");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x020A), "calls");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x020B), "slots[0]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x020C), "slots[1]");
+    assert_eq!(0x31u8, emulator.memory().debug_read().byte(0x020D), "slots[2]");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x020E), "slots[3]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0210), "wide[0] lo");
+    assert_eq!(0x00u8, emulator.memory().debug_read().byte(0x0211), "wide[0] hi");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0212), "wide[1] lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0213), "wide[1] hi");
+}
+
+#[test]
+pub fn match_test_unoptimized() {
+    let emulator = emulate!(unoptimized: match_test);
+    let expected_classified = [10u8, 20, 30, 40];
+    for (index, expected) in expected_classified.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0200 + index as u16), "classified[{}]", index);
+    }
+    let expected_sparse = [11u8, 22, 22, 99];
+    for (index, expected) in expected_sparse.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0210 + index as u16), "sparse[{}]", index);
+    }
+    let expected_dense = [0u8, 0, 1, 2, 0, 2, 3, 4, 0, 0];
+    for (index, expected) in expected_dense.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0220 + index as u16), "dense[{}]", index);
+    }
+}
+
+#[test]
+pub fn match_test_optimized() {
+    let emulator = emulate!(optimized: match_test);
+    let expected_classified = [10u8, 20, 30, 40];
+    for (index, expected) in expected_classified.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0200 + index as u16), "classified[{}]", index);
+    }
+    let expected_sparse = [11u8, 22, 22, 99];
+    for (index, expected) in expected_sparse.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0210 + index as u16), "sparse[{}]", index);
+    }
+    let expected_dense = [0u8, 0, 1, 2, 0, 2, 3, 4, 0, 0];
+    for (index, expected) in expected_dense.iter().enumerate() {
+        assert_eq!(*expected, emulator.memory().debug_read().byte(0x0220 + index as u16), "dense[{}]", index);
+    }
+}
+
+#[test]
+pub fn static_test_unoptimized() {
+    let emulator = emulate!(unoptimized: static_test);
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0200), "counted");
+    assert_eq!(0x02u8, emulator.memory().debug_read().byte(0x0202), "total lo");
+    assert_eq!(0x03u8, emulator.memory().debug_read().byte(0x0203), "total hi");
+    assert_eq!(16u8, emulator.memory().debug_read().byte(0x0204), "values[1]");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0205), "late");
+}
+
+#[test]
+pub fn static_test_optimized() {
+    let emulator = emulate!(optimized: static_test);
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0200), "counted");
+    assert_eq!(0x02u8, emulator.memory().debug_read().byte(0x0202), "total lo");
+    assert_eq!(0x03u8, emulator.memory().debug_read().byte(0x0203), "total hi");
+    assert_eq!(16u8, emulator.memory().debug_read().byte(0x0204), "values[1]");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0205), "late");
+}
+
+#[test]
+pub fn static_memory_test_unoptimized() {
+    let emulator = emulate!(unoptimized: static_memory_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0300), "screen[0]");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0303), "screen[3]");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0200), "screen_out");
+    assert_eq!(0x33u8, emulator.memory().debug_read().byte(0x0304), "tiles[0]");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0305), "buffer[0]");
+    assert_eq!(17u8, emulator.memory().debug_read().byte(0x030C), "buffer[7]");
+}
+
+#[test]
+pub fn static_memory_test_optimized() {
+    let emulator = emulate!(optimized: static_memory_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0300), "screen[0]");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0303), "screen[3]");
+    assert_eq!(4u8, emulator.memory().debug_read().byte(0x0200), "screen_out");
+    assert_eq!(0x33u8, emulator.memory().debug_read().byte(0x0304), "tiles[0]");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0305), "buffer[0]");
+    assert_eq!(17u8, emulator.memory().debug_read().byte(0x030C), "buffer[7]");
+}
+
+#[test]
+pub fn static_initializer_test() {
+    let program = "static a: u8 = 1;\nstatic b: u8 = five();\n\ndef five(): u8\n    return 5;\nend\n";
+    match compile("static_initializer_test", program, false, false) {
+        Ok(_) => panic!("initializing a static with a function call should fail to compile"),
+        Err(err) => assert_eq!(
+            "static_initializer_test:2:16: Constant evaluation failed",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn static_ram_range_test() {
+    let compiler_options = hasselc::CompilerOptionsBuilder::default()
+        .static_ram_start(0x0900u16)
+        .static_ram_end(0x07FFu16)
+        .build()
+        .unwrap();
+    match compile_with_options("static_ram_range_test", "static a: u8;\n", compiler_options) {
+        Ok(_) => panic!("a static RAM range that starts after it ends should fail to compile"),
+        Err(err) => assert_eq!(
+            "Static RAM range $0900-$07FF starts after it ends",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn zero_page_test_unoptimized() {
+    let emulator = emulate!(unoptimized: zero_page_test);
+    assert_eq!(8u8, emulator.memory().debug_read().byte(0x0200), "moved.x");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0201), "moved.y");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0202), "fast");
+    assert_eq!(0x18u8, emulator.memory().debug_read().byte(0x0203), "hot lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0204), "hot hi");
+    assert_eq!(14u8, emulator.memory().debug_read().byte(0x0205), "sum");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0080), "fast in zero page");
+    assert_eq!(0x5Au8, emulator.memory().debug_read().byte(0x0206), "flags");
+    assert_eq!(0xA5u8, emulator.memory().debug_read().byte(0x0207), "scratch[0]");
+}
+
+#[test]
+pub fn zero_page_test_optimized() {
+    let emulator = emulate!(optimized: zero_page_test);
+    assert_eq!(8u8, emulator.memory().debug_read().byte(0x0200), "moved.x");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0201), "moved.y");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0202), "fast");
+    assert_eq!(0x18u8, emulator.memory().debug_read().byte(0x0203), "hot lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0204), "hot hi");
+    assert_eq!(14u8, emulator.memory().debug_read().byte(0x0205), "sum");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0080), "fast in zero page");
+    assert_eq!(0x5Au8, emulator.memory().debug_read().byte(0x0206), "flags");
+    assert_eq!(0xA5u8, emulator.memory().debug_read().byte(0x0207), "scratch[0]");
+}
+
+#[test]
+pub fn pointer_test_unoptimized() {
+    let emulator = emulate!(unoptimized: pointer_test);
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0200), "counter");
+    assert_eq!(0x35u8, emulator.memory().debug_read().byte(0x0201), "local lo");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0202), "local hi");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0203), "bumped");
+    assert_eq!(42u8, emulator.memory().debug_read().byte(0x0204), "table[2]");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0205), "position.y");
+    assert_eq!(0xEFu8, emulator.memory().debug_read().byte(0x0206), "read_back lo");
+    assert_eq!(0xBEu8, emulator.memory().debug_read().byte(0x0207), "read_back hi");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0212), "words[1] lo");
+    assert_eq!(0x11u8, emulator.memory().debug_read().byte(0x0213), "words[1] hi");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0221), "cursor[1]");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0222), "cursor[2]");
+}
+
+#[test]
+pub fn pointer_test_optimized() {
+    let emulator = emulate!(optimized: pointer_test);
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0200), "counter");
+    assert_eq!(0x35u8, emulator.memory().debug_read().byte(0x0201), "local lo");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0202), "local hi");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0203), "bumped");
+    assert_eq!(42u8, emulator.memory().debug_read().byte(0x0204), "table[2]");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0205), "position.y");
+    assert_eq!(0xEFu8, emulator.memory().debug_read().byte(0x0206), "read_back lo");
+    assert_eq!(0xBEu8, emulator.memory().debug_read().byte(0x0207), "read_back hi");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0212), "words[1] lo");
+    assert_eq!(0x11u8, emulator.memory().debug_read().byte(0x0213), "words[1] hi");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0221), "cursor[1]");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0222), "cursor[2]");
+}
+
+#[test]
+pub fn pointer_arithmetic_type_test() {
+    let program = "def main(): void\n    var a: &[u8] = 1;\n    var b: &[u8] = a * 2;\nend\n";
+    match compile("pointer_arithmetic_type_test", program, false, false) {
+        Ok(_) => panic!("multiplying a pointer should fail to compile"),
+        Err(err) => assert_eq!(
+            "pointer_arithmetic_type_test:3:20: Can't perform arithmetic between &[u8] and u16",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn dereference_type_test() {
+    let program = "def main(): void\n    var a: u16 = 1;\n    var b: u8 = *a;\nend\n";
+    match compile("dereference_type_test", program, false, false) {
+        Ok(_) => panic!("dereferencing a non-pointer should fail to compile"),
+        Err(err) => assert_eq!(
+            "dereference_type_test:3:17: Can only dereference pointers, but got u16",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn function_pointer_test_unoptimized() {
+    let emulator = emulate!(unoptimized: function_pointer_test);
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0200), "direct_out");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0201), "applied_out");
+    assert_eq!(0x68u8, emulator.memory().debug_read().byte(0x0202), "wide_out lo");
+    assert_eq!(0x24u8, emulator.memory().debug_read().byte(0x0203), "wide_out hi");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0204), "ticks");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0205), "steps_out");
+    assert_eq!(21u8, emulator.memory().debug_read().byte(0x0210), "results[0]");
+    assert_eq!(18u8, emulator.memory().debug_read().byte(0x0211), "results[1]");
+    assert_eq!(23u8, emulator.memory().debug_read().byte(0x0212), "results[2]");
+}
+
+#[test]
+pub fn function_pointer_test_optimized() {
+    let emulator = emulate!(optimized: function_pointer_test);
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0200), "direct_out");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0201), "applied_out");
+    assert_eq!(0x68u8, emulator.memory().debug_read().byte(0x0202), "wide_out lo");
+    assert_eq!(0x24u8, emulator.memory().debug_read().byte(0x0203), "wide_out hi");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0204), "ticks");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0205), "steps_out");
+    assert_eq!(21u8, emulator.memory().debug_read().byte(0x0210), "results[0]");
+    assert_eq!(18u8, emulator.memory().debug_read().byte(0x0211), "results[1]");
+    assert_eq!(23u8, emulator.memory().debug_read().byte(0x0212), "results[2]");
+}
+
+#[test]
+pub fn call_type_test() {
+    let program = "def main(): void\n    var a: u8 = 1;\n    a(2);\nend\n";
+    match compile("call_type_test", program, false, false) {
+        Ok(_) => panic!("calling a non-function should fail to compile"),
+        Err(err) => assert_eq!(
+            "call_type_test:3:5: Can only call functions, but got u8",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn function_table_type_test() {
+    let program = "def f(a: u8): void\n    return;\nend\nconst t: &[fn(): void] = [f];\n";
+    match compile("function_table_type_test", program, false, false) {
+        Ok(_) => panic!("a function of the wrong type in a table should fail to compile"),
+        Err(err) => assert_eq!(
+            "function_table_type_test:4:27: Can't put fn(u8): void in a table of fn(): void",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+fn compile_with_zero_page(name: &str, program: &str, start: u8, end: u8) -> error::Result<hasselc::CompilerOutput> {
+    let compiler_options = hasselc::CompilerOptionsBuilder::default()
+        .zero_page_start(start)
+        .zero_page_end(end)
+        .build()
+        .unwrap();
+    let mut compiler = hasselc::Compiler::new(compiler_options);
+    compiler.parse_unit(name, program)?;
+    compiler.compile()
+}
+
+#[test]
+pub fn zero_page_conflict_test() {
+    match compile_with_zero_page("zero_page_conflict_test", "static a: u8;\n", 0x00, 0x7F) {
+        Ok(_) => panic!("a zero page range that overlaps the data stack pointer should fail to compile"),
+        Err(err) => assert_eq!(
+            "Zero page range $00-$7F overlaps the data stack pointer at $00",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn out_of_zero_page_test() {
+    let program = "zp static a: u16;\nzp static b: u8;\n";
+    match compile_with_zero_page("out_of_zero_page_test", program, 0xFE, 0xFF) {
+        Ok(_) => panic!("zp statics that don't fit in zero page should fail to compile"),
+        Err(err) => assert_eq!(
+            "Not enough zero page left to place static variable \"b\"",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn data_stack_conflict_test() {
+    match compile_with_zero_page("data_stack_conflict_test", "static a: u8;\n", 0x40, 0xFF) {
+        Ok(_) => panic!("a zero page range that overlaps the data stack should fail to compile"),
+        Err(err) => assert_eq!(
+            "Zero page range $40-$FF overlaps the data stack at $40",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn inline_test_unoptimized() {
+    let emulator = emulate!(unoptimized: inline_test);
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0200), "status");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0201), "clamped_out");
+    assert_eq!(17u8, emulator.memory().debug_read().byte(0x0202), "sum_out");
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0203), "wide_out lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0204), "wide_out hi");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0205), "count_out");
+}
+
+#[test]
+pub fn inline_test_optimized() {
+    let emulator = emulate!(optimized: inline_test);
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0200), "status");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0201), "clamped_out");
+    assert_eq!(17u8, emulator.memory().debug_read().byte(0x0202), "sum_out");
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0203), "wide_out lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0204), "wide_out hi");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0205), "count_out");
+}
+
+#[test]
+pub fn inline_return_in_loop_test() {
+    let program = "inline def f(): u8\n    while 1 == 1 do\n        return 1;\n    end\n    return 0;\nend\n";
+    match compile("inline_return_in_loop_test", program, false, false) {
+        Ok(_) => panic!("returning from a loop in an inline function should fail to compile"),
+        Err(err) => assert_eq!(
+            "inline_return_in_loop_test:3:9: Inline function f can't return from inside of a loop",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn interrupt_test_unoptimized() {
+    let emulator = run_test_with_options(
+        "interrupt_test_unoptimized",
+        include_bytes!("./interrupt_test.hsl"),
+        compiler_options(false, false)
+            .vector_irq_label(Some("irq".to_string()))
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0200), "interrupts");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0201), "sum_out");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0202), "handler_out");
+}
+
+#[test]
+pub fn interrupt_test_optimized() {
+    let emulator = run_test_with_options(
+        "interrupt_test_optimized",
+        include_bytes!("./interrupt_test.hsl"),
+        compiler_options(true, true)
+            .vector_irq_label(Some("irq".to_string()))
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0200), "interrupts");
+    assert_eq!(9u8, emulator.memory().debug_read().byte(0x0201), "sum_out");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0202), "handler_out");
+}
+
+#[test]
+pub fn interrupt_call_test() {
+    let program = "interrupt def irq(): void\n    return;\nend\ndef main(): void\n    irq();\nend\n";
+    match compile("interrupt_call_test", program, false, false) {
+        Ok(_) => panic!("calling an interrupt handler should fail to compile"),
+        Err(err) => assert_eq!(
+            "interrupt_call_test:5:5: Can't call interrupt handler irq",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn interrupt_address_test() {
+    let program = "interrupt def irq(): void\n    return;\nend\ndef main(): void\n    var f: fn(): void = irq;\nend\n";
+    match compile("interrupt_address_test", program, false, false) {
+        Ok(_) => panic!("taking the address of an interrupt handler should fail to compile"),
+        Err(err) => assert_eq!(
+            "interrupt_address_test:5:25: Can't take the address of interrupt handler irq",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn interrupt_parameters_test() {
+    let program = "interrupt def irq(a: u8): void\n    return;\nend\n";
+    match compile("interrupt_parameters_test", program, false, false) {
+        Ok(_) => panic!("an interrupt handler with parameters should fail to compile"),
+        Err(err) => assert_eq!(
+            "interrupt_parameters_test:1:1: Interrupt handlers can't take parameters",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn import_test_unoptimized() {
+    let emulator = run_test_with_options(
+        "import_test_unoptimized",
+        include_bytes!("./import_test.hsl"),
+        compiler_options(false, false)
+            .import_paths(vec![PathBuf::from("tests")])
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0200), "sum_out");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0201), "product_out");
+}
+
+#[test]
+pub fn import_test_optimized() {
+    let emulator = run_test_with_options(
+        "import_test_optimized",
+        include_bytes!("./import_test.hsl"),
+        compiler_options(true, true)
+            .import_paths(vec![PathBuf::from("tests")])
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0200), "sum_out");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0201), "product_out");
+}
+
+#[test]
+pub fn import_missing_test() {
+    let program = "import \"imports/missing.hsl\";\n";
+    let options = compiler_options(false, false)
+        .import_paths(vec![PathBuf::from("tests")])
+        .build()
+        .unwrap();
+    match compile_with_options("import_missing_test", program, options) {
+        Ok(_) => panic!("importing a file that doesn't exist should fail to compile"),
+        Err(err) => assert_eq!(
+            "import_missing_test:1:1: Couldn't import \"imports/missing.hsl\": file not found",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn import_error_location_test() {
+    let program = "import \"imports/bad_call.hsl\";\n";
+    let options = compiler_options(false, false)
+        .import_paths(vec![PathBuf::from("tests")])
+        .build()
+        .unwrap();
+    match compile_with_options("import_error_location_test", program, options) {
+        Ok(_) => panic!("an error in an imported file should fail to compile"),
+        Err(err) => assert_eq!(
+            "tests/imports/bad_call.hsl:12:5: Can only call functions, but got u8",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn bool_test_unoptimized() {
+    let emulator = emulate!(unoptimized: bool_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0200), "literal_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "const_out");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0202), "param_out");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0203), "loop_out");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0204), "not_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0205), "cast_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0206), "compare_out");
+}
+
+#[test]
+pub fn bool_test_optimized() {
+    let emulator = emulate!(optimized: bool_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0200), "literal_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0201), "const_out");
+    assert_eq!(12u8, emulator.memory().debug_read().byte(0x0202), "param_out");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0203), "loop_out");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0204), "not_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0205), "cast_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0206), "compare_out");
+}
+
+#[test]
+pub fn non_bool_condition_test() {
+    let program = "def main(): void\n    var count: u8 = 3;\n    while count do\n        count -= 1;\n    end\nend\n";
+    match compile("non_bool_condition_test", program, false, false) {
+        Ok(_) => panic!("a u8 condition should fail to compile"),
+        Err(err) => assert_eq!(
+            "non_bool_condition_test:3:11: Condition must be a bool, but got u8",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn bool_const_out_of_range_test() {
+    let program = "const flag: bool = 2;\n";
+    match compile("bool_const_out_of_range_test", program, false, false) {
+        Ok(_) => panic!("a bool constant of 2 should fail to compile"),
+        Err(err) => assert_eq!(
+            "bool_const_out_of_range_test:1:20: Integer value 2 must be between 0 and 1",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn bool_return_type_test() {
+    let program = "def check(value: u16): bool\n    return value;\nend\n";
+    match compile("bool_return_type_test", program, false, false) {
+        Ok(_) => panic!("returning a u16 from a bool function should fail to compile"),
+        Err(err) => assert_eq!(
+            "bool_return_type_test:2:5: Can't return u16 from a function that returns bool",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn literal_test_unoptimized() {
+    let emulator = emulate!(unoptimized: literal_test);
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0200), "binary_out");
+    assert_eq!(0x39u8, emulator.memory().debug_read().byte(0x0201), "separated_out lo");
+    assert_eq!(0x30u8, emulator.memory().debug_read().byte(0x0202), "separated_out hi");
+    assert_eq!(0xEFu8, emulator.memory().debug_read().byte(0x0203), "dollar_out lo");
+    assert_eq!(0xBEu8, emulator.memory().debug_read().byte(0x0204), "dollar_out hi");
+    assert_eq!(65u8, emulator.memory().debug_read().byte(0x0205), "char_out");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0206), "escape_out");
+    assert_eq!(39u8, emulator.memory().debug_read().byte(0x0207), "quote_out");
+    assert_eq!(21u8, emulator.memory().debug_read().byte(0x0208), "match_out");
+    assert_eq!(105u8, emulator.memory().debug_read().byte(0x0209), "table_out");
+}
+
+#[test]
+pub fn literal_test_optimized() {
+    let emulator = emulate!(optimized: literal_test);
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0200), "binary_out");
+    assert_eq!(0x39u8, emulator.memory().debug_read().byte(0x0201), "separated_out lo");
+    assert_eq!(0x30u8, emulator.memory().debug_read().byte(0x0202), "separated_out hi");
+    assert_eq!(0xEFu8, emulator.memory().debug_read().byte(0x0203), "dollar_out lo");
+    assert_eq!(0xBEu8, emulator.memory().debug_read().byte(0x0204), "dollar_out hi");
+    assert_eq!(65u8, emulator.memory().debug_read().byte(0x0205), "char_out");
+    assert_eq!(10u8, emulator.memory().debug_read().byte(0x0206), "escape_out");
+    assert_eq!(39u8, emulator.memory().debug_read().byte(0x0207), "quote_out");
+    assert_eq!(21u8, emulator.memory().debug_read().byte(0x0208), "match_out");
+    assert_eq!(105u8, emulator.memory().debug_read().byte(0x0209), "table_out");
+}
+
+#[test]
+pub fn literal_overflow_test() {
+    let program = "const big: u16 = 0x1_0000;\n";
+    match compile("literal_overflow_test", program, false, false) {
+        Ok(_) => panic!("a literal too big for any type should fail to compile"),
+        Err(err) => assert_eq!(
+            "literal_overflow_test:1:18: Integer value 65536 must be between 0 and 65535",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn long_literal_test() {
+    let program = "const big: u16 = 99_999_999_999_999_999_999_999_999_999;\n";
+    match compile("long_literal_test", program, false, false) {
+        Ok(_) => panic!("a literal too big for any type should fail to compile"),
+        Err(err) => assert!(
+            format!("{}", err.0).ends_with("must be between 0 and 65535"),
+            "unexpected error: {}",
+            err.0
+        ),
+    }
+}
+
+#[test]
+pub fn character_out_of_range_test() {
+    let program = "const euro: u8 = '€';\n";
+    match compile("character_out_of_range_test", program, false, false) {
+        Ok(_) => panic!("a character that doesn't fit in a byte should fail to compile"),
+        Err(err) => assert_eq!(
+            "character_out_of_range_test:1:18: Integer value 8364 must be between 0 and 255",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn enum_test_unoptimized() {
+    let emulator = emulate!(unoptimized: enum_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0200), "turned_out");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0201), "matched_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0202), "same_out");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0203), "mode_out lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0204), "mode_out hi");
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0205), "address_out lo");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0206), "address_out hi");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0210), "tiles[0]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0211), "tiles[1]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0212), "tiles[2]");
+}
+
+#[test]
+pub fn enum_test_optimized() {
+    let emulator = emulate!(optimized: enum_test);
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0200), "turned_out");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0201), "matched_out");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0202), "same_out");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0203), "mode_out lo");
+    assert_eq!(0x01u8, emulator.memory().debug_read().byte(0x0204), "mode_out hi");
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0205), "address_out lo");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0206), "address_out hi");
+    assert_eq!(3u8, emulator.memory().debug_read().byte(0x0210), "tiles[0]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0211), "tiles[1]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0212), "tiles[2]");
+}
+
+#[test]
+pub fn enum_assign_test() {
+    let program = "enum Dir: u8 { Up, Down }\ndef main(): void\n    var value: u8 = Dir.Down;\nend\n";
+    match compile("enum_assign_test", program, false, false) {
+        Ok(_) => panic!("assigning an enum value to a u8 without a cast should fail to compile"),
+        Err(err) => assert_eq!(
+            "enum_assign_test:3:5: Can't assign Dir into u8",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn enum_from_number_test() {
+    let program = "enum Dir: u8 { Up, Down }\ndef main(): void\n    var dir: Dir = 1;\nend\n";
+    match compile("enum_from_number_test", program, false, false) {
+        Ok(_) => panic!("assigning a number to an enum without a cast should fail to compile"),
+        Err(err) => assert_eq!(
+            "enum_from_number_test:3:5: Can't assign u8 into Dir",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn enum_unknown_variant_test() {
+    let program = "enum Dir: u8 { Up, Down }\ndef main(): void\n    var dir: Dir = Dir.Sideways;\nend\n";
+    match compile("enum_unknown_variant_test", program, false, false) {
+        Ok(_) => panic!("an enum variant that doesn't exist should fail to compile"),
+        Err(err) => assert_eq!(
+            "enum_unknown_variant_test:3:20: Symbol not found: \"Dir.Sideways\"",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn byte_test_unoptimized() {
+    let emulator = emulate!(unoptimized: byte_test);
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0200), "literal_out[0]");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0201), "literal_out[1]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0202), "literal_out[2]");
+    assert_eq!(0x2Eu8, emulator.memory().debug_read().byte(0x0203), "literal_out[3]");
+    assert_eq!(0xCDu8, emulator.memory().debug_read().byte(0x0204), "variable_out[0]");
+    assert_eq!(0xABu8, emulator.memory().debug_read().byte(0x0205), "variable_out[1]");
+    assert_eq!(0x42u8, emulator.memory().debug_read().byte(0x0206), "variable_out[2]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0207), "variable_out[3]");
+    assert_eq!(0xABu8, emulator.memory().debug_read().byte(0x0208), "word_out lo");
+    assert_eq!(0xCDu8, emulator.memory().debug_read().byte(0x0209), "word_out hi");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x020A), "signed_out[0]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x020B), "signed_out[1]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0210), "size_out[0]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0211), "size_out[1]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0212), "size_out[2]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0213), "size_out[3]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0214), "size_out[4]");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0215), "size_out[5]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0218), "const_out[0]");
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0219), "const_out[1]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x021A), "const_out[2]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0220), "address_out[0]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0221), "address_out[1]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0222), "address_out[2]");
+}
+
+#[test]
+pub fn byte_test_optimized() {
+    let emulator = emulate!(optimized: byte_test);
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0200), "literal_out[0]");
+    assert_eq!(0x12u8, emulator.memory().debug_read().byte(0x0201), "literal_out[1]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0202), "literal_out[2]");
+    assert_eq!(0x2Eu8, emulator.memory().debug_read().byte(0x0203), "literal_out[3]");
+    assert_eq!(0xCDu8, emulator.memory().debug_read().byte(0x0204), "variable_out[0]");
+    assert_eq!(0xABu8, emulator.memory().debug_read().byte(0x0205), "variable_out[1]");
+    assert_eq!(0x42u8, emulator.memory().debug_read().byte(0x0206), "variable_out[2]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x0207), "variable_out[3]");
+    assert_eq!(0xABu8, emulator.memory().debug_read().byte(0x0208), "word_out lo");
+    assert_eq!(0xCDu8, emulator.memory().debug_read().byte(0x0209), "word_out hi");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x020A), "signed_out[0]");
+    assert_eq!(0u8, emulator.memory().debug_read().byte(0x020B), "signed_out[1]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0210), "size_out[0]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0211), "size_out[1]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0212), "size_out[2]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0213), "size_out[3]");
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0214), "size_out[4]");
+    assert_eq!(5u8, emulator.memory().debug_read().byte(0x0215), "size_out[5]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x0218), "const_out[0]");
+    assert_eq!(0x34u8, emulator.memory().debug_read().byte(0x0219), "const_out[1]");
+    assert_eq!(0xFFu8, emulator.memory().debug_read().byte(0x021A), "const_out[2]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0220), "address_out[0]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0221), "address_out[1]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0222), "address_out[2]");
+}
+
+#[test]
+pub fn word_byte_type_test() {
+    let program = "def main(): void\n    var value: u16 = 0x1234;\n    var other: u16 = word(value, 0);\nend\n";
+    match compile("word_byte_type_test", program, false, false) {
+        Ok(_) => panic!("building a word out of a u16 should fail to compile"),
+        Err(err) => assert_eq!(
+            "word_byte_type_test:3:27: Expected a u8 byte, but got u16",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn byte_operator_arguments_test() {
+    let program = "def main(): void\n    var value: u8 = lo(1, 2);\nend\n";
+    match compile("byte_operator_arguments_test", program, false, false) {
+        Ok(_) => panic!("calling lo with two arguments should fail to compile"),
+        Err(err) => assert_eq!(
+            "byte_operator_arguments_test:2:21: In function call to \"lo\", expected 1 arguments, got 2",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn intrinsic_test_unoptimized() {
+    let emulator = run_test_with_options(
+        "intrinsic_test_unoptimized",
+        include_bytes!("./intrinsic_test.hsl"),
+        compiler_options(false, false)
+            .vector_irq_label(Some("irq".to_string()))
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0200), "interrupts");
+    assert_eq!(0x81u8, emulator.memory().debug_read().byte(0x0201), "rotated_out[0]");
+    assert_eq!(0x88u8, emulator.memory().debug_read().byte(0x0202), "rotated_out[1]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0203), "rotated_out[2]");
+    assert_eq!(0x40u8, emulator.memory().debug_read().byte(0x0204), "rotated_out[3]");
+    assert_eq!(0x03u8, emulator.memory().debug_read().byte(0x0205), "wide_out lo");
+    assert_eq!(0x80u8, emulator.memory().debug_read().byte(0x0206), "wide_out hi");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0210), "poked[0]");
+    assert_eq!(8u8, emulator.memory().debug_read().byte(0x0211), "poked[1]");
+    assert_eq!(16u8, emulator.memory().debug_read().byte(0x0212), "poked[2]");
+    assert_eq!(16u8, emulator.memory().debug_read().byte(0x0218), "peeked_out[0]");
+    assert_eq!(23u8, emulator.memory().debug_read().byte(0x0219), "peeked_out[1]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0220), "counter_out");
+}
+
+#[test]
+pub fn intrinsic_test_optimized() {
+    let emulator = run_test_with_options(
+        "intrinsic_test_optimized",
+        include_bytes!("./intrinsic_test.hsl"),
+        compiler_options(true, true)
+            .vector_irq_label(Some("irq".to_string()))
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(2u8, emulator.memory().debug_read().byte(0x0200), "interrupts");
+    assert_eq!(0x81u8, emulator.memory().debug_read().byte(0x0201), "rotated_out[0]");
+    assert_eq!(0x88u8, emulator.memory().debug_read().byte(0x0202), "rotated_out[1]");
+    assert_eq!(1u8, emulator.memory().debug_read().byte(0x0203), "rotated_out[2]");
+    assert_eq!(0x40u8, emulator.memory().debug_read().byte(0x0204), "rotated_out[3]");
+    assert_eq!(0x03u8, emulator.memory().debug_read().byte(0x0205), "wide_out lo");
+    assert_eq!(0x80u8, emulator.memory().debug_read().byte(0x0206), "wide_out hi");
+    assert_eq!(7u8, emulator.memory().debug_read().byte(0x0210), "poked[0]");
+    assert_eq!(8u8, emulator.memory().debug_read().byte(0x0211), "poked[1]");
+    assert_eq!(16u8, emulator.memory().debug_read().byte(0x0212), "poked[2]");
+    assert_eq!(16u8, emulator.memory().debug_read().byte(0x0218), "peeked_out[0]");
+    assert_eq!(23u8, emulator.memory().debug_read().byte(0x0219), "peeked_out[1]");
+    assert_eq!(6u8, emulator.memory().debug_read().byte(0x0220), "counter_out");
+}
+
+#[test]
+pub fn rotate_type_test() {
+    let program = "def main(): void\n    var flag: bool = true;\n    rol(flag);\nend\n";
+    match compile("rotate_type_test", program, false, false) {
+        Ok(_) => panic!("rotating a bool should fail to compile"),
+        Err(err) => assert_eq!(
+            "rotate_type_test:3:9: Can only rotate integers, but got bool",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn peek_address_type_test() {
+    let program = "def main(): void\n    var flag: bool = true;\n    poke(0x0200, peek(flag));\nend\n";
+    match compile("peek_address_type_test", program, false, false) {
+        Ok(_) => panic!("peeking at a bool should fail to compile"),
+        Err(err) => assert_eq!(
+            "peek_address_type_test:3:23: Can't use bool as an address",
+            format!("{}", err.0)
+        ),
+    }
+}
+
+#[test]
+pub fn poke_value_type_test() {
+    let program = "def main(): void\n    var wide: u16 = 0x0102;\n    poke(0x0200, wide);\nend\n";
+    match compile("poke_value_type_test", program, false, false) {
+        Ok(_) => panic!("poking a u16 should fail to compile"),
+        Err(err) => assert_eq!(
+            "poke_value_type_test:3:18: Can only poke u8 bytes, but got u16",
+            format!("{}", err.0)
+        ),
+    }
+}